@@ -0,0 +1,66 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use pole_client::pole::instructions::InitParams;
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+
+const SERUM_DEX_PROGRAM: &str = "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin";
+const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+/// `create_pool` parameters, read from a YAML or TOML file (by extension).
+#[derive(Deserialize, Debug)]
+pub struct PoolConfig {
+    pub name: String,
+    pub liquidity_cap: u64,
+    pub withdraw_fee_bips: u8,
+    pub port_iterate: u8,
+    pub port_reserve_percentage: u8,
+    pub port_min_deposit: u64,
+    pub swap_program: String,
+    pub reserve: String,
+    pub liquidity_mint: String,
+    pub port_lp_mint: String,
+    pub port_mint: String,
+    pub dex_market: String,
+    #[serde(default = "default_dex_program")]
+    pub dex_program: String,
+    #[serde(default = "default_token_program")]
+    pub token_program: String,
+}
+
+fn default_dex_program() -> String {
+    SERUM_DEX_PROGRAM.to_string()
+}
+
+fn default_token_program() -> String {
+    TOKEN_PROGRAM.to_string()
+}
+
+pub fn pubkey(value: &str) -> Result<Pubkey, Box<dyn Error>> {
+    Pubkey::from_str(value).map_err(|e| format!("invalid pubkey {}: {}", value, e).into())
+}
+
+impl PoolConfig {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Ok(toml::from_str(&content)?),
+            _ => Ok(serde_yaml::from_str(&content)?),
+        }
+    }
+
+    pub fn init_params(&self) -> Result<InitParams, Box<dyn Error>> {
+        Ok(InitParams {
+            liquidity_cap: self.liquidity_cap,
+            withdraw_fee_bips: self.withdraw_fee_bips,
+            port_iterate: self.port_iterate,
+            port_reserve_percentage: self.port_reserve_percentage,
+            port_min_deposit: self.port_min_deposit,
+            swap_program: pubkey(&self.swap_program)?,
+            reserve: pubkey(&self.reserve)?,
+        })
+    }
+}
@@ -0,0 +1,833 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use pole_client::instructions;
+use pole_client::instructions::PoolExtras;
+use pole_client::pda;
+use pole_client::pole::instructions::{FeeRecipient, FeeTier};
+use pole_client::pole::states::{
+    FeeSplit, RegistryPage, SellRoute, SubReward, POOL_ACTIVE, POOL_DEPRECATED, REGISTRY_PAGE_SIZE,
+};
+use pole_client::state::{
+    decode_fee_split, decode_registry, decode_registry_page, decode_sell_route, decode_sub_reward,
+    registered_pools, PoolInfo,
+};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+use solana_sdk::transaction::Transaction;
+use solana_sdk::{system_program, sysvar};
+use structopt::StructOpt;
+
+mod config;
+
+use config::{pubkey, PoolConfig};
+
+const PORT_LENDING_PROGRAM: &str = "Port7uDYB3wk6GJAw4KT1WpTeMtSu9bTcChBHkX2LfR";
+const PORT_STAKING_PROGRAM: &str = "stkarvwmSzv2BygN5e2LeTwimTczLWHCKPKGC2zVLiq";
+
+#[derive(StructOpt)]
+#[structopt(name = "pole-cli", about = "Pole pool administration")]
+struct Opts {
+    #[structopt(long, default_value = "https://api.mainnet-beta.solana.com")]
+    url: String,
+    /// Fee payer keypair, also the signer of owner instructions unless --owner is given
+    #[structopt(long, default_value = "~/.config/solana/id.json")]
+    keypair: String,
+    /// Pool owner, e.g. a multisig; the transaction is printed for it to sign
+    #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+    owner: Option<Pubkey>,
+    /// Sign with this blockhash instead of fetching one
+    #[structopt(long, parse(try_from_str = Hash::from_str))]
+    blockhash: Option<Hash>,
+    /// Print the base64 serialized transaction instead of sending it
+    #[structopt(long)]
+    sign_only: bool,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Create the pool registry, once per deployment; the signer, the program's upgrade authority, owns the registry
+    InitRegistry,
+    /// Add a page to the registry once the last one is full, signed by the registry owner
+    AddRegistryPage,
+    /// List a pool created before the registry, signed by the registry owner
+    RegisterPool {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+    },
+    /// Create a pool from a YAML or TOML config file
+    CreatePool {
+        #[structopt(long, parse(from_os_str))]
+        config: PathBuf,
+    },
+    /// Create the Port obligation and stake account of a pool
+    InitPortAccounts {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        lending_market: Pubkey,
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        staking_pool: Pubkey,
+        #[structopt(long, default_value = PORT_LENDING_PROGRAM, parse(try_from_str = Pubkey::from_str))]
+        port_lending_program: Pubkey,
+        #[structopt(long, default_value = PORT_STAKING_PROGRAM, parse(try_from_str = Pubkey::from_str))]
+        port_staking_program: Pubkey,
+    },
+    /// Upgrade the pool account to the current layout version
+    MigratePool {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+    },
+    /// Recreate a pool smaller than the current layout at its address, then run migrate-pool
+    ReallocPool {
+        /// The name the pool was created with, its address is derived from it
+        #[structopt(long)]
+        name: String,
+    },
+    /// Create the LP escrow and settled liquidity accounts of the withdraw queue
+    InitWithdrawQueue {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        liquidity_mint: Pubkey,
+    },
+    ChangeOwner {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        new_owner: Pubkey,
+    },
+    /// Claim the staking pool's second reward into a pool account, sold on `market` by sell_sub_reward
+    InitSubReward {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        mint: Pubkey,
+        /// Sub reward (coin) / liquidity mint (pc) market
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        market: Pubkey,
+    },
+    /// Sell harvests in two hops, from the harvest market's pc into the liquidity mint on `market`
+    InitSellRoute {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        market: Pubkey,
+        /// The harvest market's pc, e.g. USDC
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        route_mint: Pubkey,
+    },
+    /// Close the pool's open orders on the harvest market, once settled; harvesting stops until migrate-market
+    CloseOpenOrders {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        market: Pubkey,
+    },
+    /// Move harvesting to a new Serum market, closing the open orders on the current one
+    MigrateMarket {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        /// The current harvest market, not needed after close-open-orders
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        legacy_market: Option<Pubkey>,
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        market: Pubkey,
+    },
+    /// Move the balance of a token account owned by the pool authority, outside the pool config, to `target`
+    Sweep {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        source: Pubkey,
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        target: Pubkey,
+    },
+    /// Close a pool with no LP left and an unwound obligation, reclaiming its rent and deprecating it in the registry
+    ClosePool {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        /// The harvest market, not needed after close-open-orders
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        market: Option<Pubkey>,
+        /// Takes the PORT left in the pool
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        port_wallet: Pubkey,
+        /// Takes what is left in the sell route wallet, required when the pool has a sell route
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        route_wallet: Option<Pubkey>,
+        /// The sub reward market, required with --sub-reward-wallet
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        sub_reward_market: Option<Pubkey>,
+        /// Takes what is left in the sub reward supply, required when the pool has a sub reward
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        sub_reward_wallet: Option<Pubkey>,
+    },
+    ChangeFeeReceiver {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        fee_receiver: Pubkey,
+    },
+    ChangeLiquidityCap {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        #[structopt(long)]
+        cap: u64,
+    },
+    /// Collect fees in a pool-owned vault, split by change-fee-split; the current fee receiver gets 100% until then
+    InitFeeSplit {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        liquidity_mint: Pubkey,
+    },
+    /// Replace the fee recipients, bips have to sum to 10000
+    ChangeFeeSplit {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        /// `<liquidity token account>:<bips>`, up to 4
+        #[structopt(long = "recipient", parse(try_from_str = parse_fee_recipient))]
+        recipients: Vec<FeeRecipient>,
+    },
+    /// Pay out the fee vault to the fee split recipients
+    DistributeFees {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+    },
+    /// Share of the withdraw fee, in bips of the fee, paid to referrers
+    ChangeReferralFee {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        #[structopt(long)]
+        bips: u64,
+    },
+    /// Cap the principal each wallet may deposit, 0 removes the cap
+    ChangeWalletCap {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        #[structopt(long)]
+        cap: u64,
+    },
+    ChangeWithdrawFee {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        #[structopt(long)]
+        bips: u8,
+    },
+    ChangeMinDeposit {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        #[structopt(long)]
+        min_deposit: u64,
+    },
+    ChangeHarvestConfig {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        #[structopt(long)]
+        stale_slot: u64,
+        #[structopt(long)]
+        min_harvest_interval: u64,
+        #[structopt(long)]
+        harvest_bounty_bips: u64,
+    },
+    /// Replace the holding period withdraw fee schedule; no --tier restores the flat withdraw fee
+    ChangeFeeSchedule {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        /// `<held slots>:<bips>`, in ascending held slots starting at 0
+        #[structopt(long = "tier", parse(try_from_str = parse_fee_tier))]
+        tiers: Vec<FeeTier>,
+    },
+    /// Turn deposits from allowlisted wallet owners only on or off
+    ChangeAllowlistMode {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        #[structopt(long, parse(try_from_str))]
+        enabled: bool,
+    },
+    /// Allow the owner of liquidity wallets to deposit
+    AllowlistAdd {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        wallet: Pubkey,
+    },
+    /// Stop a wallet owner from depositing, it keeps redeeming
+    AllowlistRemove {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        wallet: Pubkey,
+    },
+    /// Mark the pool deprecated in the registry
+    DeprecatePool {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+    },
+    /// Remove the pool from the registry, signed by the registry owner
+    UnregisterPool {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+    },
+    /// List the registered pools
+    ListPools,
+    /// Print the pool configuration and state
+    Inspect {
+        #[structopt(long, parse(try_from_str = Pubkey::from_str))]
+        pool: Pubkey,
+    },
+}
+
+struct Cli {
+    rpc: RpcClient,
+    payer: Keypair,
+    owner: Pubkey,
+    blockhash: Option<Hash>,
+    sign_only: bool,
+}
+
+impl Cli {
+    fn fetch_pool(&self, address: &Pubkey) -> Result<PoolInfo, Box<dyn Error>> {
+        let data = self.rpc.get_account_data(address)?;
+        Ok(PoolInfo::decode(*address, &data)?)
+    }
+
+    /// The pool's SellRoute, or None before init-sell-route.
+    //the data of an account that may not exist
+    fn fetch_optional(&self, address: &Pubkey) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        Ok(self
+            .rpc
+            .get_account_with_commitment(address, self.rpc.commitment())?
+            .value
+            .map(|account| account.data))
+    }
+
+    fn fetch_sell_route(&self, pool: &Pubkey) -> Result<Option<SellRoute>, Box<dyn Error>> {
+        match self.fetch_optional(&pda::sell_route(pool).0)? {
+            Some(data) => Ok(Some(decode_sell_route(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn fetch_sub_reward(&self, pool: &Pubkey) -> Result<Option<SubReward>, Box<dyn Error>> {
+        match self.fetch_optional(&pda::sub_reward(pool).0)? {
+            Some(data) => Ok(Some(decode_sub_reward(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn fetch_fee_split(&self, pool: &Pubkey) -> Result<Option<FeeSplit>, Box<dyn Error>> {
+        match self.fetch_optional(&pda::fee_split(pool).0)? {
+            Some(data) => Ok(Some(decode_fee_split(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn registry_pages(&self) -> Result<Vec<RegistryPage>, Box<dyn Error>> {
+        let registry = decode_registry(&self.rpc.get_account_data(&pda::registry().0)?)?;
+        (0..registry.page_count)
+            .map(|index| {
+                let data = self.rpc.get_account_data(&pda::registry_page(index).0)?;
+                Ok(decode_registry_page(&data)?)
+            })
+            .collect()
+    }
+
+    /// The page listing `pool`, or None if it is not registered.
+    fn registry_page_of(&self, pool: &Pubkey) -> Result<Option<u64>, Box<dyn Error>> {
+        Ok(self
+            .registry_pages()?
+            .iter()
+            .find(|page| page.position(pool).is_some())
+            .map(|page| page.index))
+    }
+
+    /// The last page, if it has room for another pool.
+    fn free_registry_page(&self) -> Result<u64, Box<dyn Error>> {
+        let pages = self.registry_pages()?;
+        match pages.last() {
+            Some(page) if (page.pool_count as usize) < REGISTRY_PAGE_SIZE => Ok(page.index),
+            _ => Err("the registry is full, run add-registry-page".into()),
+        }
+    }
+
+    fn submit(
+        &self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), Box<dyn Error>> {
+        let blockhash = match self.blockhash {
+            Some(blockhash) => blockhash,
+            None => self.rpc.get_recent_blockhash()?.0,
+        };
+        let mut all_signers: Vec<&dyn Signer> = vec![&self.payer];
+        all_signers.extend(signers.iter().map(|signer| *signer as &dyn Signer));
+
+        let mut transaction = Transaction::new_with_payer(instructions, Some(&self.payer.pubkey()));
+        transaction.try_partial_sign(&all_signers, blockhash)?;
+        if self.sign_only || !transaction.is_signed() {
+            println!("{}", base64::encode(bincode::serialize(&transaction)?));
+        } else {
+            let signature = self.rpc.send_and_confirm_transaction(&transaction)?;
+            println!("Signature: {}", signature);
+        }
+        Ok(())
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn parse_fee_tier(tier: &str) -> Result<FeeTier, String> {
+    let (held_slots, bips) = tier
+        .split_once(':')
+        .ok_or_else(|| format!("expected <held slots>:<bips>, got {}", tier))?;
+    Ok(FeeTier {
+        held_slots: held_slots
+            .parse()
+            .map_err(|e| format!("{}: {}", held_slots, e))?,
+        bips: bips.parse().map_err(|e| format!("{}: {}", bips, e))?,
+    })
+}
+
+fn parse_fee_recipient(recipient: &str) -> Result<FeeRecipient, String> {
+    let (account, bips) = recipient
+        .split_once(':')
+        .ok_or_else(|| format!("expected <token account>:<bips>, got {}", recipient))?;
+    Ok(FeeRecipient {
+        recipient: Pubkey::from_str(account).map_err(|e| format!("{}: {}", account, e))?,
+        bips: bips.parse().map_err(|e| format!("{}: {}", bips, e))?,
+    })
+}
+
+fn create_pool(cli: &Cli, path: &Path) -> Result<(), Box<dyn Error>> {
+    let config = PoolConfig::load(path)?;
+    let (pole_pool, _) = pda::pole_pool(&config.name);
+    let (pole_authority, _) = pda::pole_authority(&pole_pool);
+    let lp_mint = Keypair::new();
+    let liquidity_supply = Keypair::new();
+    let port_lp_supply = Keypair::new();
+    let port_supply = Keypair::new();
+    let fee_receiver = Keypair::new();
+    let port_open_orders = Keypair::new();
+
+    let instruction = instructions::create_pool(
+        &config.name,
+        config.init_params()?,
+        pole_client::pole::accounts::CreatePool {
+            pole_pool,
+            lp_mint: lp_mint.pubkey(),
+            liquidity_supply: liquidity_supply.pubkey(),
+            port_lp_supply: port_lp_supply.pubkey(),
+            port_supply: port_supply.pubkey(),
+            fee_receiver: fee_receiver.pubkey(),
+            port_open_orders: port_open_orders.pubkey(),
+            liquidity_mint: pubkey(&config.liquidity_mint)?,
+            port_lp_mint: pubkey(&config.port_lp_mint)?,
+            port_mint: pubkey(&config.port_mint)?,
+            pole_authority,
+            user: cli.payer.pubkey(),
+            dex_market: pubkey(&config.dex_market)?,
+            token_program: pubkey(&config.token_program)?,
+            dex_program: pubkey(&config.dex_program)?,
+            pool_realloc: pda::pool_realloc(&pole_pool).0,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            clock: sysvar::clock::ID,
+        },
+        Some(pda::registry_page(cli.free_registry_page()?).0),
+    );
+    eprintln!("Pool: {}", pole_pool);
+    eprintln!("Authority: {}", pole_authority);
+    eprintln!("LP mint: {}", lp_mint.pubkey());
+    eprintln!("Fee receiver: {}", fee_receiver.pubkey());
+    cli.submit(
+        &[instruction],
+        &[
+            &lp_mint,
+            &liquidity_supply,
+            &port_lp_supply,
+            &port_supply,
+            &fee_receiver,
+            &port_open_orders,
+        ],
+    )
+}
+
+fn list_pools(cli: &Cli) -> Result<(), Box<dyn Error>> {
+    for entry in cli.registry_pages()?.iter().flat_map(registered_pools) {
+        let (pool, liquidity_mint, reserve) = (entry.pool, entry.liquidity_mint, entry.reserve);
+        let (status, created_slot) = (entry.status, entry.created_slot);
+        let status = match status {
+            POOL_ACTIVE => "active",
+            POOL_DEPRECATED => "deprecated",
+            _ => "unknown",
+        };
+        println!(
+            "{} liquidity_mint={} reserve={} status={} created_slot={}",
+            pool, liquidity_mint, reserve, status, created_slot
+        );
+    }
+    Ok(())
+}
+
+fn inspect(cli: &Cli, address: &Pubkey) -> Result<(), Box<dyn Error>> {
+    let pool = cli.fetch_pool(address)?;
+    let state = pool.state;
+    let (generic_config, port_config, serum_config) =
+        (state.generic_config, state.port_config, state.serum_config);
+    let (basic_state, port_state, harvest_config) =
+        (state.basic_state, state.port_state, state.harvest_config);
+    let (authority_version, layout_version) = (state.authority_version, state.layout_version);
+    println!("Pool: {}", pool.address);
+    println!("Layout version: {}", layout_version);
+    let allowlist_enabled = state.allowlist_enabled;
+    println!("Allowlist: {}", allowlist_enabled == 1);
+    let wallet_cap = state.wallet_cap;
+    println!("Wallet cap: {}", wallet_cap);
+    let referral_fee_bips = state.referral_fee_bips;
+    println!("Referral fee bips: {}", referral_fee_bips);
+    if let Some(sell_route) = cli.fetch_sell_route(&pool.address)? {
+        println!("SellRoute: {:#?}", sell_route);
+    }
+    println!(
+        "Authority: {} (version {})",
+        pool.authority()?,
+        authority_version
+    );
+    println!("GenericPoolConfig: {:#?}", generic_config);
+    println!("PortConfig: {:#?}", port_config);
+    println!("SerumConfig: {:#?}", serum_config);
+    println!("HarvestConfig: {:#?}", harvest_config);
+    println!("BasicState: {:#?}", basic_state);
+    println!("PortState: {:#?}", port_state);
+    let withdraw_queue = state.withdraw_queue;
+    println!("WithdrawQueue: {:#?}", withdraw_queue);
+    let reserved_liquidity = state.reserved_liquidity;
+    println!("Reserved liquidity: {}", reserved_liquidity);
+    let fee_schedule = state.fee_schedule;
+    println!("FeeSchedule: {:#?}", fee_schedule);
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let opts = Opts::from_args();
+    let payer = read_keypair_file(expand_home(&opts.keypair))
+        .map_err(|e| format!("unable to read keypair {}: {}", opts.keypair, e))?;
+    let cli = Cli {
+        rpc: RpcClient::new_with_commitment(opts.url.clone(), CommitmentConfig::confirmed()),
+        owner: opts.owner.unwrap_or_else(|| payer.pubkey()),
+        payer,
+        blockhash: opts.blockhash,
+        sign_only: opts.sign_only,
+    };
+
+    match opts.command {
+        Command::InitRegistry => cli.submit(&[instructions::init_registry(cli.owner)], &[]),
+        Command::AddRegistryPage => {
+            let registry = decode_registry(&cli.rpc.get_account_data(&pda::registry().0)?)?;
+            cli.submit(
+                &[instructions::add_registry_page(
+                    registry.page_count,
+                    cli.owner,
+                )],
+                &[],
+            )
+        }
+        Command::RegisterPool { pool } => {
+            if cli.registry_page_of(&pool)?.is_some() {
+                return Err("the pool is already registered".into());
+            }
+            let pool = cli.fetch_pool(&pool)?;
+            let page = cli.free_registry_page()?;
+            cli.submit(&[instructions::register_pool(&pool, page, cli.owner)], &[])
+        }
+        Command::CreatePool { config } => create_pool(&cli, &config),
+        Command::InitPortAccounts {
+            pool,
+            lending_market,
+            staking_pool,
+            port_lending_program,
+            port_staking_program,
+        } => {
+            let pool = cli.fetch_pool(&pool)?;
+            let obligation = Keypair::new();
+            let stake_account = Keypair::new();
+            let instruction = instructions::init_port_accounts(
+                &pool,
+                obligation.pubkey(),
+                stake_account.pubkey(),
+                port_lending_program,
+                port_staking_program,
+                lending_market,
+                staking_pool,
+                cli.owner,
+            )?;
+            eprintln!("Obligation: {}", obligation.pubkey());
+            eprintln!("Stake account: {}", stake_account.pubkey());
+            cli.submit(&[instruction], &[&obligation, &stake_account])
+        }
+        Command::MigratePool { pool } => {
+            cli.submit(&[instructions::migrate_pool(&pool, cli.owner)], &[])
+        }
+        Command::ReallocPool { name } => {
+            let (pool, _) = pda::pole_pool(&name);
+            //the closed pool is only deleted at the end of the first transaction
+            cli.submit(&[instructions::begin_pool_realloc(&pool, cli.owner)], &[])?;
+            cli.submit(&[instructions::finish_pool_realloc(&name, cli.owner)], &[])
+        }
+        Command::InitWithdrawQueue {
+            pool,
+            liquidity_mint,
+        } => {
+            let pool = cli.fetch_pool(&pool)?;
+            let lp_escrow = Keypair::new();
+            let settled_liquidity = Keypair::new();
+            let instruction = instructions::init_withdraw_queue(
+                &pool,
+                liquidity_mint,
+                lp_escrow.pubkey(),
+                settled_liquidity.pubkey(),
+                cli.owner,
+            )?;
+            eprintln!("LP escrow: {}", lp_escrow.pubkey());
+            eprintln!("Settled liquidity: {}", settled_liquidity.pubkey());
+            cli.submit(&[instruction], &[&lp_escrow, &settled_liquidity])
+        }
+        Command::ChangeOwner { pool, new_owner } => cli.submit(
+            &[instructions::change_owner(&pool, cli.owner, new_owner)],
+            &[],
+        ),
+        Command::InitSubReward { pool, mint, market } => {
+            let pool = cli.fetch_pool(&pool)?;
+            let (sub_reward_supply, open_orders) = (Keypair::new(), Keypair::new());
+            let instruction = instructions::init_sub_reward(
+                &pool,
+                mint,
+                sub_reward_supply.pubkey(),
+                open_orders.pubkey(),
+                market,
+                cli.owner,
+            )?;
+            eprintln!("Sub reward supply: {}", sub_reward_supply.pubkey());
+            cli.submit(&[instruction], &[&sub_reward_supply, &open_orders])
+        }
+        Command::InitSellRoute {
+            pool,
+            market,
+            route_mint,
+        } => {
+            let pool = cli.fetch_pool(&pool)?;
+            let (route_open_orders, route_wallet) = (Keypair::new(), Keypair::new());
+            let instruction = instructions::init_sell_route(
+                &pool,
+                market,
+                route_open_orders.pubkey(),
+                route_wallet.pubkey(),
+                route_mint,
+                cli.owner,
+            )?;
+            eprintln!("Route wallet: {}", route_wallet.pubkey());
+            cli.submit(&[instruction], &[&route_open_orders, &route_wallet])
+        }
+        Command::CloseOpenOrders { pool, market } => {
+            let pool = cli.fetch_pool(&pool)?;
+            cli.submit(
+                &[instructions::close_open_orders(&pool, market, cli.owner)?],
+                &[],
+            )
+        }
+        Command::MigrateMarket {
+            pool,
+            legacy_market,
+            market,
+        } => {
+            let pool = cli.fetch_pool(&pool)?;
+            if legacy_market.is_none()
+                && pool.state.serum_config.port_open_orders != Pubkey::default()
+            {
+                return Err("--legacy-market is required while the pool has open orders".into());
+            }
+            let port_open_orders = Keypair::new();
+            let instruction = instructions::migrate_market(
+                &pool,
+                legacy_market.unwrap_or(market),
+                port_open_orders.pubkey(),
+                market,
+                cli.owner,
+            )?;
+            eprintln!("Open orders: {}", port_open_orders.pubkey());
+            cli.submit(&[instruction], &[&port_open_orders])
+        }
+        Command::Sweep {
+            pool,
+            source,
+            target,
+        } => {
+            let pool = cli.fetch_pool(&pool)?;
+            cli.submit(
+                &[instructions::sweep(&pool, source, target, cli.owner)?],
+                &[],
+            )
+        }
+        Command::ClosePool {
+            pool,
+            market,
+            port_wallet,
+            route_wallet,
+            sub_reward_market,
+            sub_reward_wallet,
+        } => {
+            let pool = cli.fetch_pool(&pool)?;
+            let open_orders = pool.state.serum_config.port_open_orders;
+            if market.is_none() && open_orders != Pubkey::default() {
+                return Err("--market is required while the pool has open orders".into());
+            }
+            let sell_route = cli.fetch_sell_route(&pool.address)?;
+            if sell_route.is_some() && route_wallet.is_none() {
+                return Err("--route-wallet is required while the pool has a sell route".into());
+            }
+            let sub_reward = cli.fetch_sub_reward(&pool.address)?;
+            if sub_reward.is_some() && (sub_reward_market.is_none() || sub_reward_wallet.is_none())
+            {
+                return Err(
+                    "--sub-reward-market and --sub-reward-wallet are required while the pool has a sub reward"
+                        .into(),
+                );
+            }
+            let fee_split = cli.fetch_fee_split(&pool.address)?;
+            let extras = PoolExtras {
+                sell_route: sell_route.as_ref().zip(route_wallet),
+                sub_reward: sub_reward
+                    .as_ref()
+                    .zip(sub_reward_market.zip(sub_reward_wallet))
+                    .map(|(sub_reward, (market, wallet))| (sub_reward, market, wallet)),
+                fee_split: fee_split.as_ref(),
+            };
+            let registry_page = cli.registry_page_of(&pool.address)?.unwrap_or(0);
+            let instruction = instructions::close_pool(
+                &pool,
+                market.unwrap_or(pool.address),
+                port_wallet,
+                &extras,
+                registry_page,
+                cli.owner,
+            )?;
+            cli.submit(&[instruction], &[])
+        }
+        Command::ChangeFeeReceiver { pool, fee_receiver } => cli.submit(
+            &[instructions::change_fee_receiver(
+                &pool,
+                cli.owner,
+                fee_receiver,
+            )],
+            &[],
+        ),
+        Command::ChangeLiquidityCap { pool, cap } => cli.submit(
+            &[instructions::change_liquidity_cap(&pool, cli.owner, cap)],
+            &[],
+        ),
+        Command::InitFeeSplit {
+            pool,
+            liquidity_mint,
+        } => {
+            let pool = cli.fetch_pool(&pool)?;
+            let fee_vault = Keypair::new();
+            let instruction =
+                instructions::init_fee_split(&pool, liquidity_mint, fee_vault.pubkey(), cli.owner)?;
+            eprintln!("Fee vault: {}", fee_vault.pubkey());
+            cli.submit(&[instruction], &[&fee_vault])
+        }
+        Command::ChangeFeeSplit { pool, recipients } => cli.submit(
+            &[instructions::change_fee_split(&pool, cli.owner, recipients)],
+            &[],
+        ),
+        Command::DistributeFees { pool } => {
+            let pool = cli.fetch_pool(&pool)?;
+            let fee_split =
+                decode_fee_split(&cli.rpc.get_account_data(&pda::fee_split(&pool.address).0)?)?;
+            cli.submit(&[instructions::distribute_fees(&pool, &fee_split)?], &[])
+        }
+        Command::ChangeReferralFee { pool, bips } => cli.submit(
+            &[instructions::change_referral_fee(&pool, cli.owner, bips)],
+            &[],
+        ),
+        Command::ChangeWalletCap { pool, cap } => cli.submit(
+            &[instructions::change_wallet_cap(&pool, cli.owner, cap)],
+            &[],
+        ),
+        Command::ChangeWithdrawFee { pool, bips } => cli.submit(
+            &[instructions::change_withdraw_fee(&pool, cli.owner, bips)],
+            &[],
+        ),
+        Command::ChangeMinDeposit { pool, min_deposit } => cli.submit(
+            &[instructions::change_min_deposit(
+                &pool,
+                cli.owner,
+                min_deposit,
+            )],
+            &[],
+        ),
+        Command::ChangeHarvestConfig {
+            pool,
+            stale_slot,
+            min_harvest_interval,
+            harvest_bounty_bips,
+        } => cli.submit(
+            &[instructions::change_harvest_config(
+                &pool,
+                cli.owner,
+                stale_slot,
+                min_harvest_interval,
+                harvest_bounty_bips,
+            )],
+            &[],
+        ),
+        Command::ChangeFeeSchedule { pool, tiers } => cli.submit(
+            &[instructions::change_fee_schedule(&pool, cli.owner, tiers)],
+            &[],
+        ),
+        Command::ChangeAllowlistMode { pool, enabled } => cli.submit(
+            &[instructions::change_allowlist_mode(
+                &pool, cli.owner, enabled,
+            )],
+            &[],
+        ),
+        Command::AllowlistAdd { pool, wallet } => cli.submit(
+            &[instructions::add_allowlist_entry(&pool, cli.owner, wallet)],
+            &[],
+        ),
+        Command::AllowlistRemove { pool, wallet } => cli.submit(
+            &[instructions::remove_allowlist_entry(
+                &pool, cli.owner, wallet,
+            )],
+            &[],
+        ),
+        Command::DeprecatePool { pool } => {
+            let page = cli
+                .registry_page_of(&pool)?
+                .ok_or("the pool is not registered")?;
+            cli.submit(&[instructions::deprecate_pool(&pool, page, cli.owner)], &[])
+        }
+        Command::UnregisterPool { pool } => {
+            let page = cli
+                .registry_page_of(&pool)?
+                .ok_or("the pool is not registered")?;
+            cli.submit(&[instructions::unregister_pool(pool, page, cli.owner)], &[])
+        }
+        Command::ListPools => list_pools(&cli),
+        Command::Inspect { pool } => inspect(&cli, &pool),
+    }
+}
@@ -0,0 +1,23 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientError {
+    AccountDataTooSmall,
+    InvalidDiscriminator,
+    InvalidAccountData,
+    InvalidAuthority,
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            ClientError::AccountDataTooSmall => "Account data is too small",
+            ClientError::InvalidDiscriminator => "Account discriminator does not match",
+            ClientError::InvalidAccountData => "Unable to deserialize account data",
+            ClientError::InvalidAuthority => "Unable to derive pole authority",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl std::error::Error for ClientError {}
@@ -0,0 +1,82 @@
+use anchor_lang::{AnchorDeserialize, Discriminator};
+use pole::event::{
+    DidCompound, DidDeposit, DidPayReferral, DidRedeem, DidRefer, DidSell, DidStake, DidUnstake,
+    DidZap, DidZapOut,
+};
+use solana_sdk::pubkey::Pubkey;
+
+const PROGRAM_LOG: &str = "Program log: ";
+
+pub enum PoleEvent {
+    Deposit(DidDeposit),
+    Redeem(DidRedeem),
+    Sell(DidSell),
+    Compound(DidCompound),
+    Stake(DidStake),
+    Unstake(DidUnstake),
+    Refer(DidRefer),
+    PayReferral(DidPayReferral),
+    Zap(DidZap),
+    ZapOut(DidZapOut),
+}
+
+fn decode<T: AnchorDeserialize + Discriminator>(data: &[u8]) -> Option<T> {
+    if data.len() < 8 || data[..8] != T::discriminator() {
+        return None;
+    }
+    T::deserialize(&mut &data[8..]).ok()
+}
+
+/// Decodes a single base64 `emit!` payload, `None` if it is not a pole event.
+pub fn decode_event(data: &[u8]) -> Option<PoleEvent> {
+    decode::<DidDeposit>(data)
+        .map(PoleEvent::Deposit)
+        .or_else(|| decode::<DidRedeem>(data).map(PoleEvent::Redeem))
+        .or_else(|| decode::<DidSell>(data).map(PoleEvent::Sell))
+        .or_else(|| decode::<DidCompound>(data).map(PoleEvent::Compound))
+        .or_else(|| decode::<DidStake>(data).map(PoleEvent::Stake))
+        .or_else(|| decode::<DidUnstake>(data).map(PoleEvent::Unstake))
+        .or_else(|| decode::<DidRefer>(data).map(PoleEvent::Refer))
+        .or_else(|| decode::<DidPayReferral>(data).map(PoleEvent::PayReferral))
+        .or_else(|| decode::<DidZap>(data).map(PoleEvent::Zap))
+        .or_else(|| decode::<DidZapOut>(data).map(PoleEvent::ZapOut))
+}
+
+/// Collects the events emitted by pole in a transaction's log messages,
+/// ignoring logs written by the programs pole invokes.
+pub fn parse_events(logs: &[String]) -> Vec<PoleEvent> {
+    let pole_id = pole::ID.to_string();
+    let mut stack: Vec<bool> = Vec::new();
+    let mut events = Vec::new();
+    for log in logs {
+        if let Some(rest) = log.strip_prefix("Program ") {
+            let mut words = rest.split_whitespace();
+            let program = words.next().unwrap_or_default();
+            match words.next() {
+                Some("invoke") => {
+                    stack.push(program == pole_id);
+                    continue;
+                }
+                Some("success") | Some("failed:") => {
+                    if program.parse::<Pubkey>().is_ok() {
+                        stack.pop();
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if stack.last() != Some(&true) {
+            continue;
+        }
+        if let Some(payload) = log.strip_prefix(PROGRAM_LOG) {
+            if let Some(event) = base64::decode(payload)
+                .ok()
+                .and_then(|data| decode_event(&data))
+            {
+                events.push(event);
+            }
+        }
+    }
+    events
+}
@@ -0,0 +1,1461 @@
+use anchor_lang::{InstructionData, ToAccountMetas};
+use pole::instructions::{FeeRecipient, FeeTier, InitParams};
+use pole::states::{FeeSplit, SellRoute, SubReward};
+use port_variable_rate_lending_instructions as port_lending;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{system_program, sysvar};
+
+use crate::error::ClientError;
+use crate::pda;
+use crate::state::{PoolInfo, PortReserve, PortStaking, SerumMarket};
+
+fn pole_instruction(
+    accounts: impl ToAccountMetas,
+    data: impl InstructionData,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let mut accounts = accounts.to_account_metas(None);
+    accounts.extend(remaining_accounts);
+    Instruction {
+        program_id: pole::ID,
+        accounts,
+        data: data.data(),
+    }
+}
+
+//the user's UserPosition, when tracked, goes ahead of any other remaining account; deposits and
+//redeems key it by the owner of the liquidity wallet, taken to be the transfer authority
+fn position_accounts(pool: &Pubkey, user: &Pubkey, tracked: bool) -> Vec<AccountMeta> {
+    if tracked {
+        vec![AccountMeta::new(pda::user_position(pool, user).0, false)]
+    } else {
+        vec![]
+    }
+}
+
+//the referrer of the position or withdraw request goes after the oracle
+fn referrer_accounts(referrer: Option<Pubkey>) -> Vec<AccountMeta> {
+    referrer
+        .map(|referrer| AccountMeta::new(referrer, false))
+        .into_iter()
+        .collect()
+}
+
+fn market_accounts(market: &SerumMarket, open_orders: Pubkey) -> pole::accounts::MarketAccounts {
+    pole::accounts::MarketAccounts {
+        market: market.market,
+        open_orders,
+        request_queue: market.request_queue,
+        event_queue: market.event_queue,
+        bids: market.bids,
+        asks: market.asks,
+        coin_vault: market.coin_vault,
+        pc_vault: market.pc_vault,
+        vault_signer: market.vault_signer,
+    }
+}
+
+//a redeem swapping its payout passes these last, after the referrer
+fn zap_out_accounts(pool: &PoolInfo, market: &SerumMarket, output: &ZapToken) -> Vec<AccountMeta> {
+    pole::accounts::ZapOutAccounts {
+        market_accounts: market_accounts(market, output.open_orders),
+        user_output_wallet: output.wallet,
+        dex_program: pool.state.serum_config.dex_program,
+        swap_program: pool.state.serum_config.swap_program,
+        rent: sysvar::rent::ID,
+    }
+    .to_account_metas(None)
+}
+
+//the first leg of a deposit swapping into the liquidity passes these last, after the oracle
+fn zap_in_accounts(pool: &PoolInfo, market: &SerumMarket, source: &ZapToken) -> Vec<AccountMeta> {
+    pole::accounts::ZapInAccounts {
+        market_accounts: market_accounts(market, source.open_orders),
+        user_source_wallet: source.wallet,
+        source_mint: source.mint,
+        dex_program: pool.state.serum_config.dex_program,
+        swap_program: pool.state.serum_config.swap_program,
+        rent: sysvar::rent::ID,
+    }
+    .to_account_metas(None)
+}
+
+fn oracle_accounts(reserve: &PortReserve) -> Vec<AccountMeta> {
+    reserve
+        .oracle
+        .iter()
+        .map(|oracle| AccountMeta::new_readonly(*oracle, false))
+        .collect()
+}
+
+pub fn port_lending_accounts(
+    pool: &PoolInfo,
+    reserve: &PortReserve,
+) -> pole::accounts::PortLendingAccounts {
+    let port_config = &pool.state.port_config;
+    pole::accounts::PortLendingAccounts {
+        user_lp_wallet: port_config.port_lp_supply,
+        lp_mint: reserve.collateral_mint,
+        reserve_lp_wallet: reserve.collateral_supply,
+        liquidity_mint: reserve.liquidity_mint,
+        reserve_liquidity_wallet: reserve.liquidity_supply,
+        obligation: port_config.obligation,
+        reserve: reserve.address,
+        reserve_fee: reserve.liquidity_fee_receiver,
+        stake_account: port_config.stake_account,
+        staking_pool: reserve.staking_pool,
+        lending_market: reserve.lending_market,
+        lending_market_authority: pda::lending_market_authority(
+            &reserve.lending_market,
+            &port_config.port_lending_program,
+        ),
+        port_lending_program: port_config.port_lending_program,
+        port_staking_program: port_config.port_staking_program,
+    }
+}
+
+pub fn port_staking_accounts(
+    pool: &PoolInfo,
+    staking: &PortStaking,
+) -> pole::accounts::PortStakingAccounts {
+    let port_config = &pool.state.port_config;
+    pole::accounts::PortStakingAccounts {
+        staking_program_authority: pda::staking_program_authority(
+            &staking.staking_pool,
+            &port_config.port_staking_program,
+        ),
+        port_staking_program: port_config.port_staking_program,
+        stake_account: port_config.stake_account,
+        staking_pool: staking.staking_pool,
+        reward_supply: staking.reward_supply,
+    }
+}
+
+/// What a reward claim needs to pay the sub reward too, nothing without a SubReward.
+fn sub_reward_accounts(
+    pool: &PoolInfo,
+    staking: &PortStaking,
+    sub_reward: Option<&SubReward>,
+) -> Vec<AccountMeta> {
+    match (sub_reward, staking.sub_reward_supply) {
+        (Some(sub_reward), Some(sub_reward_supply)) => vec![
+            AccountMeta::new_readonly(pda::sub_reward(&pool.address).0, false),
+            AccountMeta::new(sub_reward_supply, false),
+            AccountMeta::new(sub_reward.supply, false),
+        ],
+        _ => vec![],
+    }
+}
+
+pub fn refresh_reserve(pool: &PoolInfo, reserve: &PortReserve) -> Instruction {
+    port_lending::instruction::refresh_reserve(
+        pool.state.port_config.port_lending_program,
+        reserve.address,
+        reserve.oracle.into(),
+    )
+}
+
+/// The obligation only ever holds the pool reserve, once as collateral and once as borrow.
+pub fn refresh_obligation(
+    pool: &PoolInfo,
+    reserve: &PortReserve,
+    deposits_count: usize,
+    borrows_count: usize,
+) -> Instruction {
+    port_lending::instruction::refresh_obligation(
+        pool.state.port_config.port_lending_program,
+        pool.state.port_config.obligation,
+        vec![reserve.address; deposits_count + borrows_count],
+    )
+}
+
+/// `registry_page` lists the pool, without it the registry owner runs `register_pool` later.
+pub fn create_pool(
+    name: &str,
+    init_params: InitParams,
+    accounts: pole::accounts::CreatePool,
+    registry_page: Option<Pubkey>,
+) -> Instruction {
+    let (_, pda_bump) = pda::pole_pool(name);
+    let (_, authority_bump) = pda::pole_authority(&accounts.pole_pool);
+    pole_instruction(
+        accounts,
+        pole::instruction::CreatePool {
+            authority_bump,
+            _name: name.to_string(),
+            _pda_bump: pda_bump,
+            init_params,
+        },
+        registry_page
+            .map(|page| vec![AccountMeta::new(page, false)])
+            .unwrap_or_default(),
+    )
+}
+
+/// `owner` must be the upgrade authority of the program.
+pub fn init_registry(owner: Pubkey) -> Instruction {
+    let (registry, bump) = pda::registry();
+    let (registry_page, page_bump) = pda::registry_page(0);
+    pole_instruction(
+        pole::accounts::InitRegistry {
+            registry,
+            registry_page,
+            program: pole::ID,
+            program_data: pda::program_data(),
+            owner,
+            system_program: system_program::ID,
+        },
+        pole::instruction::InitRegistry { bump, page_bump },
+        vec![],
+    )
+}
+
+/// `page_index` is the registry's current `page_count`.
+pub fn add_registry_page(page_index: u64, registry_owner: Pubkey) -> Instruction {
+    let (registry_page, bump) = pda::registry_page(page_index);
+    pole_instruction(
+        pole::accounts::AddRegistryPage {
+            registry: pda::registry().0,
+            registry_page,
+            owner: registry_owner,
+            system_program: system_program::ID,
+        },
+        pole::instruction::AddRegistryPage { bump },
+        vec![],
+    )
+}
+
+pub fn register_pool(pool: &PoolInfo, page_index: u64, registry_owner: Pubkey) -> Instruction {
+    pole_instruction(
+        pole::accounts::RegisterPool {
+            pole_pool: pool.address,
+            liquidity_supply: pool.state.generic_config.liquidity_supply,
+            registry: pda::registry().0,
+            registry_page: pda::registry_page(page_index).0,
+            owner: registry_owner,
+            clock: sysvar::clock::ID,
+        },
+        pole::instruction::RegisterPool {},
+        vec![],
+    )
+}
+
+pub fn deprecate_pool(pool: &Pubkey, page_index: u64, owner: Pubkey) -> Instruction {
+    pole_instruction(
+        pole::accounts::DeprecatePool {
+            pole_pool: *pool,
+            registry_page: pda::registry_page(page_index).0,
+            owner,
+        },
+        pole::instruction::DeprecatePool {},
+        vec![],
+    )
+}
+
+pub fn unregister_pool(pool: Pubkey, page_index: u64, registry_owner: Pubkey) -> Instruction {
+    pole_instruction(
+        pole::accounts::UnregisterPool {
+            registry: pda::registry().0,
+            registry_page: pda::registry_page(page_index).0,
+            owner: registry_owner,
+        },
+        pole::instruction::UnregisterPool { pool },
+        vec![],
+    )
+}
+
+pub fn init_port_accounts(
+    pool: &PoolInfo,
+    obligation: Pubkey,
+    stake_account: Pubkey,
+    port_lending_program: Pubkey,
+    port_staking_program: Pubkey,
+    port_lending_market: Pubkey,
+    staking_pool: Pubkey,
+    user: Pubkey,
+) -> Result<Instruction, ClientError> {
+    Ok(pole_instruction(
+        pole::accounts::InitPortAccounts {
+            pole_pool: pool.address,
+            obligation,
+            stake_account,
+            pole_authority: pool.authority()?,
+            user,
+            token_program: pool.state.generic_config.token_program,
+            port_lending_program,
+            port_staking_program,
+            port_lending_market,
+            staking_pool,
+            rent: sysvar::rent::ID,
+            clock: sysvar::clock::ID,
+            system_program: system_program::ID,
+        },
+        pole::instruction::InitPortAccounts {},
+        vec![],
+    ))
+}
+
+/// Once per user and pool, before the first deposit. `user` owns `user_liquidity_wallet`,
+/// the position is keyed by that owner.
+pub fn create_user_position(
+    pool: &Pubkey,
+    user_liquidity_wallet: Pubkey,
+    user: Pubkey,
+) -> Instruction {
+    let (user_position, bump) = pda::user_position(pool, &user);
+    pole_instruction(
+        pole::accounts::CreateUserPosition {
+            pole_pool: *pool,
+            user_position,
+            user_liquidity_wallet,
+            user,
+            system_program: system_program::ID,
+        },
+        pole::instruction::CreateUserPosition { _bump: bump },
+        vec![],
+    )
+}
+
+/// `referrer` is a liquidity token account, only the first one set on a position sticks.
+pub fn set_referrer(pool: &PoolInfo, referrer: Pubkey, user: Pubkey) -> Instruction {
+    let generic_config = &pool.state.generic_config;
+    pole_instruction(
+        pole::accounts::SetReferrer {
+            pole_pool: pool.address,
+            user_position: pda::user_position(&pool.address, &user).0,
+            referrer_fee_account: referrer,
+            liquidity_supply: generic_config.liquidity_supply,
+            user,
+            token_program: generic_config.token_program,
+        },
+        pole::instruction::SetReferrer {},
+        vec![],
+    )
+}
+
+/// The token a zap swaps out of or into: its mint, the user's token account of it and the user's
+/// open orders account on the market trading it against the liquidity mint.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ZapToken {
+    pub mint: Pubkey,
+    pub wallet: Pubkey,
+    pub open_orders: Pubkey,
+}
+
+/// `verify_deposit` followed by exactly `port_iterate` `deposit_liquidity` legs,
+/// to be sent as a single transaction. `tracked` deposits are credited to the user's
+/// [`create_user_position`], which pools with a wallet cap and a `referrer` require.
+/// With `zap_in`, `amount` is of its source token: the first leg swaps it into
+/// `user_liquidity_wallet`, failing below its minimum liquidity out, and deposits what it returned.
+pub fn deposit(
+    pool: &PoolInfo,
+    reserve: &PortReserve,
+    user_liquidity_wallet: Pubkey,
+    user_lp_wallet: Pubkey,
+    user_transfer_authority: Pubkey,
+    amount: u64,
+    min_lp_out: u64,
+    tracked: bool,
+    referrer: Option<Pubkey>,
+    zap_in: Option<(&SerumMarket, &ZapToken, u64)>,
+) -> Result<Vec<Instruction>, ClientError> {
+    let generic_config = &pool.state.generic_config;
+    let pole_authority = pool.authority()?;
+    let accounts = || pole::accounts::DepositLiquidity {
+        pole_pool: pool.address,
+        pole_authority,
+        pole_liquidity_accounts: pole::accounts::PoleLiquidityAccounts {
+            user_liquidity_wallet,
+            pole_liquidity_wallet: generic_config.liquidity_supply,
+        },
+        pole_lp_accounts: pole::accounts::PoleLPAccounts {
+            lp_mint: generic_config.lp_mint,
+            user_lp_wallet,
+        },
+        port_accounts: port_lending_accounts(pool, reserve),
+        user_transfer_authority,
+        token_program: generic_config.token_program,
+        clock: sysvar::clock::ID,
+    };
+
+    //allowlisted pools check the entry of the wallet owner, taken to be the transfer authority
+    let allowlist_accounts = if pool.state.allowlist_enabled == 1 {
+        vec![
+            AccountMeta::new_readonly(user_liquidity_wallet, false),
+            AccountMeta::new_readonly(
+                pda::allowlist_entry(&pool.address, &user_transfer_authority).0,
+                false,
+            ),
+        ]
+    } else {
+        vec![]
+    };
+    let mut instructions: Vec<Instruction> = referrer
+        .map(|referrer| set_referrer(pool, referrer, user_transfer_authority))
+        .into_iter()
+        .collect();
+    instructions.push(pole_instruction(
+        pole::accounts::VerifyDeposit {
+            pole_pool: pool.address,
+            transaction_info: sysvar::instructions::ID,
+        },
+        pole::instruction::VerifyDeposit {},
+        allowlist_accounts,
+    ));
+    for leg in 0..pool.port_iterate() {
+        let (amount, min_lp_out, zap_in) = if leg == 0 {
+            (amount, min_lp_out, zap_in)
+        } else {
+            (0, 0, None)
+        };
+        let mut remaining_accounts =
+            position_accounts(&pool.address, &user_transfer_authority, tracked);
+        remaining_accounts.extend(oracle_accounts(reserve));
+        if let Some((market, source, _)) = zap_in {
+            remaining_accounts.extend(zap_in_accounts(pool, market, source));
+        }
+        instructions.push(pole_instruction(
+            accounts(),
+            pole::instruction::DepositLiquidity {
+                amount,
+                min_lp_out,
+                zap_in_min_liquidity_out: zap_in.map(|(_, _, min_liquidity_out)| min_liquidity_out),
+            },
+            remaining_accounts,
+        ));
+    }
+    Ok(instructions)
+}
+
+/// `verify_redeem` followed by exactly `port_iterate` `redeem_liquidity` legs,
+/// to be sent as a single transaction. Untracked LP pays the first tier of the fee schedule,
+/// `referrer` is the position's referrer, required when it has one. With `zap_out`, the leg
+/// paying out swaps the payout into the output token, failing below its minimum output.
+pub fn redeem(
+    pool: &PoolInfo,
+    reserve: &PortReserve,
+    user_liquidity_wallet: Pubkey,
+    user_lp_wallet: Pubkey,
+    user_transfer_authority: Pubkey,
+    amount: u64,
+    min_liquidity_out: u64,
+    tracked: bool,
+    referrer: Option<Pubkey>,
+    zap_out: Option<(&SerumMarket, &ZapToken, u64)>,
+) -> Result<Vec<Instruction>, ClientError> {
+    let generic_config = &pool.state.generic_config;
+    let pole_authority = pool.authority()?;
+    let accounts = || pole::accounts::RedeemLiquidity {
+        pole_pool: pool.address,
+        pole_authority,
+        pole_fee_account: generic_config.fee_receiver,
+        pole_liquidity_accounts: pole::accounts::PoleLiquidityAccounts {
+            user_liquidity_wallet,
+            pole_liquidity_wallet: generic_config.liquidity_supply,
+        },
+        pole_lp_accounts: pole::accounts::PoleLPAccounts {
+            lp_mint: generic_config.lp_mint,
+            user_lp_wallet,
+        },
+        port_accounts: port_lending_accounts(pool, reserve),
+        user_transfer_authority,
+        token_program: generic_config.token_program,
+        clock: sysvar::clock::ID,
+    };
+
+    let mut instructions = vec![pole_instruction(
+        pole::accounts::VerifyRedeem {
+            pole_pool: pool.address,
+            transaction_info: sysvar::instructions::ID,
+        },
+        pole::instruction::VerifyRedeem {},
+        vec![],
+    )];
+    for leg in 0..pool.port_iterate() {
+        let (amount, min_liquidity_out) = if leg == 0 {
+            (amount, min_liquidity_out)
+        } else {
+            (0, 0)
+        };
+        let mut remaining_accounts =
+            position_accounts(&pool.address, &user_transfer_authority, tracked);
+        remaining_accounts.extend(oracle_accounts(reserve));
+        remaining_accounts.extend(referrer_accounts(referrer));
+        if let Some((market, output, _)) = zap_out {
+            remaining_accounts.extend(zap_out_accounts(pool, market, output));
+        }
+        instructions.push(pole_instruction(
+            accounts(),
+            pole::instruction::RedeemLiquidity {
+                amount,
+                min_liquidity_out,
+                zap_out_min_output: zap_out.map(|(_, _, min_output)| min_output),
+            },
+            remaining_accounts,
+        ));
+    }
+    Ok(instructions)
+}
+
+/// One leg of an interrupted deposit or redeem; the wallets must be the ones
+/// approved by the interrupted transaction. The reserve has to be refreshed first.
+/// `depositor`, the owner of the wallets, has its position credited when it is tracked.
+pub fn recover_port_state(
+    pool: &PoolInfo,
+    reserve: &PortReserve,
+    user_liquidity_wallet: Pubkey,
+    user_lp_wallet: Pubkey,
+    depositor: Option<Pubkey>,
+    owner: Pubkey,
+) -> Result<Instruction, ClientError> {
+    let generic_config = &pool.state.generic_config;
+    Ok(pole_instruction(
+        pole::accounts::RecoverPortState {
+            pole_pool: pool.address,
+            pole_authority: pool.authority()?,
+            pole_fee_account: generic_config.fee_receiver,
+            pole_liquidity_accounts: pole::accounts::PoleLiquidityAccounts {
+                user_liquidity_wallet,
+                pole_liquidity_wallet: generic_config.liquidity_supply,
+            },
+            pole_lp_accounts: pole::accounts::PoleLPAccounts {
+                lp_mint: generic_config.lp_mint,
+                user_lp_wallet,
+            },
+            port_accounts: port_lending_accounts(pool, reserve),
+            owner,
+            token_program: generic_config.token_program,
+            clock: sysvar::clock::ID,
+        },
+        pole::instruction::RecoverPortState {},
+        depositor
+            .map(|depositor| position_accounts(&pool.address, &depositor, true))
+            .unwrap_or_default(),
+    ))
+}
+
+/// `route`, the pool's SellRoute and its market, is required when `market`'s pc is not the
+/// liquidity mint, see [`init_sell_route`]; `sub_reward` claims the staking pool's second reward
+/// as well, see [`init_sub_reward`].
+pub fn claim_and_sell(
+    pool: &PoolInfo,
+    market: &SerumMarket,
+    staking: &PortStaking,
+    harvester_liquidity_wallet: Pubkey,
+    route: Option<(&SellRoute, &SerumMarket)>,
+    sub_reward: Option<&SubReward>,
+) -> Result<Instruction, ClientError> {
+    let state = &pool.state;
+    let mut remaining_accounts = match route {
+        Some((route, route_market)) => vec![
+            AccountMeta::new_readonly(pda::sell_route(&pool.address).0, false),
+            AccountMeta::new(route.market, false),
+            AccountMeta::new(route.open_orders, false),
+            AccountMeta::new(route_market.request_queue, false),
+            AccountMeta::new(route_market.event_queue, false),
+            AccountMeta::new(route_market.bids, false),
+            AccountMeta::new(route_market.asks, false),
+            AccountMeta::new(route_market.coin_vault, false),
+            AccountMeta::new(route_market.pc_vault, false),
+            AccountMeta::new_readonly(route_market.vault_signer, false),
+            AccountMeta::new(route.wallet, false),
+        ],
+        None => vec![],
+    };
+    remaining_accounts.extend(sub_reward_accounts(pool, staking, sub_reward));
+    Ok(pole_instruction(
+        pole::accounts::ClaimAndSell {
+            pole_pool: pool.address,
+            pole_authority: pool.authority()?,
+            liquidity_supply: state.generic_config.liquidity_supply,
+            harvester_liquidity_wallet,
+            port_supply: state.port_config.port_supply,
+            market_accounts: pole::accounts::MarketAccounts {
+                market: market.market,
+                open_orders: state.serum_config.port_open_orders,
+                request_queue: market.request_queue,
+                event_queue: market.event_queue,
+                bids: market.bids,
+                asks: market.asks,
+                coin_vault: market.coin_vault,
+                pc_vault: market.pc_vault,
+                vault_signer: market.vault_signer,
+            },
+            port_accounts: port_staking_accounts(pool, staking),
+            port_mint: state.port_config.port_token_mint,
+            token_program: state.generic_config.token_program,
+            dex_program: state.serum_config.dex_program,
+            swap_program: state.serum_config.swap_program,
+            clock: sysvar::clock::ID,
+            rent: sysvar::rent::ID,
+        },
+        pole::instruction::ClaimAndSell {},
+        remaining_accounts,
+    ))
+}
+
+/// `sub_reward_supply` and `sub_reward_open_orders` are fresh keypairs that have to sign,
+/// `dex_market` trades the sub reward (coin) against the liquidity mint (pc).
+pub fn init_sub_reward(
+    pool: &PoolInfo,
+    sub_reward_mint: Pubkey,
+    sub_reward_supply: Pubkey,
+    sub_reward_open_orders: Pubkey,
+    dex_market: Pubkey,
+    owner: Pubkey,
+) -> Result<Instruction, ClientError> {
+    let state = &pool.state;
+    let (sub_reward, bump) = pda::sub_reward(&pool.address);
+    Ok(pole_instruction(
+        pole::accounts::InitSubReward {
+            pole_pool: pool.address,
+            pole_authority: pool.authority()?,
+            sub_reward,
+            sub_reward_supply,
+            sub_reward_mint,
+            sub_reward_open_orders,
+            dex_market,
+            owner,
+            token_program: state.generic_config.token_program,
+            dex_program: state.serum_config.dex_program,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        pole::instruction::InitSubReward { _bump: bump },
+        vec![],
+    ))
+}
+
+pub fn sell_sub_reward(
+    pool: &PoolInfo,
+    sub_reward: &SubReward,
+    market: &SerumMarket,
+    harvester_liquidity_wallet: Pubkey,
+) -> Result<Instruction, ClientError> {
+    let state = &pool.state;
+    Ok(pole_instruction(
+        pole::accounts::SellSubReward {
+            pole_pool: pool.address,
+            pole_authority: pool.authority()?,
+            sub_reward: pda::sub_reward(&pool.address).0,
+            liquidity_supply: state.generic_config.liquidity_supply,
+            harvester_liquidity_wallet,
+            sub_reward_supply: sub_reward.supply,
+            sub_reward_mint: sub_reward.mint,
+            market_accounts: pole::accounts::MarketAccounts {
+                market: market.market,
+                open_orders: sub_reward.open_orders,
+                request_queue: market.request_queue,
+                event_queue: market.event_queue,
+                bids: market.bids,
+                asks: market.asks,
+                coin_vault: market.coin_vault,
+                pc_vault: market.pc_vault,
+                vault_signer: market.vault_signer,
+            },
+            token_program: state.generic_config.token_program,
+            dex_program: state.serum_config.dex_program,
+            swap_program: state.serum_config.swap_program,
+            clock: sysvar::clock::ID,
+            rent: sysvar::rent::ID,
+        },
+        pole::instruction::SellSubReward {},
+        vec![],
+    ))
+}
+
+/// `route_open_orders` and `route_wallet` are fresh keypairs that have to sign, `route_mint` is the
+/// harvest market's pc.
+pub fn init_sell_route(
+    pool: &PoolInfo,
+    route_market: Pubkey,
+    route_open_orders: Pubkey,
+    route_wallet: Pubkey,
+    route_mint: Pubkey,
+    owner: Pubkey,
+) -> Result<Instruction, ClientError> {
+    let state = &pool.state;
+    let (sell_route, bump) = pda::sell_route(&pool.address);
+    Ok(pole_instruction(
+        pole::accounts::InitSellRoute {
+            pole_pool: pool.address,
+            pole_authority: pool.authority()?,
+            sell_route,
+            route_open_orders,
+            route_market,
+            route_wallet,
+            route_mint,
+            owner,
+            token_program: state.generic_config.token_program,
+            dex_program: state.serum_config.dex_program,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        pole::instruction::InitSellRoute { _bump: bump },
+        vec![],
+    ))
+}
+
+fn settle(
+    pool: &PoolInfo,
+    market: &SerumMarket,
+    open_orders: Pubkey,
+    coin_wallet: Pubkey,
+    pc_wallet: Pubkey,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Result<Instruction, ClientError> {
+    let state = &pool.state;
+    Ok(pole_instruction(
+        pole::accounts::SettleOpenOrders {
+            pole_pool: pool.address,
+            pole_authority: pool.authority()?,
+            coin_wallet,
+            pc_wallet,
+            market_accounts: pole::accounts::MarketAccounts {
+                market: market.market,
+                open_orders,
+                request_queue: market.request_queue,
+                event_queue: market.event_queue,
+                bids: market.bids,
+                asks: market.asks,
+                coin_vault: market.coin_vault,
+                pc_vault: market.pc_vault,
+                vault_signer: market.vault_signer,
+            },
+            token_program: state.generic_config.token_program,
+            dex_program: state.serum_config.dex_program,
+        },
+        pole::instruction::SettleOpenOrders {},
+        remaining_accounts,
+    ))
+}
+
+/// Credits what the harvest market holds for the pool, PORT to `port_supply` and the pc to
+/// `liquidity_supply`, or to the route wallet when the pool sells through `route`.
+pub fn settle_open_orders(
+    pool: &PoolInfo,
+    market: &SerumMarket,
+    route: Option<&SellRoute>,
+) -> Result<Instruction, ClientError> {
+    let state = &pool.state;
+    let (pc_wallet, remaining_accounts) = match route {
+        Some(route) => (
+            route.wallet,
+            vec![AccountMeta::new_readonly(
+                pda::sell_route(&pool.address).0,
+                false,
+            )],
+        ),
+        None => (state.generic_config.liquidity_supply, vec![]),
+    };
+    settle(
+        pool,
+        market,
+        state.serum_config.port_open_orders,
+        state.port_config.port_supply,
+        pc_wallet,
+        remaining_accounts,
+    )
+}
+
+/// Credits what the sell route's market holds for the pool, liquidity to `liquidity_supply` and
+/// the pc to the route wallet.
+pub fn settle_route_open_orders(
+    pool: &PoolInfo,
+    route: &SellRoute,
+    route_market: &SerumMarket,
+) -> Result<Instruction, ClientError> {
+    settle(
+        pool,
+        route_market,
+        route.open_orders,
+        pool.state.generic_config.liquidity_supply,
+        route.wallet,
+        vec![AccountMeta::new_readonly(
+            pda::sell_route(&pool.address).0,
+            false,
+        )],
+    )
+}
+
+/// Credits what the sub reward's market holds for the pool, the sub reward to its supply and
+/// liquidity to `liquidity_supply`.
+pub fn settle_sub_reward_open_orders(
+    pool: &PoolInfo,
+    sub_reward: &SubReward,
+    market: &SerumMarket,
+) -> Result<Instruction, ClientError> {
+    settle(
+        pool,
+        market,
+        sub_reward.open_orders,
+        sub_reward.supply,
+        pool.state.generic_config.liquidity_supply,
+        vec![AccountMeta::new_readonly(
+            pda::sub_reward(&pool.address).0,
+            false,
+        )],
+    )
+}
+
+pub fn close_open_orders(
+    pool: &PoolInfo,
+    dex_market: Pubkey,
+    owner: Pubkey,
+) -> Result<Instruction, ClientError> {
+    let state = &pool.state;
+    Ok(pole_instruction(
+        pole::accounts::ClosePoolOpenOrders {
+            pole_pool: pool.address,
+            pole_authority: pool.authority()?,
+            port_open_orders: state.serum_config.port_open_orders,
+            dex_market,
+            owner,
+            dex_program: state.serum_config.dex_program,
+        },
+        pole::instruction::CloseOpenOrders {},
+        vec![],
+    ))
+}
+
+/// `port_open_orders` is a fresh keypair that has to sign. `legacy_market` is ignored once
+/// the open orders have been closed.
+pub fn migrate_market(
+    pool: &PoolInfo,
+    legacy_market: Pubkey,
+    port_open_orders: Pubkey,
+    dex_market: Pubkey,
+    owner: Pubkey,
+) -> Result<Instruction, ClientError> {
+    let state = &pool.state;
+    let (legacy_open_orders, legacy_market) =
+        if state.serum_config.port_open_orders == Pubkey::default() {
+            (dex_market, dex_market)
+        } else {
+            (state.serum_config.port_open_orders, legacy_market)
+        };
+    Ok(pole_instruction(
+        pole::accounts::MigrateMarket {
+            pole_pool: pool.address,
+            pole_authority: pool.authority()?,
+            legacy_open_orders,
+            legacy_market,
+            port_open_orders,
+            dex_market,
+            owner,
+            dex_program: state.serum_config.dex_program,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        pole::instruction::MigrateMarket {},
+        vec![],
+    ))
+}
+
+/// The accounts a pool may have besides its config, closed with it by close_pool.
+#[derive(Default)]
+pub struct PoolExtras<'a> {
+    /// The sell route and the owner wallet taking what is left in the route wallet.
+    pub sell_route: Option<(&'a SellRoute, Pubkey)>,
+    /// The sub reward, its market and the owner wallet taking what is left in its supply.
+    pub sub_reward: Option<(&'a SubReward, Pubkey, Pubkey)>,
+    pub fee_split: Option<&'a FeeSplit>,
+}
+
+/// Closes a pool with no LP left, no pending withdraw requests and an unwound obligation, rent goes
+/// to `owner`. Leftover liquidity goes to `fee_receiver`, or the fee split recipients when the pool
+/// has one, and leftover PORT to `owner_port_wallet`. `extras` has to list every extra account the
+/// pool has. `registry_page` is the page listing the pool, any page if it is not listed.
+pub fn close_pool(
+    pool: &PoolInfo,
+    dex_market: Pubkey,
+    owner_port_wallet: Pubkey,
+    extras: &PoolExtras,
+    registry_page: u64,
+    owner: Pubkey,
+) -> Result<Instruction, ClientError> {
+    let state = &pool.state;
+    let mut remaining = vec![];
+    if let Some((route, dust_wallet)) = extras.sell_route {
+        remaining.extend([
+            AccountMeta::new(route.open_orders, false),
+            AccountMeta::new(route.market, false),
+            AccountMeta::new(route.wallet, false),
+            AccountMeta::new(dust_wallet, false),
+        ]);
+    }
+    if let Some((sub_reward, market, dust_wallet)) = extras.sub_reward {
+        remaining.extend([
+            AccountMeta::new(sub_reward.open_orders, false),
+            AccountMeta::new(market, false),
+            AccountMeta::new(sub_reward.supply, false),
+            AccountMeta::new(dust_wallet, false),
+        ]);
+    }
+    if let Some(fee_split) = extras.fee_split {
+        remaining.extend(
+            fee_split.recipients[..fee_split.recipient_count as usize]
+                .iter()
+                .map(|recipient| AccountMeta::new(*recipient, false)),
+        );
+    }
+    if state.withdraw_queue.is_initialized() {
+        remaining.extend([
+            AccountMeta::new(state.withdraw_queue.lp_escrow, false),
+            AccountMeta::new(state.withdraw_queue.settled_liquidity, false),
+        ]);
+    }
+    Ok(pole_instruction(
+        pole::accounts::ClosePool {
+            pole_pool: pool.address,
+            pole_authority: pool.authority()?,
+            lp_mint: state.generic_config.lp_mint,
+            liquidity_supply: state.generic_config.liquidity_supply,
+            fee_receiver: state.generic_config.fee_receiver,
+            port_lp_supply: state.port_config.port_lp_supply,
+            port_supply: state.port_config.port_supply,
+            owner_port_wallet,
+            port_open_orders: state.serum_config.port_open_orders,
+            dex_market,
+            obligation: state.port_config.obligation,
+            sell_route: pda::sell_route(&pool.address).0,
+            sub_reward: pda::sub_reward(&pool.address).0,
+            fee_split: pda::fee_split(&pool.address).0,
+            registry_page: pda::registry_page(registry_page).0,
+            owner,
+            token_program: state.generic_config.token_program,
+            dex_program: state.serum_config.dex_program,
+        },
+        pole::instruction::ClosePool {},
+        remaining,
+    ))
+}
+
+/// Moves the whole balance of `source`, a token account of the pool authority the pool does not use, to `target`.
+pub fn sweep(
+    pool: &PoolInfo,
+    source: Pubkey,
+    target: Pubkey,
+    owner: Pubkey,
+) -> Result<Instruction, ClientError> {
+    Ok(pole_instruction(
+        pole::accounts::Sweep {
+            pole_pool: pool.address,
+            pole_authority: pool.authority()?,
+            source,
+            target,
+            sell_route: pda::sell_route(&pool.address).0,
+            sub_reward: pda::sub_reward(&pool.address).0,
+            owner,
+            token_program: pool.state.generic_config.token_program,
+        },
+        pole::instruction::Sweep {},
+        vec![],
+    ))
+}
+
+/// Exactly `port_iterate` `auto_compound` legs, to be sent as a single transaction. The legs
+/// refresh the reserve and obligation when stale.
+pub fn auto_compound(
+    pool: &PoolInfo,
+    reserve: &PortReserve,
+) -> Result<Vec<Instruction>, ClientError> {
+    let pole_authority = pool.authority()?;
+    Ok((0..pool.port_iterate())
+        .map(|leverage| {
+            pole_instruction(
+                pole::accounts::AutoCompound {
+                    pole_pool: pool.address,
+                    pole_authority,
+                    liquidity_supply: pool.state.generic_config.liquidity_supply,
+                    port_accounts: port_lending_accounts(pool, reserve),
+                    transaction_info: sysvar::instructions::ID,
+                    token_program: pool.state.generic_config.token_program,
+                    clock: sysvar::clock::ID,
+                },
+                pole::instruction::AutoCompound {
+                    leverage: leverage as u8,
+                },
+                oracle_accounts(reserve),
+            )
+        })
+        .collect())
+}
+
+/// The reserve and obligation have to be refreshed earlier in the same transaction.
+pub fn unwind_obligation(
+    pool: &PoolInfo,
+    reserve: &PortReserve,
+    owner: Pubkey,
+    repay_amount: u64,
+    withdraw_amount: u64,
+) -> Result<Instruction, ClientError> {
+    Ok(pole_instruction(
+        pole::accounts::UnwindObligation {
+            pole_pool: pool.address,
+            pole_authority: pool.authority()?,
+            liquidity_supply: pool.state.generic_config.liquidity_supply,
+            port_accounts: port_lending_accounts(pool, reserve),
+            owner,
+            token_program: pool.state.generic_config.token_program,
+            clock: sysvar::clock::ID,
+        },
+        pole::instruction::UnwindObligation {
+            repay_amount,
+            withdraw_amount,
+        },
+        vec![],
+    ))
+}
+
+/// `lp_escrow` and `settled_liquidity` are fresh keypairs that have to sign.
+pub fn init_withdraw_queue(
+    pool: &PoolInfo,
+    liquidity_mint: Pubkey,
+    lp_escrow: Pubkey,
+    settled_liquidity: Pubkey,
+    owner: Pubkey,
+) -> Result<Instruction, ClientError> {
+    let generic_config = &pool.state.generic_config;
+    Ok(pole_instruction(
+        pole::accounts::InitWithdrawQueue {
+            pole_pool: pool.address,
+            pole_authority: pool.authority()?,
+            lp_mint: generic_config.lp_mint,
+            liquidity_mint,
+            liquidity_supply: generic_config.liquidity_supply,
+            lp_escrow,
+            settled_liquidity,
+            owner,
+            token_program: generic_config.token_program,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        pole::instruction::InitWithdrawQueue {},
+        vec![],
+    ))
+}
+
+/// A `tracked` request pays the fee tier of the user's position, and gives the position its
+/// LP back on [`cancel_withdraw_request`].
+pub fn request_withdraw(
+    pool: &PoolInfo,
+    user_lp_wallet: Pubkey,
+    user: Pubkey,
+    amount: u64,
+    tracked: bool,
+) -> Instruction {
+    let (withdraw_request, bump) = pda::withdraw_request(&pool.address, &user);
+    pole_instruction(
+        pole::accounts::RequestWithdraw {
+            pole_pool: pool.address,
+            withdraw_request,
+            user_lp_wallet,
+            lp_escrow: pool.state.withdraw_queue.lp_escrow,
+            user,
+            token_program: pool.state.generic_config.token_program,
+            system_program: system_program::ID,
+            clock: sysvar::clock::ID,
+        },
+        pole::instruction::RequestWithdraw {
+            _bump: bump,
+            amount,
+        },
+        position_accounts(&pool.address, &user, tracked),
+    )
+}
+
+/// The reserve and obligation have to be refreshed earlier in the same transaction,
+/// follow with `process_withdraw_request` for the requests the freed liquidity covers.
+/// Both amounts are capped on chain to what the pending requests are short of.
+pub fn unwind_for_withdrawals(
+    pool: &PoolInfo,
+    reserve: &PortReserve,
+    keeper: Pubkey,
+    repay_amount: u64,
+    withdraw_amount: u64,
+) -> Result<Instruction, ClientError> {
+    Ok(pole_instruction(
+        pole::accounts::UnwindForWithdrawals {
+            pole_pool: pool.address,
+            pole_authority: pool.authority()?,
+            liquidity_supply: pool.state.generic_config.liquidity_supply,
+            port_accounts: port_lending_accounts(pool, reserve),
+            keeper,
+            token_program: pool.state.generic_config.token_program,
+            clock: sysvar::clock::ID,
+        },
+        pole::instruction::UnwindForWithdrawals {
+            repay_amount,
+            withdraw_amount,
+        },
+        vec![],
+    ))
+}
+
+/// The reserve and obligation have to be refreshed earlier in the same transaction;
+/// `referrer` is the request's referrer, required when it has one.
+pub fn process_withdraw_request(
+    pool: &PoolInfo,
+    withdraw_request: Pubkey,
+    referrer: Option<Pubkey>,
+    keeper: Pubkey,
+) -> Result<Instruction, ClientError> {
+    let state = &pool.state;
+    Ok(pole_instruction(
+        pole::accounts::ProcessWithdrawRequest {
+            pole_pool: pool.address,
+            pole_authority: pool.authority()?,
+            withdraw_request,
+            lp_mint: state.generic_config.lp_mint,
+            lp_escrow: state.withdraw_queue.lp_escrow,
+            liquidity_supply: state.generic_config.liquidity_supply,
+            settled_liquidity: state.withdraw_queue.settled_liquidity,
+            pole_fee_account: state.generic_config.fee_receiver,
+            reserve: state.port_config.reserve,
+            obligation: state.port_config.obligation,
+            keeper,
+            token_program: state.generic_config.token_program,
+            clock: sysvar::clock::ID,
+        },
+        pole::instruction::ProcessWithdrawRequest {},
+        referrer_accounts(referrer),
+    ))
+}
+
+pub fn claim_withdraw(
+    pool: &PoolInfo,
+    user_liquidity_wallet: Pubkey,
+    user: Pubkey,
+) -> Result<Instruction, ClientError> {
+    Ok(pole_instruction(
+        pole::accounts::ClaimWithdraw {
+            pole_pool: pool.address,
+            pole_authority: pool.authority()?,
+            withdraw_request: pda::withdraw_request(&pool.address, &user).0,
+            settled_liquidity: pool.state.withdraw_queue.settled_liquidity,
+            user_liquidity_wallet,
+            user,
+            token_program: pool.state.generic_config.token_program,
+        },
+        pole::instruction::ClaimWithdraw {},
+        vec![],
+    ))
+}
+
+pub fn cancel_withdraw_request(
+    pool: &PoolInfo,
+    user_lp_wallet: Pubkey,
+    user: Pubkey,
+    tracked: bool,
+) -> Result<Instruction, ClientError> {
+    Ok(pole_instruction(
+        pole::accounts::CancelWithdrawRequest {
+            pole_pool: pool.address,
+            pole_authority: pool.authority()?,
+            withdraw_request: pda::withdraw_request(&pool.address, &user).0,
+            lp_escrow: pool.state.withdraw_queue.lp_escrow,
+            user_lp_wallet,
+            user,
+            token_program: pool.state.generic_config.token_program,
+        },
+        pole::instruction::CancelWithdrawRequest {},
+        position_accounts(&pool.address, &user, tracked),
+    ))
+}
+
+/// Claims the legacy stake account's rewards, and the sub reward when `sub_reward` is given,
+/// before the stake account is dropped; the obligation has to be unwound first.
+pub fn migrate_authority(
+    pool: &PoolInfo,
+    port_open_orders: Pubkey,
+    dex_market: Pubkey,
+    staking: &PortStaking,
+    sub_reward: Option<&SubReward>,
+    owner: Pubkey,
+) -> Instruction {
+    let state = &pool.state;
+    let (pole_authority, authority_bump) = pda::pole_authority(&pool.address);
+    pole_instruction(
+        pole::accounts::MigrateAuthority {
+            pole_pool: pool.address,
+            legacy_authority: pda::legacy_authority().0,
+            pole_authority,
+            lp_mint: state.generic_config.lp_mint,
+            liquidity_supply: state.generic_config.liquidity_supply,
+            port_lp_supply: state.port_config.port_lp_supply,
+            port_supply: state.port_config.port_supply,
+            legacy_open_orders: state.serum_config.port_open_orders,
+            port_open_orders,
+            dex_market,
+            obligation: state.port_config.obligation,
+            port_accounts: port_staking_accounts(pool, staking),
+            owner,
+            token_program: state.generic_config.token_program,
+            dex_program: state.serum_config.dex_program,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+            clock: sysvar::clock::ID,
+        },
+        pole::instruction::MigrateAuthority { authority_bump },
+        sub_reward_accounts(pool, staking, sub_reward),
+    )
+}
+
+pub fn migrate_pool(pool: &Pubkey, owner: Pubkey) -> Instruction {
+    pole_instruction(
+        pole::accounts::MigratePool {
+            pole_pool: *pool,
+            owner,
+        },
+        pole::instruction::MigratePool {},
+        vec![],
+    )
+}
+
+pub fn begin_pool_realloc(pool: &Pubkey, owner: Pubkey) -> Instruction {
+    let (pool_realloc, bump) = pda::pool_realloc(pool);
+    pole_instruction(
+        pole::accounts::BeginPoolRealloc {
+            pole_pool: *pool,
+            pool_realloc,
+            owner,
+            system_program: system_program::ID,
+        },
+        pole::instruction::BeginPoolRealloc { _bump: bump },
+        vec![],
+    )
+}
+
+/// Must be sent after the begin_pool_realloc transaction, the closed pool is only
+/// deleted at its end.
+pub fn finish_pool_realloc(name: &str, owner: Pubkey) -> Instruction {
+    let (pole_pool, pda_bump) = pda::pole_pool(name);
+    pole_instruction(
+        pole::accounts::FinishPoolRealloc {
+            pole_pool,
+            pool_realloc: pda::pool_realloc(&pole_pool).0,
+            owner,
+            system_program: system_program::ID,
+        },
+        pole::instruction::FinishPoolRealloc {
+            _name: name.to_string(),
+            _pda_bump: pda_bump,
+        },
+        vec![],
+    )
+}
+
+pub fn change_owner(pool: &Pubkey, owner: Pubkey, new_owner: Pubkey) -> Instruction {
+    pole_instruction(
+        pole::accounts::ChangeOwner {
+            pole_pool: *pool,
+            owner,
+        },
+        pole::instruction::ChangeOwner { owner: new_owner },
+        vec![],
+    )
+}
+
+/// Fails once the pool has a fee split, see [`change_fee_split`].
+pub fn change_fee_receiver(pool: &Pubkey, owner: Pubkey, new_fee_receiver: Pubkey) -> Instruction {
+    pole_instruction(
+        pole::accounts::ChangeFeeReceiver {
+            pole_pool: *pool,
+            fee_split: pda::fee_split(pool).0,
+            owner,
+        },
+        pole::instruction::ChangeFeeReceiver { new_fee_receiver },
+        vec![],
+    )
+}
+
+pub fn change_liquidity_cap(pool: &Pubkey, owner: Pubkey, cap: u64) -> Instruction {
+    pole_instruction(
+        pole::accounts::ChangeLiquidityCap {
+            pole_pool: *pool,
+            owner,
+        },
+        pole::instruction::ChangeLiquidityCap { cap },
+        vec![],
+    )
+}
+
+pub fn change_referral_fee(pool: &Pubkey, owner: Pubkey, bips: u64) -> Instruction {
+    pole_instruction(
+        pole::accounts::ChangeReferralFee {
+            pole_pool: *pool,
+            owner,
+        },
+        pole::instruction::ChangeReferralFee { bips },
+        vec![],
+    )
+}
+
+pub fn change_wallet_cap(pool: &Pubkey, owner: Pubkey, cap: u64) -> Instruction {
+    pole_instruction(
+        pole::accounts::ChangeWalletCap {
+            pole_pool: *pool,
+            owner,
+        },
+        pole::instruction::ChangeWalletCap { cap },
+        vec![],
+    )
+}
+
+pub fn change_withdraw_fee(pool: &Pubkey, owner: Pubkey, bips: u8) -> Instruction {
+    pole_instruction(
+        pole::accounts::ChangeWithdrawFee {
+            pole_pool: *pool,
+            owner,
+        },
+        pole::instruction::ChangeWithdrawFee { bips },
+        vec![],
+    )
+}
+
+pub fn change_min_deposit(pool: &Pubkey, owner: Pubkey, min_deposit: u64) -> Instruction {
+    pole_instruction(
+        pole::accounts::ChangeMinDeposit {
+            pole_pool: *pool,
+            owner,
+        },
+        pole::instruction::ChangeMinDeposit { min_deposit },
+        vec![],
+    )
+}
+
+pub fn change_harvest_config(
+    pool: &Pubkey,
+    owner: Pubkey,
+    stale_slot: u64,
+    min_harvest_interval: u64,
+    harvest_bounty_bips: u64,
+) -> Instruction {
+    pole_instruction(
+        pole::accounts::ChangeHarvestConfig {
+            pole_pool: *pool,
+            owner,
+        },
+        pole::instruction::ChangeHarvestConfig {
+            stale_slot,
+            min_harvest_interval,
+            harvest_bounty_bips,
+        },
+        vec![],
+    )
+}
+
+pub fn change_fee_schedule(pool: &Pubkey, owner: Pubkey, tiers: Vec<FeeTier>) -> Instruction {
+    pole_instruction(
+        pole::accounts::ChangeFeeSchedule {
+            pole_pool: *pool,
+            owner,
+        },
+        pole::instruction::ChangeFeeSchedule { tiers },
+        vec![],
+    )
+}
+
+pub fn change_allowlist_mode(pool: &Pubkey, owner: Pubkey, enabled: bool) -> Instruction {
+    pole_instruction(
+        pole::accounts::ChangeAllowlistMode {
+            pole_pool: *pool,
+            owner,
+        },
+        pole::instruction::ChangeAllowlistMode { enabled },
+        vec![],
+    )
+}
+
+/// `wallet` is the owner of the liquidity wallets allowed to deposit.
+pub fn add_allowlist_entry(pool: &Pubkey, owner: Pubkey, wallet: Pubkey) -> Instruction {
+    let (allowlist_entry, bump) = pda::allowlist_entry(pool, &wallet);
+    pole_instruction(
+        pole::accounts::AddAllowlistEntry {
+            pole_pool: *pool,
+            allowlist_entry,
+            owner,
+            system_program: system_program::ID,
+        },
+        pole::instruction::AddAllowlistEntry {
+            _bump: bump,
+            wallet,
+        },
+        vec![],
+    )
+}
+
+pub fn remove_allowlist_entry(pool: &Pubkey, owner: Pubkey, wallet: Pubkey) -> Instruction {
+    pole_instruction(
+        pole::accounts::RemoveAllowlistEntry {
+            pole_pool: *pool,
+            allowlist_entry: pda::allowlist_entry(pool, &wallet).0,
+            owner,
+        },
+        pole::instruction::RemoveAllowlistEntry {},
+        vec![],
+    )
+}
+
+/// `fee_vault` is a fresh keypair that has to sign, it becomes the pool's fee_receiver.
+pub fn init_fee_split(
+    pool: &PoolInfo,
+    liquidity_mint: Pubkey,
+    fee_vault: Pubkey,
+    owner: Pubkey,
+) -> Result<Instruction, ClientError> {
+    let generic_config = &pool.state.generic_config;
+    let (fee_split, bump) = pda::fee_split(&pool.address);
+    Ok(pole_instruction(
+        pole::accounts::InitFeeSplit {
+            pole_pool: pool.address,
+            pole_authority: pool.authority()?,
+            fee_split,
+            fee_vault,
+            liquidity_mint,
+            liquidity_supply: generic_config.liquidity_supply,
+            owner,
+            token_program: generic_config.token_program,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        },
+        pole::instruction::InitFeeSplit { _bump: bump },
+        vec![],
+    ))
+}
+
+pub fn change_fee_split(
+    pool: &Pubkey,
+    owner: Pubkey,
+    recipients: Vec<FeeRecipient>,
+) -> Instruction {
+    pole_instruction(
+        pole::accounts::ChangeFeeSplit {
+            pole_pool: *pool,
+            fee_split: pda::fee_split(pool).0,
+            owner,
+        },
+        pole::instruction::ChangeFeeSplit { recipients },
+        vec![],
+    )
+}
+
+pub fn distribute_fees(pool: &PoolInfo, fee_split: &FeeSplit) -> Result<Instruction, ClientError> {
+    let recipient_count = fee_split.recipient_count as usize;
+    Ok(pole_instruction(
+        pole::accounts::DistributeFees {
+            pole_pool: pool.address,
+            pole_authority: pool.authority()?,
+            fee_split: pda::fee_split(&pool.address).0,
+            fee_vault: fee_split.fee_vault,
+            token_program: pool.state.generic_config.token_program,
+        },
+        pole::instruction::DistributeFees {},
+        fee_split.recipients[..recipient_count.min(fee_split.recipients.len())]
+            .iter()
+            .map(|recipient| AccountMeta::new(*recipient, false))
+            .collect(),
+    ))
+}
@@ -0,0 +1,10 @@
+//! Off-chain helpers for the pole program: PDA derivation, account decoding,
+//! instruction builders and event parsing.
+
+pub mod error;
+pub mod event;
+pub mod instructions;
+pub mod pda;
+pub mod state;
+
+pub use pole;
@@ -0,0 +1,85 @@
+use pole::states::{
+    ALLOWLIST_SEED, FEE_SPLIT_SEED, POSITION_SEED, REALLOC_SEED, REGISTRY_SEED, SELL_ROUTE_SEED,
+    SUB_REWARD_SEED, WITHDRAW_REQUEST_SEED,
+};
+use solana_sdk::bpf_loader_upgradeable;
+use solana_sdk::pubkey::Pubkey;
+
+pub fn pole_pool(name: &str) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[name.as_bytes()], &pole::ID)
+}
+
+pub fn pole_authority(pole_pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[pole_pool.as_ref()], &pole::ID)
+}
+
+// authority shared by pools created before per-pool authorities
+pub fn legacy_authority() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[], &pole::ID)
+}
+
+pub fn registry() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REGISTRY_SEED, pole::ID.as_ref()], &pole::ID)
+}
+
+pub fn registry_page(index: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[REGISTRY_SEED, pole::ID.as_ref(), &index.to_le_bytes()],
+        &pole::ID,
+    )
+}
+
+// holds the upgrade authority init_registry checks
+pub fn program_data() -> Pubkey {
+    Pubkey::find_program_address(&[pole::ID.as_ref()], &bpf_loader_upgradeable::id()).0
+}
+
+pub fn fee_split(pole_pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[FEE_SPLIT_SEED, pole_pool.as_ref()], &pole::ID)
+}
+
+pub fn sub_reward(pole_pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SUB_REWARD_SEED, pole_pool.as_ref()], &pole::ID)
+}
+
+pub fn sell_route(pole_pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SELL_ROUTE_SEED, pole_pool.as_ref()], &pole::ID)
+}
+
+// holds a pool's data between begin_pool_realloc and finish_pool_realloc
+pub fn pool_realloc(pole_pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REALLOC_SEED, pole_pool.as_ref()], &pole::ID)
+}
+
+pub fn user_balance(user: &Pubkey, pole_pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[user.as_ref(), pole_pool.as_ref()], &pole::ID)
+}
+
+pub fn user_position(pole_pool: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[POSITION_SEED, pole_pool.as_ref(), user.as_ref()],
+        &pole::ID,
+    )
+}
+
+pub fn allowlist_entry(pole_pool: &Pubkey, wallet: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ALLOWLIST_SEED, pole_pool.as_ref(), wallet.as_ref()],
+        &pole::ID,
+    )
+}
+
+pub fn withdraw_request(pole_pool: &Pubkey, user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[WITHDRAW_REQUEST_SEED, pole_pool.as_ref(), user.as_ref()],
+        &pole::ID,
+    )
+}
+
+pub fn lending_market_authority(lending_market: &Pubkey, port_lending_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[lending_market.as_ref()], port_lending_program).0
+}
+
+pub fn staking_program_authority(staking_pool: &Pubkey, port_staking_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[staking_pool.as_ref()], port_staking_program).0
+}
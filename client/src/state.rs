@@ -0,0 +1,190 @@
+use std::mem::size_of;
+
+use anchor_lang::{AccountDeserialize, Discriminator};
+use pole::states::{
+    FeeSplit, PolePortPool, PoolRegistry, RegistryEntry, RegistryPage, SellRoute, SubReward,
+    UserBalance, UserPosition, WithdrawRequest,
+};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::error::ClientError;
+
+const DISCRIMINATOR_SIZE: usize = 8;
+
+pub fn decode_pole_pool(data: &[u8]) -> Result<PolePortPool, ClientError> {
+    let end = DISCRIMINATOR_SIZE + size_of::<PolePortPool>();
+    if data.len() < end {
+        return Err(ClientError::AccountDataTooSmall);
+    }
+    if data[..DISCRIMINATOR_SIZE] != PolePortPool::discriminator() {
+        return Err(ClientError::InvalidDiscriminator);
+    }
+    bytemuck::try_from_bytes::<PolePortPool>(&data[DISCRIMINATOR_SIZE..end])
+        .map(|pool| *pool)
+        .map_err(|_| ClientError::InvalidAccountData)
+}
+
+pub fn decode_registry(data: &[u8]) -> Result<PoolRegistry, ClientError> {
+    let end = DISCRIMINATOR_SIZE + size_of::<PoolRegistry>();
+    if data.len() < end {
+        return Err(ClientError::AccountDataTooSmall);
+    }
+    if data[..DISCRIMINATOR_SIZE] != PoolRegistry::discriminator() {
+        return Err(ClientError::InvalidDiscriminator);
+    }
+    bytemuck::try_from_bytes::<PoolRegistry>(&data[DISCRIMINATOR_SIZE..end])
+        .map(|registry| *registry)
+        .map_err(|_| ClientError::InvalidAccountData)
+}
+
+pub fn decode_registry_page(data: &[u8]) -> Result<RegistryPage, ClientError> {
+    let end = DISCRIMINATOR_SIZE + size_of::<RegistryPage>();
+    if data.len() < end {
+        return Err(ClientError::AccountDataTooSmall);
+    }
+    if data[..DISCRIMINATOR_SIZE] != RegistryPage::discriminator() {
+        return Err(ClientError::InvalidDiscriminator);
+    }
+    bytemuck::try_from_bytes::<RegistryPage>(&data[DISCRIMINATOR_SIZE..end])
+        .map(|page| *page)
+        .map_err(|_| ClientError::InvalidAccountData)
+}
+
+/// The pools registered in a page, in no particular order.
+pub fn registered_pools(page: &RegistryPage) -> Vec<RegistryEntry> {
+    let pool_count = page.pool_count as usize;
+    let pools = page.pools;
+    pools[..pool_count.min(pools.len())].to_vec()
+}
+
+pub fn decode_user_balance(data: &[u8]) -> Result<UserBalance, ClientError> {
+    if data.len() < DISCRIMINATOR_SIZE {
+        return Err(ClientError::AccountDataTooSmall);
+    }
+    if data[..DISCRIMINATOR_SIZE] != UserBalance::discriminator() {
+        return Err(ClientError::InvalidDiscriminator);
+    }
+    UserBalance::try_deserialize(&mut &data[..]).map_err(|_| ClientError::InvalidAccountData)
+}
+
+pub fn decode_fee_split(data: &[u8]) -> Result<FeeSplit, ClientError> {
+    if data.len() < DISCRIMINATOR_SIZE {
+        return Err(ClientError::AccountDataTooSmall);
+    }
+    if data[..DISCRIMINATOR_SIZE] != FeeSplit::discriminator() {
+        return Err(ClientError::InvalidDiscriminator);
+    }
+    FeeSplit::try_deserialize(&mut &data[..]).map_err(|_| ClientError::InvalidAccountData)
+}
+
+pub fn decode_sub_reward(data: &[u8]) -> Result<SubReward, ClientError> {
+    if data.len() < DISCRIMINATOR_SIZE {
+        return Err(ClientError::AccountDataTooSmall);
+    }
+    if data[..DISCRIMINATOR_SIZE] != SubReward::discriminator() {
+        return Err(ClientError::InvalidDiscriminator);
+    }
+    SubReward::try_deserialize(&mut &data[..]).map_err(|_| ClientError::InvalidAccountData)
+}
+
+pub fn decode_sell_route(data: &[u8]) -> Result<SellRoute, ClientError> {
+    if data.len() < DISCRIMINATOR_SIZE {
+        return Err(ClientError::AccountDataTooSmall);
+    }
+    if data[..DISCRIMINATOR_SIZE] != SellRoute::discriminator() {
+        return Err(ClientError::InvalidDiscriminator);
+    }
+    SellRoute::try_deserialize(&mut &data[..]).map_err(|_| ClientError::InvalidAccountData)
+}
+
+pub fn decode_user_position(data: &[u8]) -> Result<UserPosition, ClientError> {
+    if data.len() < DISCRIMINATOR_SIZE {
+        return Err(ClientError::AccountDataTooSmall);
+    }
+    if data[..DISCRIMINATOR_SIZE] != UserPosition::discriminator() {
+        return Err(ClientError::InvalidDiscriminator);
+    }
+    UserPosition::try_deserialize(&mut &data[..]).map_err(|_| ClientError::InvalidAccountData)
+}
+
+pub fn decode_withdraw_request(data: &[u8]) -> Result<WithdrawRequest, ClientError> {
+    if data.len() < DISCRIMINATOR_SIZE {
+        return Err(ClientError::AccountDataTooSmall);
+    }
+    if data[..DISCRIMINATOR_SIZE] != WithdrawRequest::discriminator() {
+        return Err(ClientError::InvalidDiscriminator);
+    }
+    WithdrawRequest::try_deserialize(&mut &data[..]).map_err(|_| ClientError::InvalidAccountData)
+}
+
+/// A decoded pool together with its address, everything the builders need to know about it.
+#[derive(Clone, Copy, Debug)]
+pub struct PoolInfo {
+    pub address: Pubkey,
+    pub state: PolePortPool,
+}
+
+impl PoolInfo {
+    pub fn decode(address: Pubkey, data: &[u8]) -> Result<Self, ClientError> {
+        Ok(PoolInfo {
+            address,
+            state: decode_pole_pool(data)?,
+        })
+    }
+
+    pub fn authority(&self) -> Result<Pubkey, ClientError> {
+        self.state
+            .authority(&self.address)
+            .map_err(|_| ClientError::InvalidAuthority)
+    }
+
+    pub fn port_iterate(&self) -> u64 {
+        self.state.port_config.port_iterate
+    }
+
+    pub fn is_idle(&self) -> bool {
+        let port_state = &self.state.port_state;
+        let (deposit_verified, redeem_verified, leverage) = (
+            port_state.deposit_verified,
+            port_state.redeem_verified,
+            port_state.leverage,
+        );
+        deposit_verified == 0 && redeem_verified == 0 && leverage == 0
+    }
+}
+
+/// Addresses of the Port reserve the pool lends to, read off the reserve account.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PortReserve {
+    pub address: Pubkey,
+    pub lending_market: Pubkey,
+    pub liquidity_mint: Pubkey,
+    pub liquidity_supply: Pubkey,
+    pub liquidity_fee_receiver: Pubkey,
+    pub collateral_mint: Pubkey,
+    pub collateral_supply: Pubkey,
+    pub staking_pool: Pubkey,
+    pub oracle: Option<Pubkey>,
+}
+
+/// The serum market `claim_and_sell` sells PORT on; the open orders come from the pool.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SerumMarket {
+    pub market: Pubkey,
+    pub request_queue: Pubkey,
+    pub event_queue: Pubkey,
+    pub bids: Pubkey,
+    pub asks: Pubkey,
+    pub coin_vault: Pubkey,
+    pub pc_vault: Pubkey,
+    pub vault_signer: Pubkey,
+}
+
+/// The Port staking pool rewards are claimed from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PortStaking {
+    pub staking_pool: Pubkey,
+    pub reward_supply: Pubkey,
+    // the staking pool's sub reward supply, when it pays a second reward
+    pub sub_reward_supply: Option<Pubkey>,
+}
@@ -0,0 +1,89 @@
+use std::error::Error;
+
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::transaction::Transaction;
+
+/// What the keeper needs from a cluster. Implemented for `RpcClient` (mainnet or a
+/// `solana-test-validator`), and by the in-memory `MockChain` the keeper tests run on.
+pub trait Chain {
+    fn slot(&self) -> Result<u64, Box<dyn Error>>;
+    fn account_data(&self, address: &Pubkey) -> Result<Vec<u8>, Box<dyn Error>>;
+    fn send(
+        &self,
+        instructions: &[Instruction],
+        payer: &Keypair,
+    ) -> Result<Signature, Box<dyn Error>>;
+}
+
+impl Chain for RpcClient {
+    fn slot(&self) -> Result<u64, Box<dyn Error>> {
+        Ok(self.get_slot()?)
+    }
+
+    fn account_data(&self, address: &Pubkey) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(self.get_account_data(address)?)
+    }
+
+    fn send(
+        &self,
+        instructions: &[Instruction],
+        payer: &Keypair,
+    ) -> Result<Signature, Box<dyn Error>> {
+        let (blockhash, _) = self.get_recent_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&payer.pubkey()),
+            &[payer],
+            blockhash,
+        );
+        Ok(self.send_and_confirm_transaction(&transaction)?)
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// Serves accounts from memory at a settable slot and records what the keeper sends.
+    #[derive(Default)]
+    pub struct MockChain {
+        pub slot: Cell<u64>,
+        pub accounts: RefCell<HashMap<Pubkey, Vec<u8>>>,
+        pub sent: RefCell<Vec<Vec<Instruction>>>,
+    }
+
+    impl MockChain {
+        pub fn set_account(&self, address: Pubkey, data: Vec<u8>) {
+            self.accounts.borrow_mut().insert(address, data);
+        }
+    }
+
+    impl Chain for MockChain {
+        fn slot(&self) -> Result<u64, Box<dyn Error>> {
+            Ok(self.slot.get())
+        }
+
+        fn account_data(&self, address: &Pubkey) -> Result<Vec<u8>, Box<dyn Error>> {
+            self.accounts
+                .borrow()
+                .get(address)
+                .cloned()
+                .ok_or_else(|| format!("account {} not found", address).into())
+        }
+
+        fn send(
+            &self,
+            instructions: &[Instruction],
+            _payer: &Keypair,
+        ) -> Result<Signature, Box<dyn Error>> {
+            self.sent.borrow_mut().push(instructions.to_vec());
+            Ok(Signature::default())
+        }
+    }
+}
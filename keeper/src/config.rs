@@ -0,0 +1,176 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use pole_client::state::{PortReserve, PortStaking, SerumMarket};
+use serde::{Deserialize, Deserializer};
+use solana_sdk::pubkey::Pubkey;
+
+fn pubkey<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    Pubkey::from_str(&value).map_err(serde::de::Error::custom)
+}
+
+fn optional_pubkey<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Pubkey>, D::Error> {
+    match Option::<String>::deserialize(deserializer)? {
+        Some(value) => Pubkey::from_str(&value)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+fn default_poll_interval_ms() -> u64 {
+    2000
+}
+
+fn default_harvest_margin_slots() -> u64 {
+    10
+}
+
+fn default_health_alert_ratio() -> f64 {
+    0.9
+}
+
+fn default_ltv_alert_margin() -> u64 {
+    5
+}
+
+#[derive(Deserialize, Debug)]
+pub struct KeeperConfig {
+    pub url: String,
+    pub keypair: String,
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+    // harvest this many slots before the stale window closes
+    #[serde(default = "default_harvest_margin_slots")]
+    pub harvest_margin_slots: u64,
+    // alert when borrowed value / unhealthy borrow value goes above this
+    #[serde(default = "default_health_alert_ratio")]
+    pub health_alert_ratio: f64,
+    // alert when the reserve LTV comes this close to port_reserve_percentage
+    #[serde(default = "default_ltv_alert_margin")]
+    pub ltv_alert_margin: u64,
+    pub pools: Vec<PoolConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PoolConfig {
+    #[serde(deserialize_with = "pubkey")]
+    pub address: Pubkey,
+    #[serde(deserialize_with = "pubkey")]
+    pub harvester_liquidity_wallet: Pubkey,
+    #[serde(default)]
+    pub auto_compound: bool,
+    pub reserve: ReserveConfig,
+    pub market: MarketConfig,
+    // second hop market for pools whose liquidity mint is not the market's pc,
+    // the rest of the route is read from the pool's SellRoute
+    #[serde(default)]
+    pub route_market: Option<MarketConfig>,
+    // sub reward (coin) / liquidity mint (pc) market `sell_sub_reward` sells on
+    #[serde(default)]
+    pub sub_reward_market: Option<MarketConfig>,
+    pub staking: StakingConfig,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ReserveConfig {
+    #[serde(deserialize_with = "pubkey")]
+    pub address: Pubkey,
+    #[serde(deserialize_with = "pubkey")]
+    pub lending_market: Pubkey,
+    #[serde(deserialize_with = "pubkey")]
+    pub liquidity_mint: Pubkey,
+    #[serde(deserialize_with = "pubkey")]
+    pub liquidity_supply: Pubkey,
+    #[serde(deserialize_with = "pubkey")]
+    pub liquidity_fee_receiver: Pubkey,
+    #[serde(deserialize_with = "pubkey")]
+    pub collateral_mint: Pubkey,
+    #[serde(deserialize_with = "pubkey")]
+    pub collateral_supply: Pubkey,
+    #[serde(deserialize_with = "pubkey")]
+    pub staking_pool: Pubkey,
+    #[serde(default, deserialize_with = "optional_pubkey")]
+    pub oracle: Option<Pubkey>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MarketConfig {
+    #[serde(deserialize_with = "pubkey")]
+    pub market: Pubkey,
+    #[serde(deserialize_with = "pubkey")]
+    pub request_queue: Pubkey,
+    #[serde(deserialize_with = "pubkey")]
+    pub event_queue: Pubkey,
+    #[serde(deserialize_with = "pubkey")]
+    pub bids: Pubkey,
+    #[serde(deserialize_with = "pubkey")]
+    pub asks: Pubkey,
+    #[serde(deserialize_with = "pubkey")]
+    pub coin_vault: Pubkey,
+    #[serde(deserialize_with = "pubkey")]
+    pub pc_vault: Pubkey,
+    #[serde(deserialize_with = "pubkey")]
+    pub vault_signer: Pubkey,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct StakingConfig {
+    #[serde(deserialize_with = "pubkey")]
+    pub staking_pool: Pubkey,
+    #[serde(deserialize_with = "pubkey")]
+    pub reward_supply: Pubkey,
+    // the staking pool's second reward supply, for pools set up with `init-sub-reward`
+    #[serde(default, deserialize_with = "optional_pubkey")]
+    pub sub_reward_supply: Option<Pubkey>,
+}
+
+impl KeeperConfig {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(serde_yaml::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+impl From<&ReserveConfig> for PortReserve {
+    fn from(config: &ReserveConfig) -> Self {
+        PortReserve {
+            address: config.address,
+            lending_market: config.lending_market,
+            liquidity_mint: config.liquidity_mint,
+            liquidity_supply: config.liquidity_supply,
+            liquidity_fee_receiver: config.liquidity_fee_receiver,
+            collateral_mint: config.collateral_mint,
+            collateral_supply: config.collateral_supply,
+            staking_pool: config.staking_pool,
+            oracle: config.oracle,
+        }
+    }
+}
+
+impl From<&MarketConfig> for SerumMarket {
+    fn from(config: &MarketConfig) -> Self {
+        SerumMarket {
+            market: config.market,
+            request_queue: config.request_queue,
+            event_queue: config.event_queue,
+            bids: config.bids,
+            asks: config.asks,
+            coin_vault: config.coin_vault,
+            pc_vault: config.pc_vault,
+            vault_signer: config.vault_signer,
+        }
+    }
+}
+
+impl From<&StakingConfig> for PortStaking {
+    fn from(config: &StakingConfig) -> Self {
+        PortStaking {
+            staking_pool: config.staking_pool,
+            reward_supply: config.reward_supply,
+            sub_reward_supply: config.sub_reward_supply,
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use std::error::Error;
+
+use log::{error, info, warn};
+use pole_client::state::{
+    decode_sell_route, decode_sub_reward, PoolInfo, PortReserve, PortStaking, SerumMarket,
+};
+use pole_client::{instructions, pda};
+use port_variable_rate_lending_instructions::state::{Obligation, Reserve};
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use spl_token::state::{Account as TokenAccount, Mint};
+
+use crate::chain::Chain;
+use crate::config::{KeeperConfig, PoolConfig};
+use crate::monitor::{self, Alert};
+
+pub struct Keeper<C: Chain> {
+    pub chain: C,
+    pub payer: Keypair,
+    pub harvest_margin_slots: u64,
+    pub health_alert_ratio: f64,
+    pub ltv_alert_margin: u64,
+}
+
+/// What a single pass over a pool did, mostly for logging and tests.
+#[derive(Debug, Default)]
+pub struct TickReport {
+    pub alerts: Vec<Alert>,
+    pub harvested: bool,
+    pub compounded: bool,
+}
+
+impl<C: Chain> Keeper<C> {
+    pub fn new(chain: C, payer: Keypair, config: &KeeperConfig) -> Self {
+        Keeper {
+            chain,
+            payer,
+            harvest_margin_slots: config.harvest_margin_slots,
+            health_alert_ratio: config.health_alert_ratio,
+            ltv_alert_margin: config.ltv_alert_margin,
+        }
+    }
+
+    fn fetch_pool(&self, address: &Pubkey) -> Result<PoolInfo, Box<dyn Error>> {
+        Ok(PoolInfo::decode(
+            *address,
+            &self.chain.account_data(address)?,
+        )?)
+    }
+
+    fn token_amount(&self, address: &Pubkey) -> Result<u64, Box<dyn Error>> {
+        Ok(TokenAccount::unpack(&self.chain.account_data(address)?)?.amount)
+    }
+
+    pub fn tick(&self, config: &PoolConfig) -> Result<TickReport, Box<dyn Error>> {
+        let mut report = TickReport::default();
+        let slot = self.chain.slot()?;
+        let pool = self.fetch_pool(&config.address)?;
+        let reserve = PortReserve::from(&config.reserve);
+
+        let lp_supply = Mint::unpack(
+            &self
+                .chain
+                .account_data(&pool.state.generic_config.lp_mint)?,
+        )?
+        .supply;
+        report
+            .alerts
+            .extend(monitor::check_pool(&pool.state, slot, lp_supply));
+
+        let port_reserve = Reserve::unpack(&self.chain.account_data(&reserve.address)?)?;
+        report.alerts.extend(monitor::check_reserve_ltv(
+            &pool.state,
+            port_reserve.config.loan_to_value_ratio,
+            self.ltv_alert_margin,
+        ));
+
+        let obligation_key = pool.state.port_config.obligation;
+        let obligation = if obligation_key == Pubkey::default() {
+            None
+        } else {
+            let obligation = Obligation::unpack(&self.chain.account_data(&obligation_key)?)?;
+            report.alerts.extend(monitor::check_obligation(
+                &obligation,
+                self.health_alert_ratio,
+            ));
+            Some(obligation)
+        };
+
+        for alert in report.alerts.iter() {
+            if alert.is_critical() {
+                error!("pool {}: {}", pool.address, alert);
+            } else {
+                warn!("pool {}: {}", pool.address, alert);
+            }
+        }
+        if !pool.is_idle() {
+            return Ok(report);
+        }
+
+        if monitor::harvest_due(&pool.state, slot, self.harvest_margin_slots) {
+            let market = SerumMarket::from(&config.market);
+            let route_market = config.route_market.as_ref().map(SerumMarket::from);
+            let sell_route = match route_market {
+                Some(_) => Some(decode_sell_route(
+                    &self.chain.account_data(&pda::sell_route(&pool.address).0)?,
+                )?),
+                None => None,
+            };
+            let staking = PortStaking::from(&config.staking);
+            let sub_reward = match staking.sub_reward_supply {
+                Some(_) => Some(decode_sub_reward(
+                    &self.chain.account_data(&pda::sub_reward(&pool.address).0)?,
+                )?),
+                None => None,
+            };
+            //settling picks up whatever earlier swaps left unsettled on the markets
+            let mut instructions = vec![
+                instructions::claim_and_sell(
+                    &pool,
+                    &market,
+                    &staking,
+                    config.harvester_liquidity_wallet,
+                    sell_route.as_ref().zip(route_market.as_ref()),
+                    sub_reward.as_ref(),
+                )?,
+                instructions::settle_open_orders(&pool, &market, sell_route.as_ref())?,
+            ];
+            if let (Some(sell_route), Some(route_market)) = (&sell_route, &route_market) {
+                instructions.push(instructions::settle_route_open_orders(
+                    &pool,
+                    sell_route,
+                    route_market,
+                )?);
+            }
+            let signature = self.chain.send(&instructions, &self.payer)?;
+            info!("pool {}: claim_and_sell {}", pool.address, signature);
+            //sell_sub_reward keeps its own min_harvest_interval from the sub reward's last sale
+            let sub_reward = sub_reward.filter(|sub_reward| {
+                slot.saturating_sub(sub_reward.last_sold_slot)
+                    >= pool.state.harvest_config.min_harvest_interval
+            });
+            if let (Some(sub_reward), Some(market)) = (&sub_reward, &config.sub_reward_market) {
+                let market = SerumMarket::from(market);
+                let instructions = [
+                    instructions::sell_sub_reward(
+                        &pool,
+                        sub_reward,
+                        &market,
+                        config.harvester_liquidity_wallet,
+                    )?,
+                    instructions::settle_sub_reward_open_orders(&pool, sub_reward, &market)?,
+                ];
+                let signature = self.chain.send(&instructions, &self.payer)?;
+                info!("pool {}: sell_sub_reward {}", pool.address, signature);
+            }
+            report.harvested = true;
+        }
+
+        if config.auto_compound && obligation.is_some() {
+            let liquidity = self.token_amount(&pool.state.generic_config.liquidity_supply)?;
+            //auto_compound would re-deposit liquidity freed for queued withdrawals
+            let pending_lp = pool.state.withdraw_queue.pending_lp;
+            if liquidity >= pool.state.port_config.min_deposit && pending_lp == 0 {
+                let signature = self
+                    .chain
+                    .send(&instructions::auto_compound(&pool, &reserve)?, &self.payer)?;
+                info!("pool {}: auto_compound {}", pool.address, signature);
+                report.compounded = true;
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::{AccountSerialize, Discriminator};
+    use pole_client::pole::states::{
+        PolePortPool, SellRoute, CURRENT_LAYOUT_VERSION, POOL_AUTHORITY,
+    };
+    use port_variable_rate_lending_instructions::state::PROGRAM_VERSION;
+    use solana_sdk::program_option::COption;
+
+    use super::*;
+    use crate::chain::mock::MockChain;
+
+    const LAST_SOLD_SLOT: u64 = 1000;
+    const STALE_SLOT: u64 = 100;
+    const MARGIN: u64 = 10;
+
+    fn market_config() -> String {
+        let key = || Pubkey::new_unique().to_string();
+        format!(
+            "{{market: {}, request_queue: {}, event_queue: {}, bids: {}, asks: {}, coin_vault: {}, pc_vault: {}, vault_signer: {}}}",
+            key(), key(), key(), key(), key(), key(), key(), key()
+        )
+    }
+
+    fn pool_config() -> PoolConfig {
+        let key = || Pubkey::new_unique().to_string();
+        let market = market_config();
+        serde_yaml::from_str(&format!(
+            "address: {}\nharvester_liquidity_wallet: {}\nreserve: {{address: {}, lending_market: {}, liquidity_mint: {}, \
+             liquidity_supply: {}, liquidity_fee_receiver: {}, collateral_mint: {}, collateral_supply: {}, staking_pool: {}}}\n\
+             market: {}\nstaking: {{staking_pool: {}, reward_supply: {}}}\n",
+            key(), key(), key(), key(), key(), key(), key(), key(), key(), key(), market, key(), key()
+        ))
+        .unwrap()
+    }
+
+    /// A valid, idle pool without obligation, last harvested at LAST_SOLD_SLOT.
+    fn setup(chain: &MockChain, config: &PoolConfig) -> PolePortPool {
+        let mut pool: PolePortPool = bytemuck::Zeroable::zeroed();
+        pool.layout_version = CURRENT_LAYOUT_VERSION;
+        pool.authority_version = POOL_AUTHORITY;
+        pool.generic_config.bump = pda::pole_authority(&config.address).1 as u64;
+        pool.generic_config.lp_mint = Pubkey::new_unique();
+        pool.port_config.min_deposit = 100;
+        pool.port_config.port_iterate = 5;
+        pool.port_config.port_reserve_percentage = 5;
+        pool.port_state.last_sold_slot = LAST_SOLD_SLOT;
+        pool.harvest_config.stale_slot = STALE_SLOT;
+        write_pool(chain, config, &pool);
+
+        let mut lp_mint = vec![0; Mint::LEN];
+        Mint::pack(
+            Mint {
+                mint_authority: COption::None,
+                supply: 0,
+                decimals: 6,
+                is_initialized: true,
+                freeze_authority: COption::None,
+            },
+            &mut lp_mint,
+        )
+        .unwrap();
+        chain.set_account(pool.generic_config.lp_mint, lp_mint);
+
+        let mut reserve = Reserve::default();
+        reserve.version = PROGRAM_VERSION;
+        reserve.config.loan_to_value_ratio = 75;
+        let mut reserve_data = vec![0; Reserve::LEN];
+        Reserve::pack(reserve, &mut reserve_data).unwrap();
+        chain.set_account(config.reserve.address, reserve_data);
+        pool
+    }
+
+    fn write_pool(chain: &MockChain, config: &PoolConfig, pool: &PolePortPool) {
+        let mut data = PolePortPool::discriminator().to_vec();
+        data.extend_from_slice(bytemuck::bytes_of(pool));
+        chain.set_account(config.address, data);
+    }
+
+    fn keeper(chain: MockChain) -> Keeper<MockChain> {
+        Keeper {
+            chain,
+            payer: Keypair::new(),
+            harvest_margin_slots: MARGIN,
+            health_alert_ratio: 0.9,
+            ltv_alert_margin: 5,
+        }
+    }
+
+    #[test]
+    fn harvests_before_the_stale_window_closes() {
+        let config = pool_config();
+        let chain = MockChain::default();
+        setup(&chain, &config);
+        chain.slot.set(LAST_SOLD_SLOT + STALE_SLOT - MARGIN);
+        let keeper = keeper(chain);
+
+        let report = keeper.tick(&config).unwrap();
+        assert!(report.harvested);
+        let sent = keeper.chain.sent.borrow();
+        assert_eq!(sent.len(), 1);
+        //claim_and_sell, then settle_open_orders
+        assert_eq!(sent[0].len(), 2);
+        assert!(sent[0]
+            .iter()
+            .all(|ix| ix.program_id == pole_client::pole::ID));
+    }
+
+    #[test]
+    fn settles_the_sell_route_with_the_harvest() {
+        let mut config = pool_config();
+        config.route_market = Some(serde_yaml::from_str(&market_config()).unwrap());
+        let chain = MockChain::default();
+        setup(&chain, &config);
+        let sell_route = SellRoute {
+            pool: config.address,
+            market: Pubkey::new_unique(),
+            open_orders: Pubkey::new_unique(),
+            wallet: Pubkey::new_unique(),
+            _padding: [0; 4],
+        };
+        let mut data = vec![];
+        sell_route.try_serialize(&mut data).unwrap();
+        chain.set_account(pda::sell_route(&config.address).0, data);
+        chain.slot.set(LAST_SOLD_SLOT + STALE_SLOT - MARGIN);
+        let keeper = keeper(chain);
+
+        assert!(keeper.tick(&config).unwrap().harvested);
+        let sent = keeper.chain.sent.borrow();
+        //claim_and_sell, then settle_open_orders on the harvest market and the route's
+        assert_eq!(sent[0].len(), 3);
+        assert!(sent[0][1]
+            .accounts
+            .iter()
+            .any(|account| account.pubkey == sell_route.wallet));
+        assert!(sent[0][2]
+            .accounts
+            .iter()
+            .any(|account| account.pubkey == sell_route.open_orders));
+    }
+
+    #[test]
+    fn does_not_harvest_a_fresh_pool() {
+        let config = pool_config();
+        let chain = MockChain::default();
+        setup(&chain, &config);
+        chain.slot.set(LAST_SOLD_SLOT + STALE_SLOT - MARGIN - 1);
+        let keeper = keeper(chain);
+
+        let report = keeper.tick(&config).unwrap();
+        assert!(!report.harvested);
+        assert!(keeper.chain.sent.borrow().is_empty());
+    }
+
+    #[test]
+    fn does_not_harvest_before_min_harvest_interval() {
+        let config = pool_config();
+        let chain = MockChain::default();
+        let mut pool = setup(&chain, &config);
+        pool.harvest_config.min_harvest_interval = STALE_SLOT - 1;
+        write_pool(&chain, &config, &pool);
+        chain.slot.set(LAST_SOLD_SLOT + STALE_SLOT - MARGIN);
+        let keeper = keeper(chain);
+
+        assert!(!keeper.tick(&config).unwrap().harvested);
+        assert!(keeper.chain.sent.borrow().is_empty());
+    }
+
+    #[test]
+    fn only_alerts_on_a_stuck_pool() {
+        let config = pool_config();
+        let chain = MockChain::default();
+        let mut pool = setup(&chain, &config);
+        pool.port_state.deposit_verified = 1;
+        write_pool(&chain, &config, &pool);
+        chain.slot.set(LAST_SOLD_SLOT + STALE_SLOT);
+        let keeper = keeper(chain);
+
+        let report = keeper.tick(&config).unwrap();
+        assert!(report.alerts.contains(&Alert::StuckPortState));
+        assert!(!report.harvested);
+        assert!(keeper.chain.sent.borrow().is_empty());
+    }
+}
@@ -0,0 +1,67 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use log::{debug, error};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::read_keypair_file;
+use structopt::StructOpt;
+
+mod chain;
+mod config;
+mod keeper;
+mod monitor;
+
+use config::KeeperConfig;
+use keeper::Keeper;
+
+#[derive(StructOpt)]
+#[structopt(
+    name = "pole-keeper",
+    about = "Harvests, compounds and monitors pole pools"
+)]
+struct Opts {
+    #[structopt(long, parse(from_os_str))]
+    config: PathBuf,
+    /// Run a single pass over the pools and exit
+    #[structopt(long)]
+    once: bool,
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let opts = Opts::from_args();
+    let config = KeeperConfig::load(&opts.config)?;
+    let payer = read_keypair_file(expand_home(&config.keypair))
+        .map_err(|e| format!("unable to read keypair {}: {}", config.keypair, e))?;
+    let chain = RpcClient::new_with_commitment(config.url.clone(), CommitmentConfig::confirmed());
+    let keeper = Keeper::new(chain, payer, &config);
+
+    loop {
+        for pool in config.pools.iter() {
+            match keeper.tick(pool) {
+                Ok(report) => debug!(
+                    "pool {}: {} alerts, harvested {}, compounded {}",
+                    pool.address,
+                    report.alerts.len(),
+                    report.harvested,
+                    report.compounded
+                ),
+                Err(e) => error!("pool {}: {}", pool.address, e),
+            }
+        }
+        if opts.once {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(config.poll_interval_ms));
+    }
+}
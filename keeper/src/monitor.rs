@@ -0,0 +1,250 @@
+use std::fmt;
+
+use pole_client::pole::states::{PolePortPool, CURRENT_LAYOUT_VERSION};
+use port_variable_rate_lending_instructions::math::Decimal;
+use port_variable_rate_lending_instructions::state::Obligation;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Alert {
+    InvalidConfig(&'static str),
+    StuckPortState,
+    HarvestOverdue {
+        slots_since_sold: u64,
+        stale_slot: u64,
+    },
+    LPSupplyMismatch {
+        lp_amount: u64,
+        lp_supply: u64,
+    },
+    ObligationShape {
+        deposits: usize,
+        borrows: usize,
+    },
+    ObligationHealth {
+        ratio: f64,
+    },
+    ReserveLtv {
+        ltv: u8,
+        port_reserve_percentage: u64,
+    },
+    WithdrawalsPending {
+        pending_lp: u64,
+    },
+}
+
+impl Alert {
+    /// Critical alerts mean deposits or redeems are failing right now.
+    pub fn is_critical(&self) -> bool {
+        match self {
+            Alert::InvalidConfig(_)
+            | Alert::StuckPortState
+            | Alert::HarvestOverdue { .. }
+            | Alert::LPSupplyMismatch { .. }
+            | Alert::ObligationShape { .. } => true,
+            Alert::ReserveLtv {
+                ltv,
+                port_reserve_percentage,
+            } => (*ltv as u64) <= *port_reserve_percentage,
+            Alert::ObligationHealth { ratio } => *ratio >= 1.0,
+            Alert::WithdrawalsPending { .. } => false,
+        }
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Alert::InvalidConfig(config) => write!(f, "{} does not validate", config),
+            Alert::StuckPortState => write!(
+                f,
+                "port state is not idle between transactions, run recover_port_state"
+            ),
+            Alert::HarvestOverdue {
+                slots_since_sold,
+                stale_slot,
+            } => write!(
+                f,
+                "last harvest {} slots ago, deposits fail after {}",
+                slots_since_sold, stale_slot
+            ),
+            Alert::LPSupplyMismatch {
+                lp_amount,
+                lp_supply,
+            } => write!(
+                f,
+                "pool lp_amount {} does not match LP mint supply {}",
+                lp_amount, lp_supply
+            ),
+            Alert::ObligationShape { deposits, borrows } => write!(
+                f,
+                "obligation has {} deposits and {} borrows, at most one of each is supported",
+                deposits, borrows
+            ),
+            Alert::ObligationHealth { ratio } => write!(
+                f,
+                "obligation borrowed value is {:.2}% of its liquidation threshold",
+                ratio * 100.0
+            ),
+            Alert::ReserveLtv {
+                ltv,
+                port_reserve_percentage,
+            } => write!(
+                f,
+                "reserve LTV {}% leaves little room over port_reserve_percentage {}%",
+                ltv, port_reserve_percentage
+            ),
+            Alert::WithdrawalsPending { pending_lp } => write!(
+                f,
+                "{} LP waits in the withdraw queue, run unwind_for_withdrawals and process_withdraw_request",
+                pending_lp
+            ),
+        }
+    }
+}
+
+pub fn slots_since_sold(pool: &PolePortPool, slot: u64) -> u64 {
+    slot.saturating_sub(pool.port_state.last_sold_slot)
+}
+
+/// Harvest `margin` slots before deposits start failing with `PortNotSell`,
+/// but never before `min_harvest_interval` allows it.
+pub fn harvest_due(pool: &PolePortPool, slot: u64, margin: u64) -> bool {
+    let since = slots_since_sold(pool, slot);
+    since >= pool.harvest_config.min_harvest_interval
+        && since.saturating_add(margin) >= pool.harvest_config.stale_slot()
+}
+
+/// The checks `valid_pole_pool` and the deposit/redeem constraints make, seen from outside.
+pub fn check_pool(pool: &PolePortPool, slot: u64, lp_supply: u64) -> Vec<Alert> {
+    let mut alerts = vec![];
+    if pool.layout_version != CURRENT_LAYOUT_VERSION {
+        alerts.push(Alert::InvalidConfig("layout_version"));
+    }
+    if !pool.generic_config.validate() {
+        alerts.push(Alert::InvalidConfig("GenericPoolConfig"));
+    }
+    if !pool.port_config.validate() {
+        alerts.push(Alert::InvalidConfig("PortConfig"));
+    }
+    if !pool.harvest_config.validate() {
+        alerts.push(Alert::InvalidConfig("HarvestConfig"));
+    }
+    if !pool.fee_schedule.validate() {
+        alerts.push(Alert::InvalidConfig("FeeSchedule"));
+    }
+    let port_state = &pool.port_state;
+    if port_state.deposit_verified != 0
+        || port_state.redeem_verified != 0
+        || port_state.leverage != 0
+    {
+        alerts.push(Alert::StuckPortState);
+    }
+    let since = slots_since_sold(pool, slot);
+    let stale_slot = pool.harvest_config.stale_slot();
+    if pool.port_state.last_sold_slot != 0 && since > stale_slot {
+        alerts.push(Alert::HarvestOverdue {
+            slots_since_sold: since,
+            stale_slot,
+        });
+    }
+    let lp_amount = pool.basic_state.lp_amount;
+    if lp_amount != lp_supply {
+        alerts.push(Alert::LPSupplyMismatch {
+            lp_amount,
+            lp_supply,
+        });
+    }
+    let pending_lp = pool.withdraw_queue.pending_lp;
+    if pending_lp > 0 {
+        alerts.push(Alert::WithdrawalsPending { pending_lp });
+    }
+    alerts
+}
+
+fn ratio(numerator: Decimal, denominator: Decimal) -> Option<f64> {
+    let numerator = numerator.to_scaled_val().ok()? as f64;
+    let denominator = denominator.to_scaled_val().ok()? as f64;
+    if denominator == 0.0 {
+        None
+    } else {
+        Some(numerator / denominator)
+    }
+}
+
+/// Values are as of the last obligation refresh.
+pub fn check_obligation(obligation: &Obligation, health_alert_ratio: f64) -> Vec<Alert> {
+    let mut alerts = vec![];
+    if obligation.deposits.len() > 1 || obligation.borrows.len() > 1 {
+        alerts.push(Alert::ObligationShape {
+            deposits: obligation.deposits.len(),
+            borrows: obligation.borrows.len(),
+        });
+    }
+    if let Some(ratio) = ratio(obligation.borrowed_value, obligation.unhealthy_borrow_value) {
+        if ratio >= health_alert_ratio {
+            alerts.push(Alert::ObligationHealth { ratio });
+        }
+    }
+    alerts
+}
+
+/// Deposits borrow at `ltv - port_reserve_percentage`, which must stay positive.
+pub fn check_reserve_ltv(pool: &PolePortPool, ltv: u8, margin: u64) -> Vec<Alert> {
+    let port_reserve_percentage = pool.port_config.port_reserve_percentage;
+    if (ltv as u64) < port_reserve_percentage.saturating_add(margin) {
+        vec![Alert::ReserveLtv {
+            ltv,
+            port_reserve_percentage,
+        }]
+    } else {
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(last_sold_slot: u64, stale_slot: u64, min_harvest_interval: u64) -> PolePortPool {
+        let mut pool: PolePortPool = bytemuck::Zeroable::zeroed();
+        pool.port_state.last_sold_slot = last_sold_slot;
+        pool.harvest_config.stale_slot = stale_slot;
+        pool.harvest_config.min_harvest_interval = min_harvest_interval;
+        pool
+    }
+
+    #[test]
+    fn harvest_is_due_margin_slots_before_stale() {
+        let pool = pool(1000, 100, 0);
+        assert!(!harvest_due(&pool, 1000 + 89, 10));
+        assert!(harvest_due(&pool, 1000 + 90, 10));
+        assert!(harvest_due(&pool, 1000 + 150, 10));
+    }
+
+    #[test]
+    fn harvest_waits_for_min_harvest_interval() {
+        //a margin wider than the window would harvest right away without the interval
+        let pool = pool(1000, 100, 40);
+        assert!(!harvest_due(&pool, 1000 + 39, 80));
+        assert!(harvest_due(&pool, 1000 + 40, 80));
+    }
+
+    #[test]
+    fn harvest_uses_the_default_stale_slot() {
+        let pool = pool(1000, 0, 0);
+        let stale_slot = pool.harvest_config.stale_slot();
+        assert!(stale_slot > 0);
+        assert!(!harvest_due(&pool, 1000 + stale_slot - 11, 10));
+        assert!(harvest_due(&pool, 1000 + stale_slot - 10, 10));
+    }
+
+    #[test]
+    fn stale_harvest_is_critical() {
+        let alerts = check_pool(&pool(1000, 100, 0), 1000 + 101, 0);
+        assert!(alerts.contains(&Alert::HarvestOverdue {
+            slots_since_sold: 101,
+            stale_slot: 100,
+        }));
+        assert!(alerts.iter().any(Alert::is_critical));
+    }
+}
@@ -1,3 +1,5 @@
 mod mint_accessors;
+mod program_accessors;
 
 pub use mint_accessors::*;
+pub use program_accessors::*;
@@ -0,0 +1,29 @@
+use anchor_lang::prelude::{AccountInfo, ProgramError, Pubkey};
+use anchor_lang::solana_program::bpf_loader_upgradeable;
+
+/// Whether `signer` may act as the admin of `program`: its upgrade authority when it was deployed
+/// with the upgradeable loader. A program loaded any other way, or made immutable, has no
+/// upgrade authority and takes no signer.
+pub fn is_upgrade_authority(
+    program: &AccountInfo,
+    program_data: &AccountInfo,
+    signer: &Pubkey,
+) -> Result<bool, ProgramError> {
+    if *program.key != crate::ID || !program.executable {
+        return Ok(false);
+    }
+    if *program.owner != bpf_loader_upgradeable::ID {
+        return Ok(false);
+    }
+    //UpgradeableLoaderState::Program { programdata_address }
+    let bytes = program.try_borrow_data()?;
+    if bytes.len() < 36 || bytes[4..36] != program_data.key.to_bytes() {
+        return Ok(false);
+    }
+    //UpgradeableLoaderState::ProgramData { slot, upgrade_authority_address: Option<Pubkey> }
+    let bytes = program_data.try_borrow_data()?;
+    if *program_data.owner != bpf_loader_upgradeable::ID || bytes.len() < 45 || bytes[12] != 1 {
+        return Ok(false);
+    }
+    Ok(bytes[13..45] == signer.to_bytes())
+}
@@ -75,4 +75,78 @@ pub enum PoleError {
 
     #[msg("MeetDepositLimit")]
     MeetDepositLimit,
+    #[msg("LPOutTooSmall")]
+    LPOutTooSmall,
+    #[msg("LiquidityOutTooSmall")]
+    LiquidityOutTooSmall,
+    // 335
+    #[msg("Harvested too recently")]
+    HarvestTooSoon,
+    #[msg("InvalidAuthority")]
+    InvalidAuthority,
+    #[msg("Obligation is not fully unwound")]
+    ObligationNotUnwound,
+    #[msg("Pool layout is outdated, run migrate_pool")]
+    InvalidLayoutVersion,
+    #[msg("Pool account is smaller than the current layout, run begin_pool_realloc")]
+    PoolNeedsRealloc,
+
+    // 340
+    #[msg("Registry page is full, run add_registry_page")]
+    RegistryFull,
+    #[msg("Pool is not in the registry")]
+    PoolNotRegistered,
+    #[msg("Withdraw queue is not set up")]
+    WithdrawQueueNotInitialized,
+    #[msg("Withdraw request is not in the expected status")]
+    InvalidWithdrawRequest,
+    #[msg("Not enough available liquidity to settle the withdraw request")]
+    WithdrawalNotFunded,
+
+    // 345
+    #[msg("Available liquidity already covers pending withdrawals")]
+    WithdrawalsAlreadyFunded,
+    #[msg("Withdrawals are pending")]
+    WithdrawalsPending,
+    #[msg("User position does not belong to this pool and user")]
+    InvalidUserPosition,
+    #[msg("Wallet owner is not on the pool allowlist")]
+    NotAllowlisted,
+    #[msg("Deposit exceeds the per-wallet cap")]
+    MeetWalletDepositLimit,
+
+    // 350
+    #[msg("Referrer account does not match the position")]
+    InvalidReferrer,
+    #[msg("Fee split needs 1 to 4 recipients whose bips sum to 10000")]
+    InvalidFeeSplit,
+    #[msg("Market does not trade the source token against the liquidity token")]
+    InvalidSwapMarket,
+    #[msg("Swap returned less than the minimum out")]
+    SwapOutTooSmall,
+    #[msg("Harvest route accounts do not match the pool")]
+    InvalidSellRoute,
+    // 355
+    #[msg("Sub reward accounts do not match the pool")]
+    InvalidSubReward,
+    #[msg("Pool still has LP outstanding")]
+    PoolNotEmpty,
+    #[msg("Account is part of the pool config and can't be swept")]
+    InvalidSweepAccount,
+    #[msg("Pool is being reallocated, run finish_pool_realloc")]
+    PoolReallocPending,
+    #[msg("Pool is already in the registry page")]
+    PoolAlreadyRegistered,
+
+    // 360
+    #[msg("Signer is not the upgrade authority of the program")]
+    InvalidUpgradeAuthority,
+    #[msg("Pool caps deposits per wallet, pass the user position")]
+    UserPositionRequired,
+    #[msg("Fees go to the fee split vault, change the recipients with change_fee_split")]
+    FeeSplitActive,
+    #[msg("Open orders or settle wallets do not belong to the pool")]
+    InvalidSettleAccounts,
+    #[msg("Account is not a writable registry page")]
+    InvalidRegistryPage,
 }
@@ -12,9 +12,31 @@ pub struct DidRedeem {
     pub lp_amount_burned: u64,
 }
 
+#[event]
+pub struct DidZap {
+    pub source_mint: Pubkey,
+    pub source_amount: u64,
+    pub liquidity_amount: u64,
+}
+
+#[event]
+pub struct DidZapOut {
+    pub output_mint: Pubkey,
+    pub liquidity_amount: u64,
+    pub output_amount: u64,
+}
+
 #[event]
 pub struct DidSell {
+    pub reward_mint: Pubkey,
     pub base_amount: u64,
+    pub bounty_amount: u64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct DidCompound {
+    pub liquidity_amount_compounded: u64,
     pub slot: u64,
 }
 
@@ -29,3 +51,16 @@ pub struct DidUnstake {
     pub amount: u64,
     pub slot: u64,
 }
+
+#[event]
+pub struct DidRefer {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub referrer: Pubkey,
+}
+
+#[event]
+pub struct DidPayReferral {
+    pub referrer: Pubkey,
+    pub amount: u64,
+}
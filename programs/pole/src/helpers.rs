@@ -1,15 +1,28 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar::instructions::get_instruction_relative;
+use anchor_spl::dex::serum_dex::state::MarketState;
 use anchor_spl::token;
+use anchor_spl::token::{transfer, Transfer};
 use port_variable_rate_lending_instructions::state::CollateralExchangeRate;
 
 use crate::error::PoleError;
-use crate::{PolePortAccounts, PortLendingAccounts, PortLendingLeveragingParams};
+use crate::event::{DidDeposit, DidPayReferral, DidRedeem};
+use crate::states::{
+    AllowlistEntry, FeeSplit, PolePortPool, RegistryPage, SellRoute, SubReward, UserPosition,
+    CURRENT_LAYOUT_VERSION,
+};
+use crate::{
+    PoleLPAccounts, PoleLiquidityAccounts, PolePortAccounts, PortLendingAccounts,
+    PortLendingLeveragingParams, ZapInAccounts, ZapOutAccounts, AUTO_COMPOUND_SIGHASH,
+    SUB_REWARD_ACCOUNTS, ZAP_IN_ACCOUNTS, ZAP_OUT_ACCOUNTS,
+};
 use port_anchor_adaptor::port_accessor::{
-    exchange_rate, obligation_borrows_count, obligation_deposits_count, obligation_liquidity,
+    exchange_rate, is_obligation_stale, is_reserve_stale, obligation_borrows_count,
+    obligation_deposits_count, obligation_liquidity, reserve_ltv,
 };
 use port_anchor_adaptor::{borrow, deposit_and_collateralize, repay, withdraw};
 use port_anchor_adaptor::{redeem as port_redeem, refresh_port_obligation, refresh_port_reserve};
-use solana_maths::{Decimal, Rate, TryMul};
+use solana_maths::{Decimal, Rate, TryAdd, TryMul, TrySub, U128, U192};
 
 #[inline(always)]
 pub fn port_lending_leveraging<'info, 'a>(
@@ -18,18 +31,21 @@ pub fn port_lending_leveraging<'info, 'a>(
     borrow_fraction: u64,
     iterate: u8,
     current_leverage: u8, // [0..iterate)
-    bump: u8,
+    reserved_liquidity: u64,
+    seeds: &[&[u8]],
 ) -> ProgramResult {
-    let available_liquidity = token::accessor::amount(params.user_liquidity)?;
+    let available_liquidity = token::accessor::amount(params.user_liquidity)?
+        .checked_sub(reserved_liquidity)
+        .ok_or(PoleError::MathOverflow)?;
     //assume reserve and obligation are refreshed
     deposit_and_collateralize(
-        port_accounts.create_deposit_and_collateralize_context(params, &[&[&[bump]]]),
+        port_accounts.create_deposit_and_collateralize_context(params, &[seeds]),
         available_liquidity,
     )?;
     if current_leverage < iterate.checked_sub(1).ok_or(PoleError::MathOverflow)? {
         refresh_port_reserve_and_obligation(port_accounts, params)?;
         borrow(
-            port_accounts.create_borrow_context(params, &[&[&[bump]]]),
+            port_accounts.create_borrow_context(params, &[seeds]),
             available_liquidity * borrow_fraction / 100,
         )?;
     }
@@ -42,7 +58,7 @@ pub fn port_lending_unroll<'info, 'a>(
     repay_ratio: Decimal,
     iterate: u8,
     current_leverage: u8, // [0..iterate)
-    bump: u8,
+    seeds: &[&[u8]],
     mut unroll_amount: u64,
 ) -> ProgramResult {
     //assume reserve and obligation are refreshed
@@ -72,26 +88,586 @@ pub fn port_lending_unroll<'info, 'a>(
 
     if current_leverage == 0 {
         withdraw(
-            port_accounts.create_withdraw_context(params, &[&[&[bump]]]),
+            port_accounts.create_withdraw_context(params, &[seeds]),
             withdraw_amount,
         )?;
         port_redeem(
-            port_accounts.create_redeem_context(params, &[&[&[bump]]]),
+            port_accounts.create_redeem_context(params, &[seeds]),
             withdraw_amount,
         )?;
     } else {
         repay(
-            port_accounts.create_repay_context(params, &[&[&[bump]]]),
+            port_accounts.create_repay_context(params, &[seeds]),
             repay_amount,
         )?;
         refresh_port_reserve_and_obligation(port_accounts, params)?;
 
         withdraw(
-            port_accounts.create_withdraw_context(params, &[&[&[bump]]]),
+            port_accounts.create_withdraw_context(params, &[seeds]),
+            withdraw_amount,
+        )?;
+        port_redeem(
+            port_accounts.create_redeem_context(params, &[seeds]),
+            withdraw_amount,
+        )?;
+    }
+    Ok(())
+}
+
+/// One leg of a deposit: put the pole liquidity wallet, but for the liquidity reserved for
+/// queued withdrawals, to work and advance the leverage.
+pub fn deposit_leverage_step<'info>(
+    pole_pool: &mut PolePortPool,
+    port_accounts: &PortLendingAccounts<'info>,
+    pole_liquidity_wallet: &AccountInfo<'info>,
+    pole_authority: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    clock: &Sysvar<'info, Clock>,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let ltv = reserve_ltv(&port_accounts.reserve)?;
+    let lending_leveraging_params = PortLendingLeveragingParams {
+        user_liquidity: pole_liquidity_wallet,
+        obligation_owner: pole_authority,
+        transfer_authority: pole_authority,
+        clock: &clock.to_account_info(),
+        token_program,
+    };
+
+    port_lending_leveraging(
+        port_accounts,
+        &lending_leveraging_params,
+        (ltv as u64)
+            .checked_sub(pole_pool.port_config.port_reserve_percentage)
+            .ok_or(PoleError::MathOverflow)?,
+        pole_pool.port_config.port_iterate as u8,
+        pole_pool.port_state.leverage as u8,
+        pole_pool.reserved_liquidity,
+        seeds,
+    )?;
+
+    pole_pool.port_state.leverage = pole_pool
+        .port_state
+        .leverage
+        .checked_add(1)
+        .ok_or(PoleError::MathOverflow)?;
+    Ok(())
+}
+
+/// The first auto_compound leg: the pole instructions after it have to be the other
+/// `port_iterate - 1` legs, in order.
+pub fn verify_compound_legs(transaction: &AccountInfo, port_iterate: u64) -> ProgramResult {
+    let mut count = 1;
+    let mut i = 1;
+    while count < port_iterate {
+        let ins =
+            get_instruction_relative(i, transaction).map_err(|_| PoleError::InvalidTransaction)?;
+        if ins.program_id == crate::ID {
+            if ins.data.len() < 8 || ins.data[..8] != AUTO_COMPOUND_SIGHASH {
+                return Err(PoleError::InvalidTransaction.into());
+            }
+            let crate::instruction::AutoCompound { leverage } =
+                crate::instruction::AutoCompound::deserialize(&mut &ins.data[8..])
+                    .map_err(|_| anchor_lang::__private::ErrorCode::InstructionDidNotDeserialize)?;
+            if leverage as u64 != count {
+                return Err(PoleError::InvalidTransaction.into());
+            }
+            count += 1;
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+/// Last leg of a deposit: mint LP for the liquidity the user added and reset the port state.
+pub fn finish_deposit<'info>(
+    pole_pool: &mut PolePortPool,
+    port_accounts: &PortLendingAccounts<'info>,
+    pole_liquidity_accounts: &PoleLiquidityAccounts<'info>,
+    pole_lp_accounts: &PoleLPAccounts<'info>,
+    pole_authority: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    clock: &Sysvar<'info, Clock>,
+    seeds: &[&[u8]],
+) -> Result<u64, ProgramError> {
+    let port_exchange_rate = exchange_rate(&port_accounts.reserve)?;
+    let init_port_liquidity = Decimal(U192(pole_pool.port_state.init_port_liquidity));
+
+    let after_port_liquidity = get_port_liquidity(&port_exchange_rate, &port_accounts.obligation)?;
+
+    assert_eq!(
+        token::accessor::amount(&pole_liquidity_accounts.pole_liquidity_wallet)?,
+        pole_pool.reserved_liquidity
+    );
+
+    let user_liquidity_rate = Rate(U128(pole_pool.port_state.user_liquidity_percentage));
+
+    let user_liquidity_gain = after_port_liquidity
+        .try_sub(init_port_liquidity)?
+        .try_mul(user_liquidity_rate)?
+        .try_floor_u64()?;
+    //the reserved liquidity still backs the pending requests' lp, as in process_withdraw_request
+    let exchange_rate = pole_pool.basic_state.exchange_rate(
+        after_port_liquidity
+            .try_add(pole_pool.reserved_liquidity.into())?
+            .try_sub(user_liquidity_gain.into())?,
+    )?;
+
+    let mint_amount = exchange_rate.liquidity_to_lp(user_liquidity_gain)?;
+    if mint_amount < pole_pool.port_state.min_amount_out {
+        return Err(PoleError::LPOutTooSmall.into());
+    }
+    pole_pool.basic_state.lp_amount = pole_pool
+        .basic_state
+        .lp_amount
+        .checked_add(mint_amount)
+        .ok_or(PoleError::MathOverflow)?;
+    token::mint_to(
+        pole_lp_accounts.create_mint_to_context(
+            pole_authority.clone(),
+            token_program.clone(),
+            &[seeds],
+        ),
+        mint_amount,
+    )?;
+    //reset deposit state
+    pole_pool.port_state.reset();
+    let last_sold_slot = pole_pool.port_state.last_sold_slot;
+    if last_sold_slot == 0 {
+        pole_pool.port_state.last_sold_slot = clock.slot
+    };
+
+    emit!(DidDeposit {
+        liquidity_amount_deposited: user_liquidity_gain,
+        lp_amount_minted: mint_amount,
+    });
+    Ok(mint_amount)
+}
+
+/// Allowlisted pools take the approved liquidity wallet and the AllowlistEntry of its owner
+/// as the remaining accounts of verify_deposit.
+pub fn verify_allowlisted(
+    pole_pool: &Pubkey,
+    approved_wallet: &Pubkey,
+    remaining_accounts: &[AccountInfo],
+) -> ProgramResult {
+    let (wallet, entry) = match remaining_accounts {
+        [wallet, entry, ..] => (wallet, entry),
+        _ => return Err(PoleError::NotAllowlisted.into()),
+    };
+    if wallet.key != approved_wallet {
+        return Err(PoleError::WrongWallet.into());
+    }
+    //a wallet owner without an entry passes its uncreated address
+    let entry: Account<AllowlistEntry> =
+        Account::try_from(entry).map_err(|_| PoleError::NotAllowlisted)?;
+    if entry.pool != *pole_pool || entry.wallet != token::accessor::authority(wallet)? {
+        return Err(PoleError::NotAllowlisted.into());
+    }
+    Ok(())
+}
+
+/// create_pool takes the registry page as its only remaining account, clients built before
+/// the registry leave it out and the registry owner lists their pools with register_pool.
+pub fn split_registry_page<'info>(
+    remaining_accounts: &[AccountInfo<'info>],
+) -> Result<Option<AccountLoader<'info, RegistryPage>>, ProgramError> {
+    let account = match remaining_accounts.first() {
+        Some(account) => account,
+        None => return Ok(None),
+    };
+    let registry_page: AccountLoader<RegistryPage> = AccountLoader::try_from(account)?;
+    let (index, bump) = {
+        let page = registry_page.load()?;
+        (page.index, page.bump as u8)
+    };
+    if RegistryPage::address(index, bump)? != *account.key || !account.is_writable {
+        return Err(PoleError::InvalidRegistryPage.into());
+    }
+    Ok(Some(registry_page))
+}
+
+/// A tracked wallet passes its UserPosition as the first remaining account, told apart from
+/// the reserve oracle after it by being owned by this program; untracked wallets pass none.
+pub fn split_user_position<'a, 'info>(
+    pole_pool: &Pubkey,
+    user: &Pubkey,
+    remaining_accounts: &'a [AccountInfo<'info>],
+) -> Result<
+    (
+        Option<Account<'info, UserPosition>>,
+        &'a [AccountInfo<'info>],
+    ),
+    ProgramError,
+> {
+    match remaining_accounts.split_first() {
+        Some((position, rest)) if position.owner == &crate::ID => {
+            let position: Account<UserPosition> = Account::try_from(position)?;
+            if position.pool != *pole_pool
+                || position.user != *user
+                || !position.to_account_info().is_writable
+            {
+                return Err(PoleError::InvalidUserPosition.into());
+            }
+            Ok((Some(position), rest))
+        }
+        _ => Ok((None, remaining_accounts)),
+    }
+}
+
+/// A redeem swapping its payout passes its ZapOutAccounts last, after the referrer.
+pub fn split_zap_out<'a, 'info>(
+    program_id: &Pubkey,
+    remaining_accounts: &'a [AccountInfo<'info>],
+    zap_out: bool,
+) -> Result<(Option<ZapOutAccounts<'info>>, &'a [AccountInfo<'info>]), ProgramError> {
+    if !zap_out {
+        return Ok((None, remaining_accounts));
+    }
+    let (rest, mut accounts) =
+        remaining_accounts.split_at(remaining_accounts.len().saturating_sub(ZAP_OUT_ACCOUNTS));
+    let zap_out = ZapOutAccounts::try_accounts(program_id, &mut accounts, &[])?;
+    Ok((Some(zap_out), rest))
+}
+
+/// A first deposit leg swapping into the liquidity passes its ZapInAccounts last, after the oracle.
+pub fn split_zap_in<'a, 'info>(
+    program_id: &Pubkey,
+    remaining_accounts: &'a [AccountInfo<'info>],
+    zap_in: bool,
+) -> Result<(Option<ZapInAccounts<'info>>, &'a [AccountInfo<'info>]), ProgramError> {
+    if !zap_in {
+        return Ok((None, remaining_accounts));
+    }
+    let (rest, mut accounts) =
+        remaining_accounts.split_at(remaining_accounts.len().saturating_sub(ZAP_IN_ACCOUNTS));
+    let zap_in = ZapInAccounts::try_accounts(program_id, &mut accounts, &[])?;
+    Ok((Some(zap_in), rest))
+}
+
+/// `sub_reward` is the pool's SubReward, the staking pool's sub reward supply and SubReward.supply.
+pub fn check_sub_reward(pole_pool: &Pubkey, sub_reward: &[AccountInfo]) -> ProgramResult {
+    if sub_reward.len() != SUB_REWARD_ACCOUNTS {
+        return Err(PoleError::InvalidSubReward.into());
+    }
+    let config: Account<SubReward> = Account::try_from(&sub_reward[0])?;
+    if config.pool != *pole_pool || config.supply != sub_reward[2].key() {
+        return Err(PoleError::InvalidSubReward.into());
+    }
+    Ok(())
+}
+
+/// The first `count` accounts and the rest, `error` when there are fewer.
+pub fn split_accounts<'a, 'info>(
+    accounts: &'a [AccountInfo<'info>],
+    count: usize,
+    error: PoleError,
+) -> Result<(&'a [AccountInfo<'info>], &'a [AccountInfo<'info>]), ProgramError> {
+    if accounts.len() < count {
+        return Err(error.into());
+    }
+    Ok(accounts.split_at(count))
+}
+
+/// Closes an account owned by the program, its rent goes to `destination`; the runtime
+/// deletes it at the end of the transaction.
+pub fn close_program_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+    **destination.lamports.borrow_mut() = destination
+        .lamports()
+        .checked_add(account.lamports())
+        .ok_or(PoleError::MathOverflow)?;
+    **account.lamports.borrow_mut() = 0;
+    account.try_borrow_mut_data()?.fill(0);
+    Ok(())
+}
+
+/// Pays `fee_vault` out to the split's recipients by their bips, the last one taking the
+/// rounding remainder; `recipients` are in fee_split order.
+pub fn distribute_fee_vault<'info>(
+    fee_split: &FeeSplit,
+    fee_vault: &AccountInfo<'info>,
+    recipients: &[AccountInfo<'info>],
+    pole_authority: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let recipient_count = fee_split.recipient_count as usize;
+    if recipients.len() != recipient_count {
+        return Err(PoleError::InvalidFeeSplit.into());
+    }
+
+    let total = token::accessor::amount(fee_vault)?;
+    let mut distributed = 0u64;
+    for (i, recipient) in recipients.iter().enumerate() {
+        if *recipient.key != fee_split.recipients[i] {
+            return Err(PoleError::InvalidFeeSplit.into());
+        }
+        let share = if i + 1 == recipient_count {
+            total - distributed
+        } else {
+            Decimal::from_bips(fee_split.bips[i])
+                .try_mul(total)?
+                .try_floor_u64()?
+        };
+        distributed += share;
+        if share > 0 {
+            transfer(
+                CpiContext::new_with_signer(
+                    token_program.clone(),
+                    Transfer {
+                        from: fee_vault.clone(),
+                        to: recipient.clone(),
+                        authority: pole_authority.clone(),
+                    },
+                    &[seeds],
+                ),
+                share,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// The (coin, pc) wallets settle_open_orders credits `open_orders` to: those of the harvest
+/// market, the sell route or the sub reward, the latter two read from `remaining_accounts`.
+pub fn settle_wallets(
+    pole_pool: &PolePortPool,
+    pole_pool_key: &Pubkey,
+    open_orders: &Pubkey,
+    remaining_accounts: &[AccountInfo],
+) -> Result<(Pubkey, Pubkey), ProgramError> {
+    let liquidity_supply = pole_pool.generic_config.liquidity_supply;
+    let sell_route = remaining_accounts
+        .first()
+        .and_then(|account| Account::<SellRoute>::try_from(account).ok())
+        .filter(|sell_route| sell_route.pool == *pole_pool_key);
+    let sub_reward = remaining_accounts
+        .first()
+        .and_then(|account| Account::<SubReward>::try_from(account).ok())
+        .filter(|sub_reward| sub_reward.pool == *pole_pool_key);
+    if *open_orders == pole_pool.serum_config.port_open_orders {
+        let pc_wallet = sell_route.map_or(liquidity_supply, |sell_route| sell_route.wallet);
+        return Ok((pole_pool.port_config.port_supply, pc_wallet));
+    }
+    match (sell_route, sub_reward) {
+        (Some(sell_route), _) if sell_route.open_orders == *open_orders => {
+            Ok((liquidity_supply, sell_route.wallet))
+        }
+        (_, Some(sub_reward)) if sub_reward.open_orders == *open_orders => {
+            Ok((sub_reward.supply, liquidity_supply))
+        }
+        _ => Err(PoleError::InvalidSettleAccounts.into()),
+    }
+}
+
+/// One leg of a redeem: unroll the obligation for the leg the pool is currently at, if needed.
+pub fn redeem_unroll_step<'info>(
+    pole_pool: &PolePortPool,
+    port_accounts: &PortLendingAccounts<'info>,
+    pole_liquidity_wallet: &AccountInfo<'info>,
+    pole_authority: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    clock: &Sysvar<'info, Clock>,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    if pole_pool.port_state.leverage > 0
+        || (pole_pool.port_state.leverage == 0 && pole_pool.port_state.amount_to_unroll != 0)
+    {
+        let ltv = reserve_ltv(&port_accounts.reserve)?;
+        let lending_leveraging_params = PortLendingLeveragingParams {
+            user_liquidity: pole_liquidity_wallet,
+            obligation_owner: pole_authority,
+            transfer_authority: pole_authority,
+            clock: &clock.to_account_info(),
+            token_program,
+        };
+
+        let repay_ratio = Decimal::from_percent(
+            ltv.checked_sub(pole_pool.port_config.port_reserve_percentage as u8)
+                .ok_or(PoleError::MathOverflow)?,
+        );
+        port_lending_unroll(
+            port_accounts,
+            &lending_leveraging_params,
+            repay_ratio,
+            pole_pool.port_config.port_iterate as u8,
+            pole_pool.port_state.leverage as u8,
+            seeds,
+            pole_pool.port_state.amount_to_unroll,
+        )?;
+    }
+    Ok(())
+}
+
+fn market_mint(words: [u64; 4]) -> Pubkey {
+    let mut bytes = [0u8; 32];
+    for (chunk, word) in bytes.chunks_mut(8).zip(words.iter()) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    Pubkey::new_from_array(bytes)
+}
+
+/// The mint `market` pays out when selling its coin.
+pub fn pc_mint(market: &MarketState) -> Pubkey {
+    market_mint(market.pc_mint)
+}
+
+/// Whether swapping `from_mint` into `to_mint` on `market` is an ask (`from_mint` is the coin)
+/// or a bid (`from_mint` is the pc).
+pub fn is_ask_into(
+    market: &MarketState,
+    from_mint: &Pubkey,
+    to_mint: &Pubkey,
+) -> Result<bool, ProgramError> {
+    let coin_mint = market_mint(market.coin_mint);
+    let pc_mint = market_mint(market.pc_mint);
+    if coin_mint == *from_mint && pc_mint == *to_mint {
+        Ok(true)
+    } else if pc_mint == *from_mint && coin_mint == *to_mint {
+        Ok(false)
+    } else {
+        Err(PoleError::InvalidSwapMarket.into())
+    }
+}
+
+/// The referrer's fee account, the last remaining account whenever `referrer` is set;
+/// `None` when there is no referrer or its account is no longer a token account.
+pub fn active_referrer<'a, 'info>(
+    referrer: &Pubkey,
+    remaining_accounts: &'a [AccountInfo<'info>],
+    token_program: &AccountInfo<'info>,
+) -> Result<Option<&'a AccountInfo<'info>>, ProgramError> {
+    if *referrer == Pubkey::default() {
+        return Ok(None);
+    }
+    match remaining_accounts.last() {
+        Some(referrer_fee_account) if referrer_fee_account.key == referrer => {
+            Ok(Some(referrer_fee_account).filter(|account| account.owner == token_program.key))
+        }
+        _ => Err(PoleError::InvalidReferrer.into()),
+    }
+}
+
+/// Splits `fee` out of `from` between the pool fee account and the referrer, if any.
+pub fn pay_withdraw_fee<'info>(
+    pole_pool: &PolePortPool,
+    from: &AccountInfo<'info>,
+    pole_fee_account: &AccountInfo<'info>,
+    referrer_fee_account: Option<&AccountInfo<'info>>,
+    pole_authority: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    fee: u64,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let referral = match referrer_fee_account {
+        Some(_) => Decimal::from_bips(pole_pool.referral_fee_bips)
+            .try_mul(fee)?
+            .try_floor_u64()?,
+        None => 0,
+    };
+    let fee_transfer = |to: &AccountInfo<'info>, amount: u64| {
+        transfer(
+            CpiContext::new_with_signer(
+                token_program.clone(),
+                Transfer {
+                    from: from.clone(),
+                    to: to.clone(),
+                    authority: pole_authority.clone(),
+                },
+                &[seeds],
+            ),
+            amount,
+        )
+    };
+    fee_transfer(
+        pole_fee_account,
+        fee.checked_sub(referral).ok_or(PoleError::MathOverflow)?,
+    )?;
+    if let (Some(referrer_fee_account), true) = (referrer_fee_account, referral > 0) {
+        fee_transfer(referrer_fee_account, referral)?;
+        emit!(DidPayReferral {
+            referrer: referrer_fee_account.key(),
+            amount: referral,
+        });
+    }
+    Ok(())
+}
+
+/// Pay out a fully unrolled redeem, minus the withdraw fee, and return the payout.
+//recover_port_state passes no referrer, the whole fee goes to the pool then
+pub fn finish_redeem<'info>(
+    pole_pool: &mut PolePortPool,
+    pole_liquidity_accounts: &PoleLiquidityAccounts<'info>,
+    pole_fee_account: &AccountInfo<'info>,
+    referrer_fee_account: Option<&AccountInfo<'info>>,
+    pole_authority: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    seeds: &[&[u8]],
+) -> Result<u64, ProgramError> {
+    let fee_rate = Decimal::from_bips(pole_pool.redeem_fee_bips);
+
+    let fee = fee_rate
+        .try_mul(pole_pool.port_state.redeem_amount)?
+        .try_ceil_u64()?;
+    let redeem_exclude_fee = pole_pool
+        .port_state
+        .redeem_amount
+        .checked_sub(fee)
+        .ok_or(PoleError::MathOverflow)?;
+    if redeem_exclude_fee < pole_pool.port_state.min_amount_out {
+        return Err(PoleError::LiquidityOutTooSmall.into());
+    }
+
+    pay_withdraw_fee(
+        pole_pool,
+        &pole_liquidity_accounts.pole_liquidity_wallet,
+        pole_fee_account,
+        referrer_fee_account,
+        pole_authority,
+        token_program,
+        fee,
+        seeds,
+    )?;
+    transfer(
+        pole_liquidity_accounts.create_transfer_pole_to_user_context(
+            pole_authority.clone(),
+            token_program.clone(),
+            &[seeds],
+        ),
+        redeem_exclude_fee,
+    )?;
+    pole_pool.port_state.is_redeemed = 1;
+    emit!(DidRedeem {
+        liquidity_amount_redeemed: pole_pool.port_state.redeem_amount,
+        lp_amount_burned: pole_pool.redeem_lp_amount,
+    });
+    Ok(redeem_exclude_fee)
+}
+
+/// Repay `repay_amount` of the borrow and withdraw `withdraw_amount` of collateral back to liquidity.
+#[inline(always)]
+pub fn port_lending_unwind<'info, 'a>(
+    port_accounts: &PortLendingAccounts<'info>,
+    params: &PortLendingLeveragingParams<'info, 'a>,
+    repay_amount: u64,
+    withdraw_amount: u64,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    //assume reserve and obligation are refreshed
+    if repay_amount != 0 {
+        repay(
+            port_accounts.create_repay_context(params, &[seeds]),
+            repay_amount,
+        )?;
+        refresh_port_reserve_and_obligation(port_accounts, params)?;
+    }
+    if withdraw_amount != 0 {
+        withdraw(
+            port_accounts.create_withdraw_context(params, &[seeds]),
             withdraw_amount,
         )?;
         port_redeem(
-            port_accounts.create_redeem_context(params, &[&[&[bump]]]),
+            port_accounts.create_redeem_context(params, &[seeds]),
             withdraw_amount,
         )?;
     }
@@ -103,8 +679,40 @@ pub fn refresh_port_reserve_and_obligation<'info>(
     port_accounts: &PortLendingAccounts<'info>,
     params: &PortLendingLeveragingParams<'info, '_>,
 ) -> ProgramResult {
-    let obligation = &port_accounts.obligation;
     refresh_port_reserve(port_accounts.create_fresh_reserve_context(params, vec![]))?;
+    refresh_obligation(port_accounts, params)
+}
+
+/// Refresh the reserve, and the obligation if `with_obligation`, only when they are stale.
+/// `oracles` are handed to the reserve refresh, so it also works for reserves with a price oracle.
+pub fn refresh_port_if_stale<'info>(
+    port_accounts: &PortLendingAccounts<'info>,
+    params: &PortLendingLeveragingParams<'info, '_>,
+    oracles: &[AccountInfo<'info>],
+    with_obligation: bool,
+) -> ProgramResult {
+    if is_reserve_stale(&port_accounts.reserve)? {
+        refresh_port_reserve(port_accounts.create_fresh_reserve_context(params, oracles.to_vec()))?;
+    }
+    if with_obligation && is_obligation_stale(&port_accounts.obligation)? {
+        refresh_obligation(port_accounts, params)?;
+    }
+
+    if is_reserve_stale(&port_accounts.reserve)? {
+        return Err(PoleError::ReserveStale.into());
+    }
+    if with_obligation && is_obligation_stale(&port_accounts.obligation)? {
+        return Err(PoleError::ObligationStale.into());
+    }
+    Ok(())
+}
+
+#[inline(always)]
+fn refresh_obligation<'info>(
+    port_accounts: &PortLendingAccounts<'info>,
+    params: &PortLendingLeveragingParams<'info, '_>,
+) -> ProgramResult {
+    let obligation = &port_accounts.obligation;
     if obligation_deposits_count(obligation)? == 0 && obligation_borrows_count(obligation)? == 0 {
         Ok(())
     } else if obligation_deposits_count(obligation)? == 1
@@ -132,8 +740,27 @@ macro_rules! assert_state {
 }
 pub fn valid_pole_pool<T: PolePortAccounts>(ctx: &Context<T>) -> ProgramResult {
     let pole_pool = ctx.accounts.get_pole_pool()?;
+    if pole_pool.layout_version != CURRENT_LAYOUT_VERSION {
+        return Err(PoleError::InvalidLayoutVersion.into());
+    }
+    valid_pool_state(&pole_pool)
+}
+
+/// For the instructions taking liquidity out of a pool: a pool on an older layout stays
+/// usable for them until migrate_pool, every layout change so far was carved out of zeroed
+/// padding that reads as its legacy behaviour. Anything else waits for the migration.
+pub fn valid_outdated_pole_pool<T: PolePortAccounts>(ctx: &Context<T>) -> ProgramResult {
+    let pole_pool = ctx.accounts.get_pole_pool()?;
+    if pole_pool.layout_version > CURRENT_LAYOUT_VERSION {
+        return Err(PoleError::InvalidLayoutVersion.into());
+    }
+    valid_pool_state(&pole_pool)
+}
+
+fn valid_pool_state(pole_pool: &PolePortPool) -> ProgramResult {
     assert_state!(pole_pool.generic_config.validate(), "Generic config")?;
     assert_state!(pole_pool.port_config.validate(), "Port config")?;
+    assert_state!(pole_pool.harvest_config.validate(), "Harvest config")?;
     assert_state!(
         pole_pool.port_config.port_iterate > pole_pool.port_state.leverage,
         "Leverage sanity check"
@@ -164,6 +791,7 @@ pub fn valid_pole_pool<T: PolePortAccounts>(ctx: &Context<T>) -> ProgramResult {
                 && (unsafe { *init_user_liquidity_percentage == Rate::zero().0 .0 })
                 && (pole_pool.port_state.amount_to_unroll == 0)
                 && (pole_pool.port_state.redeem_amount == 0)
+                && (pole_pool.port_state.min_amount_out == 0)
         } else if pole_pool.basic_state.lp_amount != 0 {
             unsafe { *init_liquidity != Decimal::zero().0 .0 }
         } else {
@@ -1,15 +1,23 @@
 use std::cell::Ref;
 use std::mem::size_of;
 
-use crate::adaptors::supply;
+use crate::adaptors::{decimal, is_upgrade_authority, supply};
 use crate::error::PoleError;
-use crate::states::{PolePortPool, UserBalance, DISCRIMINATOR_SIZE};
+use crate::states::{
+    AllowlistEntry, FeeSplit, GenericPoolConfig, PolePortPool, PoolRealloc, PoolRegistry,
+    PortState, RegistryPage, SellRoute, SubReward, UserBalance, UserPosition, WithdrawRequest,
+    ALLOWLIST_SEED, CURRENT_LAYOUT_VERSION, DISCRIMINATOR_SIZE, FEE_SPLIT_SEED, LEGACY_AUTHORITY,
+    MAX_FEE_RECIPIENTS, MAX_FEE_TIERS, MAX_HARVEST_BOUNTY_BIPS, MAX_REFERRAL_FEE_BIPS,
+    POOL_AUTHORITY, POSITION_SEED, REALLOC_SEED, REGISTRY_SEED, SELL_ROUTE_SEED, SUB_REWARD_SEED,
+    WITHDRAW_PENDING, WITHDRAW_REQUEST_SEED, WITHDRAW_SETTLED,
+};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::program_pack::Pack;
 use anchor_spl::dex;
 use anchor_spl::dex::serum_dex::state::OpenOrders;
-use anchor_spl::dex::InitOpenOrders;
-use anchor_spl::token::{Burn, MintTo, Transfer};
+use anchor_spl::dex::{CloseOpenOrders, InitOpenOrders, SettleFunds};
+use anchor_spl::token::accessor::{authority, mint};
+use anchor_spl::token::{Burn, CloseAccount, MintTo, SetAuthority, Transfer};
 use port_anchor_adaptor::port_accessor::{
     is_obligation_stale, is_reserve_stale, obligation_borrows_count, obligation_deposits_count,
 };
@@ -18,9 +26,8 @@ use port_lending::state::Obligation;
 use port_staking::state::StakeAccount;
 use port_staking_instructions as port_staking;
 use port_variable_rate_lending_instructions as port_lending;
-use serum_swap::cpi::accounts::{MarketAccounts as SwapMarketAccounts, Swap};
+use serum_swap::cpi::accounts::{MarketAccounts as SwapMarketAccounts, Swap, SwapTransitive};
 
-const STALE_SLOT: u64 = 50;
 const MIN_LIQUIDITY_CAP: u64 = 1_000_000;
 #[derive(AnchorDeserialize, AnchorSerialize, Debug)]
 pub struct InitParams {
@@ -33,6 +40,18 @@ pub struct InitParams {
     pub reserve: Pubkey,
 }
 
+#[derive(AnchorDeserialize, AnchorSerialize, Debug, PartialEq, Copy, Clone)]
+pub struct FeeTier {
+    pub held_slots: u64,
+    pub bips: u16,
+}
+
+#[derive(AnchorDeserialize, AnchorSerialize, Debug, PartialEq, Copy, Clone)]
+pub struct FeeRecipient {
+    pub recipient: Pubkey,
+    pub bips: u64,
+}
+
 #[derive(AnchorDeserialize, AnchorSerialize, Debug, PartialEq, Copy, Clone)]
 pub struct StakingBumps {
     pub pool_bump: u8,
@@ -43,11 +62,20 @@ pub struct StakingBumps {
 }
 
 const SERUM_PADDING: usize = 12;
+// claim_and_sell remaining accounts: the sell route's, when the harvest market's pc is not the
+// liquidity mint, then the sub reward's
+pub const SELL_ROUTE_ACCOUNTS: usize = 11;
+pub const SUB_REWARD_ACCOUNTS: usize = 3;
+// redeem_liquidity remaining accounts end with the zap out's when it swaps its payout
+pub const ZAP_OUT_ACCOUNTS: usize = 13;
+// and deposit_liquidity's first leg with the zap in's when it swaps into the liquidity
+pub const ZAP_IN_ACCOUNTS: usize = 14;
 
 pub trait PolePortAccounts {
     fn get_pole_pool(&self) -> Result<Ref<PolePortPool>, ProgramError>;
 }
 
+//remaining accounts: the registry page the pool is listed in, optional
 #[derive(Accounts, Clone)]
 #[instruction(authority_bump: u8, name: String, pda_bump: u8, init_params: InitParams)]
 pub struct CreatePool<'info> {
@@ -60,7 +88,7 @@ pub struct CreatePool<'info> {
     ]
     pub pole_pool: AccountLoader<'info, PolePortPool>,
 
-    #[account(init, payer=user, mint::authority=pole_authority, mint::decimals=6)]
+    #[account(init, payer=user, mint::authority=pole_authority, mint::decimals=decimal(&liquidity_mint)?)]
     pub lp_mint: AccountInfo<'info>,
 
     #[account(init, payer=user, token::authority=pole_authority, token::mint=liquidity_mint)]
@@ -87,7 +115,7 @@ pub struct CreatePool<'info> {
     #[account(owner=token_program.key())]
     pub port_mint: AccountInfo<'info>,
 
-    #[account(seeds=[], bump=authority_bump)]
+    #[account(seeds=[pole_pool.key().as_ref()], bump=authority_bump)]
     pub pole_authority: AccountInfo<'info>,
 
     #[account(mut)]
@@ -102,14 +130,163 @@ pub struct CreatePool<'info> {
     #[account(executable)]
     pub dex_program: AccountInfo<'info>,
 
+    //a pool closed by begin_pool_realloc keeps its authority and token accounts,
+    //its name must stay reserved until finish_pool_realloc
+    #[account(
+        constraint = pool_realloc.key() == PoolRealloc::address(&pole_pool.key()) @ PoleError::InvalidPoolState,
+        constraint = pool_realloc.data_is_empty() @ PoleError::PoolReallocPending,
+    )]
+    pub pool_realloc: AccountInfo<'info>,
+
     pub system_program: Program<'info, System>,
     pub rent: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+//creates the registry with its first page, only the program's upgrade authority may own it
+#[derive(Accounts, Clone)]
+#[instruction(bump: u8, page_bump: u8)]
+pub struct InitRegistry<'info> {
+    #[account(init, seeds = [REGISTRY_SEED, crate::ID.as_ref()], bump = bump, payer = owner, space = size_of::<PoolRegistry>() + DISCRIMINATOR_SIZE)]
+    pub registry: AccountLoader<'info, PoolRegistry>,
+    #[account(init, seeds = [REGISTRY_SEED, crate::ID.as_ref(), &0u64.to_le_bytes()], bump = page_bump, payer = owner, space = size_of::<RegistryPage>() + DISCRIMINATOR_SIZE)]
+    pub registry_page: AccountLoader<'info, RegistryPage>,
+    #[account(constraint = is_upgrade_authority(&program, &program_data, &owner.key())? @ PoleError::InvalidUpgradeAuthority)]
+    pub program: AccountInfo<'info>,
+    pub program_data: AccountInfo<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts, Clone)]
+#[instruction(bump: u8)]
+pub struct AddRegistryPage<'info> {
+    #[account(mut, seeds = [REGISTRY_SEED, crate::ID.as_ref()], bump = registry.load()?.bump as u8,
+        constraint = registry.load()?.owner == owner.key() @ PoleError::InvalidOwner,
+    )]
+    pub registry: AccountLoader<'info, PoolRegistry>,
+    #[account(init, seeds = [REGISTRY_SEED, crate::ID.as_ref(), &registry.load()?.page_count.to_le_bytes()], bump = bump, payer = owner, space = size_of::<RegistryPage>() + DISCRIMINATOR_SIZE)]
+    pub registry_page: AccountLoader<'info, RegistryPage>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+//lists a pool created before the registry, or one unregistered by mistake
+#[derive(Accounts, Clone)]
+#[instruction()]
+pub struct RegisterPool<'info> {
+    #[account(
+        constraint = pole_pool.load()?.generic_config.liquidity_supply == liquidity_supply.key() @ PoleError::InvalidLiquidityWallet,
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    pub liquidity_supply: AccountInfo<'info>,
+    #[account(seeds = [REGISTRY_SEED, crate::ID.as_ref()], bump = registry.load()?.bump as u8,
+        constraint = registry.load()?.owner == owner.key() @ PoleError::InvalidOwner,
+    )]
+    pub registry: AccountLoader<'info, PoolRegistry>,
+    #[account(mut, seeds = [REGISTRY_SEED, crate::ID.as_ref(), &registry_page.load()?.index.to_le_bytes()], bump = registry_page.load()?.bump as u8)]
+    pub registry_page: AccountLoader<'info, RegistryPage>,
+    pub owner: Signer<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts, Clone)]
+#[instruction()]
+pub struct DeprecatePool<'info> {
+    #[account(constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner)]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(mut, seeds = [REGISTRY_SEED, crate::ID.as_ref(), &registry_page.load()?.index.to_le_bytes()], bump = registry_page.load()?.bump as u8)]
+    pub registry_page: AccountLoader<'info, RegistryPage>,
+    pub owner: Signer<'info>,
+}
+
+//the obligation and stake account belong to Port, which can't close them; remaining accounts,
+//each group only when the pool has it: the route open orders, market, wallet and a wallet for its
+//dust; the sub reward open orders, market, supply and a wallet for its dust; the fee split
+//recipients; the withdraw queue lp escrow and settled liquidity
+#[derive(Accounts, Clone)]
+#[instruction()]
+pub struct ClosePool<'info> {
+    #[account(mut, close = owner,
+        constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner,
+        constraint = pole_pool.load()?.port_state.deposit_verified == 0 && pole_pool.load()?.port_state.redeem_verified == 0 && pole_pool.load()?.port_state.leverage == 0 @ PoleError::InvalidPoolState,
+        constraint = pole_pool.load()?.generic_config.lp_mint == lp_mint.key() @ PoleError::InvalidLPMint,
+        constraint = pole_pool.load()?.generic_config.liquidity_supply == liquidity_supply.key() @ PoleError::InvalidLiquidityWallet,
+        constraint = pole_pool.load()?.generic_config.fee_receiver == fee_receiver.key() @ PoleError::InvalidFeeAccount,
+        constraint = pole_pool.load()?.port_config.port_lp_supply == port_lp_supply.key() @ PoleError::InvalidPortWallet,
+        constraint = pole_pool.load()?.port_config.port_supply == port_supply.key() @ PoleError::InvalidPortWallet,
+        constraint = pole_pool.load()?.serum_config.port_open_orders == port_open_orders.key() @ PoleError::InvalidPoolConfig,
+        constraint = pole_pool.load()?.serum_config.dex_program == dex_program.key() @ PoleError::InvalidDexProgram,
+        constraint = pole_pool.load()?.generic_config.token_program == token_program.key() @ PoleError::InvalidTokenProgram,
+        constraint = pole_pool.load()?.port_config.obligation == obligation.key() @ PoleError::InvalidObligation,
+        constraint = obligation.key() == Pubkey::default()
+            || (obligation_deposits_count(&obligation)? == 0 && obligation_borrows_count(&obligation)? == 0) @ PoleError::ObligationNotUnwound,
+        constraint = supply(&lp_mint)? == 0 @ PoleError::PoolNotEmpty,
+        //pending requests keep their lp in lp_escrow, but settled ones burned it and wait in
+        //settled_liquidity until claimed, which the supply check alone misses
+        constraint = pole_pool.load()?.withdraw_queue.pending_lp == 0 && pole_pool.load()?.withdraw_queue.settled_liquidity_amount == 0 @ PoleError::WithdrawalsPending,
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub pole_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub lp_mint: AccountInfo<'info>,
+    #[account(mut)]
+    pub liquidity_supply: AccountInfo<'info>,
+    #[account(mut)]
+    pub fee_receiver: AccountInfo<'info>,
+    #[account(mut)]
+    pub port_lp_supply: AccountInfo<'info>,
+    #[account(mut)]
+    pub port_supply: AccountInfo<'info>,
+    //takes the PORT left below the market's lot size
+    #[account(mut, owner=token_program.key())]
+    pub owner_port_wallet: AccountInfo<'info>,
+    //ignored once close_open_orders retired the market
+    #[account(mut)]
+    pub port_open_orders: AccountInfo<'info>,
+    #[account(mut)]
+    pub dex_market: AccountInfo<'info>,
+    pub obligation: AccountInfo<'info>,
+    //empty unless init_sell_route gave the pool a route
+    #[account(mut, constraint = sell_route.key() == SellRoute::address(&pole_pool.key()) @ PoleError::InvalidSellRoute)]
+    pub sell_route: AccountInfo<'info>,
+    //empty unless init_sub_reward gave the pool a sub reward
+    #[account(mut, constraint = sub_reward.key() == SubReward::address(&pole_pool.key()) @ PoleError::InvalidSubReward)]
+    pub sub_reward: AccountInfo<'info>,
+    //empty unless init_fee_split made fee_receiver its vault
+    #[account(mut, constraint = fee_split.key() == FeeSplit::address(&pole_pool.key()) @ PoleError::InvalidFeeSplit)]
+    pub fee_split: AccountInfo<'info>,
+    //the page listing the pool, if it is listed
+    #[account(mut, seeds = [REGISTRY_SEED, crate::ID.as_ref(), &registry_page.load()?.index.to_le_bytes()], bump = registry_page.load()?.bump as u8)]
+    pub registry_page: AccountLoader<'info, RegistryPage>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+    #[account(executable)]
+    pub dex_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts, Clone)]
+#[instruction(pool: Pubkey)]
+pub struct UnregisterPool<'info> {
+    #[account(seeds = [REGISTRY_SEED, crate::ID.as_ref()], bump = registry.load()?.bump as u8,
+        constraint = registry.load()?.owner == owner.key() @ PoleError::InvalidOwner,
+    )]
+    pub registry: AccountLoader<'info, PoolRegistry>,
+    #[account(mut, seeds = [REGISTRY_SEED, crate::ID.as_ref(), &registry_page.load()?.index.to_le_bytes()], bump = registry_page.load()?.bump as u8)]
+    pub registry_page: AccountLoader<'info, RegistryPage>,
+    pub owner: Signer<'info>,
 }
+
 #[derive(Accounts, Clone)]
 #[instruction()]
 pub struct InitPortAccounts<'info> {
     #[account(mut,
-    constraint = pole_pool.load()?.generic_config.owner == user.key() @ PoleError::InvalidOwner)]
+    constraint = pole_pool.load()?.generic_config.owner == user.key() @ PoleError::InvalidOwner,
+    constraint = pole_pool.load()?.port_config.obligation == Pubkey::default() @ PoleError::InvalidObligation)]
     pub pole_pool: AccountLoader<'info, PolePortPool>,
 
     #[account(init, payer=user, owner=port_lending_program.key(), space=Obligation::LEN)]
@@ -118,7 +295,7 @@ pub struct InitPortAccounts<'info> {
     #[account(init, payer=user, owner=port_staking_program.key(), space=StakeAccount::LEN)]
     pub stake_account: AccountInfo<'info>,
 
-    #[account(seeds = [], bump = pole_pool.load() ?.generic_config.bump as u8)]
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
     pub pole_authority: AccountInfo<'info>,
 
     #[account(mut)]
@@ -162,12 +339,14 @@ pub struct VerifyRedeem<'info> {
     pub transaction_info: AccountInfo<'info>,
 }
 
-//assume reserve and obligation are refreshed
+//reserve and obligation are refreshed in the handler when stale,
+//remaining accounts: the user's UserPosition (if tracked), the reserve oracle (if any), then
+//ZapInAccounts when zap_in_min_liquidity_out is set
 #[derive(Accounts, Clone)]
-#[instruction(amount: u64)]
+#[instruction(amount: u64, min_lp_out: u64, zap_in_min_liquidity_out: Option<u64>)]
 pub struct DepositLiquidity<'info> {
     #[account(mut,
-        constraint = pole_pool.load()?.port_state.last_sold_slot == 0 || STALE_SLOT >= clock.slot.checked_sub(pole_pool.load()?.port_state.last_sold_slot).ok_or(PoleError::MathOverflow)?  @ PoleError::PortNotSell,
+        constraint = pole_pool.load()?.port_state.last_sold_slot == 0 || pole_pool.load()?.harvest_config.stale_slot() >= clock.slot.checked_sub(pole_pool.load()?.port_state.last_sold_slot).ok_or(PoleError::MathOverflow)?  @ PoleError::PortNotSell,
         constraint = pole_pool.load()?.generic_config.liquidity_supply
             == pole_liquidity_accounts.pole_liquidity_wallet.key() @ PoleError::InvalidLiquidityWallet,
         constraint = pole_pool.load()?.generic_config.lp_mint
@@ -184,11 +363,9 @@ pub struct DepositLiquidity<'info> {
         constraint = (pole_pool.load()?.port_state.leverage == 0) ^ (amount == 0) @ PoleError::DepositAmountInvalid,
         constraint = pole_pool.load()?.basic_state.lp_amount == supply(&pole_lp_accounts.lp_mint)? @ PoleError::LPMintAmountNotMatch,
         constraint = pole_pool.load()?.port_state.approved_wallet == pole_liquidity_accounts.user_liquidity_wallet.key() @ PoleError::WrongWallet,
-        constraint = !is_obligation_stale(&port_accounts.obligation)? ^ (amount == 0) @ PoleError::ObligationStale,
-        constraint = !is_reserve_stale(&port_accounts.reserve)? @ PoleError::ReserveStale
     )]
     pub pole_pool: AccountLoader<'info, PolePortPool>,
-    #[account(seeds = [], bump = pole_pool.load() ?.generic_config.bump as u8)]
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
     pub pole_authority: AccountInfo<'info>,
     pub pole_liquidity_accounts: PoleLiquidityAccounts<'info>,
     pub pole_lp_accounts: PoleLPAccounts<'info>,
@@ -201,12 +378,15 @@ pub struct DepositLiquidity<'info> {
     pub clock: Sysvar<'info, Clock>,
 }
 
-//assume reserve and obligation are refreshed
+//reserve and obligation are refreshed in the handler when stale,
+//remaining accounts: the user's UserPosition (if tracked), the reserve oracle (if any), the
+//position's referrer (if it has one), then ZapOutAccounts when zap_out_min_output is set;
+//untracked LP pays the first tier of the fee schedule
 #[derive(Accounts, Clone)]
-#[instruction(amount: u64)]
+#[instruction(amount: u64, min_liquidity_out: u64, zap_out_min_output: Option<u64>)]
 pub struct RedeemLiquidity<'info> {
     #[account(mut,
-        constraint = STALE_SLOT >= clock.slot.checked_sub(pole_pool.load()?.port_state.last_sold_slot).ok_or(PoleError::MathOverflow)? @ PoleError::PortNotSell,
+        constraint = pole_pool.load()?.harvest_config.stale_slot() >= clock.slot.checked_sub(pole_pool.load()?.port_state.last_sold_slot).ok_or(PoleError::MathOverflow)? @ PoleError::PortNotSell,
         constraint = (amount == 0) ^ (pole_pool.load()?.port_state.leverage == 0) @ PoleError::RedeemAmountInvalid,
         constraint = pole_pool.load()?.generic_config.liquidity_supply == pole_liquidity_accounts.pole_liquidity_wallet.key() @ PoleError::InvalidLiquidityWallet,
         constraint = pole_pool.load()?.port_config.obligation == port_accounts.obligation.key() @ PoleError::InvalidObligation,
@@ -219,7 +399,7 @@ pub struct RedeemLiquidity<'info> {
         constraint = pole_pool.load()?.port_state.approved_wallet == pole_lp_accounts.user_lp_wallet.key() @ PoleError::WrongWallet
     )]
     pub pole_pool: AccountLoader<'info, PolePortPool>,
-    #[account(seeds = [], bump = pole_pool.load() ?.generic_config.bump as u8)]
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
     pub pole_authority: AccountInfo<'info>,
     #[account(mut)]
     pub pole_fee_account: AccountInfo<'info>,
@@ -234,6 +414,39 @@ pub struct RedeemLiquidity<'info> {
     pub clock: Sysvar<'info, Clock>,
 }
 
+//owner only, and the leg can still only pay the wallet approved by the interrupted transaction;
+//pass the depositor's UserPosition (if tracked) as remaining account to credit a finished deposit
+#[derive(Accounts, Clone)]
+#[instruction()]
+pub struct RecoverPortState<'info> {
+    #[account(mut,
+        constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner,
+        constraint = pole_pool.load()?.generic_config.liquidity_supply == pole_liquidity_accounts.pole_liquidity_wallet.key() @ PoleError::InvalidLiquidityWallet,
+        constraint = pole_pool.load()?.generic_config.lp_mint == pole_lp_accounts.lp_mint.key() @ PoleError::InvalidLPMint,
+        constraint = pole_pool.load()?.port_config.obligation == port_accounts.obligation.key() @ PoleError::InvalidObligation,
+        constraint = pole_pool.load()?.port_config.stake_account == port_accounts.stake_account.key() @ PoleError::InvalidStakeAccount,
+        constraint = pole_pool.load()?.port_config.port_lending_program == port_accounts.port_lending_program.key() @ PoleError::InvalidPortLendingProgram,
+        constraint = pole_pool.load()?.generic_config.token_program == token_program.key() @ PoleError::InvalidTokenProgram,
+        constraint = pole_pool.load()?.generic_config.fee_receiver == pole_fee_account.key() @ PoleError::InvalidFeeAccount,
+        constraint = pole_pool.load()?.basic_state.lp_amount == supply(&pole_lp_accounts.lp_mint)? @ PoleError::LPMintAmountNotMatch,
+        constraint = pole_pool.load()?.generic_config.validate() && pole_pool.load()?.port_config.validate() @ PoleError::InvalidPoolConfig,
+        constraint = !is_reserve_stale(&port_accounts.reserve)? @ PoleError::ReserveStale
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub pole_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub pole_fee_account: AccountInfo<'info>,
+    pub pole_liquidity_accounts: PoleLiquidityAccounts<'info>,
+    pub pole_lp_accounts: PoleLPAccounts<'info>,
+    #[account(constraint = port_accounts.reserve.key() == pole_pool.load()?.port_config.reserve)]
+    pub port_accounts: PortLendingAccounts<'info>,
+    pub owner: Signer<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
 #[derive(Accounts, Clone)]
 #[instruction()]
 pub struct ClaimAndSell<'info> {
@@ -252,12 +465,17 @@ pub struct ClaimAndSell<'info> {
             == dex_program.key() @ PoleError::InvalidDexProgram,
         constraint = pole_pool.load()?.serum_config.swap_program
             == swap_program.key() @ PoleError::InvalidSwapProgram,
+        constraint = clock.slot.checked_sub(pole_pool.load()?.port_state.last_sold_slot).ok_or(PoleError::MathOverflow)?
+            >= pole_pool.load()?.harvest_config.min_harvest_interval @ PoleError::HarvestTooSoon,
     )]
     pub pole_pool: AccountLoader<'info, PolePortPool>,
-    #[account(seeds = [], bump = pole_pool.load() ?.generic_config.bump as u8)]
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
     pub pole_authority: AccountInfo<'info>,
     #[account(mut, owner=token_program.key())]
     pub liquidity_supply: AccountInfo<'info>,
+    //receives the harvest bounty, any liquidity token account of the caller
+    #[account(mut, owner=token_program.key())]
+    pub harvester_liquidity_wallet: AccountInfo<'info>,
     #[account(mut, owner=token_program.key())]
     pub port_supply: AccountInfo<'info>,
     pub market_accounts: MarketAccounts<'info>,
@@ -273,63 +491,823 @@ pub struct ClaimAndSell<'info> {
     pub rent: AccountInfo<'info>,
 }
 #[derive(Accounts, Clone)]
-#[instruction(new_owner: Pubkey)]
-pub struct ChangeOwner<'info> {
-    #[account(mut, constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner)]
+#[instruction(bump: u8)]
+pub struct InitSubReward<'info> {
+    #[account(
+        constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner,
+        constraint = pole_pool.load()?.serum_config.dex_program == dex_program.key() @ PoleError::InvalidDexProgram,
+        constraint = pole_pool.load()?.generic_config.token_program == token_program.key() @ PoleError::InvalidTokenProgram,
+    )]
     pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub pole_authority: AccountInfo<'info>,
+    #[account(init, seeds = [SUB_REWARD_SEED, pole_pool.key().as_ref()], bump = bump, payer = owner, space = size_of::<SubReward>() + DISCRIMINATOR_SIZE)]
+    pub sub_reward: Account<'info, SubReward>,
+    #[account(init, payer=owner, token::authority=pole_authority, token::mint=sub_reward_mint)]
+    pub sub_reward_supply: AccountInfo<'info>,
+    #[account(owner=token_program.key())]
+    pub sub_reward_mint: AccountInfo<'info>,
+    #[account(init, payer=owner, owner=dex::ID, space=size_of::<OpenOrders>() + SERUM_PADDING,)]
+    pub sub_reward_open_orders: AccountInfo<'info>,
+    #[account(mut, owner=dex_program.key())]
+    pub dex_market: AccountInfo<'info>,
+    #[account(mut)]
     pub owner: Signer<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+    #[account(executable)]
+    pub dex_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+    pub rent: AccountInfo<'info>,
 }
 
+//permissionless, sells what claim_and_sell claimed of the sub reward
 #[derive(Accounts, Clone)]
-#[instruction(new_fee_receiver: Pubkey)]
-pub struct ChangeFeeReceiver<'info> {
-    #[account(mut, constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner)]
+#[instruction()]
+pub struct SellSubReward<'info> {
+    #[account(mut,
+        constraint = pole_pool.load()?.generic_config.liquidity_supply
+            == liquidity_supply.key() @ PoleError::InvalidLiquidityWallet,
+        constraint = pole_pool.load()?.generic_config.token_program
+            == token_program.key() @ PoleError::InvalidTokenProgram,
+        constraint = pole_pool.load()?.serum_config.dex_program
+            == dex_program.key() @ PoleError::InvalidDexProgram,
+        constraint = pole_pool.load()?.serum_config.swap_program
+            == swap_program.key() @ PoleError::InvalidSwapProgram,
+    )]
     pub pole_pool: AccountLoader<'info, PolePortPool>,
-    pub owner: Signer<'info>,
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub pole_authority: AccountInfo<'info>,
+    #[account(mut,
+        constraint = sub_reward.pool == pole_pool.key() @ PoleError::InvalidSubReward,
+        constraint = sub_reward.supply == sub_reward_supply.key() @ PoleError::InvalidSubReward,
+        constraint = sub_reward.mint == sub_reward_mint.key() @ PoleError::InvalidSubReward,
+        constraint = sub_reward.open_orders == market_accounts.open_orders.key() @ PoleError::InvalidSubReward,
+        constraint = clock.slot.checked_sub(sub_reward.last_sold_slot).ok_or(PoleError::MathOverflow)?
+            >= pole_pool.load()?.harvest_config.min_harvest_interval @ PoleError::HarvestTooSoon,
+    )]
+    pub sub_reward: Account<'info, SubReward>,
+    #[account(mut, owner=token_program.key())]
+    pub liquidity_supply: AccountInfo<'info>,
+    //receives the harvest bounty, any liquidity token account of the caller
+    #[account(mut, owner=token_program.key())]
+    pub harvester_liquidity_wallet: AccountInfo<'info>,
+    #[account(mut, owner=token_program.key())]
+    pub sub_reward_supply: AccountInfo<'info>,
+    pub sub_reward_mint: AccountInfo<'info>,
+    pub market_accounts: MarketAccounts<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+    #[account(executable)]
+    pub dex_program: AccountInfo<'info>,
+    #[account(executable)]
+    pub swap_program: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub rent: AccountInfo<'info>,
 }
 
+//route_market trades the liquidity mint (coin) against the harvest market's pc, route_wallet holds
+//that pc between the two hops
 #[derive(Accounts, Clone)]
-#[instruction(cap: u64)]
-pub struct ChangeLiquidityCap<'info> {
-    #[account(mut, constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner, constraint = cap >= MIN_LIQUIDITY_CAP @ PoleError::InvalidPoolConfig,)]
+#[instruction(bump: u8)]
+pub struct InitSellRoute<'info> {
+    #[account(
+        constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner,
+        constraint = pole_pool.load()?.serum_config.dex_program == dex_program.key() @ PoleError::InvalidDexProgram,
+        constraint = pole_pool.load()?.generic_config.token_program == token_program.key() @ PoleError::InvalidTokenProgram,
+    )]
     pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub pole_authority: AccountInfo<'info>,
+    #[account(init, seeds = [SELL_ROUTE_SEED, pole_pool.key().as_ref()], bump = bump, payer = owner, space = size_of::<SellRoute>() + DISCRIMINATOR_SIZE)]
+    pub sell_route: Account<'info, SellRoute>,
+    #[account(init, payer=owner, owner=dex::ID, space=size_of::<OpenOrders>() + SERUM_PADDING,)]
+    pub route_open_orders: AccountInfo<'info>,
+    #[account(mut, owner=dex_program.key())]
+    pub route_market: AccountInfo<'info>,
+    #[account(init, payer=owner, token::authority=pole_authority, token::mint=route_mint)]
+    pub route_wallet: AccountInfo<'info>,
+    #[account(owner=token_program.key())]
+    pub route_mint: AccountInfo<'info>,
+    #[account(mut)]
     pub owner: Signer<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+    #[account(executable)]
+    pub dex_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+    pub rent: AccountInfo<'info>,
 }
 
+//permissionless, settles the harvest market's, the sell route's or the sub reward's open orders;
+//the SellRoute or SubReward is the only remaining account for the latter two, and for the harvest
+//market of a routed pool, whose pc goes to the route wallet
 #[derive(Accounts, Clone)]
-#[instruction(bips: u8)]
-pub struct ChangeWithdrawFee<'info> {
-    #[account(mut, constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner)]
+#[instruction()]
+pub struct SettleOpenOrders<'info> {
+    #[account(
+        constraint = pole_pool.load()?.generic_config.token_program
+            == token_program.key() @ PoleError::InvalidTokenProgram,
+        constraint = pole_pool.load()?.serum_config.dex_program
+            == dex_program.key() @ PoleError::InvalidDexProgram,
+    )]
     pub pole_pool: AccountLoader<'info, PolePortPool>,
-    pub owner: Signer<'info>,
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub pole_authority: AccountInfo<'info>,
+    #[account(mut, owner=token_program.key())]
+    pub coin_wallet: AccountInfo<'info>,
+    #[account(mut, owner=token_program.key())]
+    pub pc_wallet: AccountInfo<'info>,
+    pub market_accounts: MarketAccounts<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+    #[account(executable)]
+    pub dex_program: AccountInfo<'info>,
 }
 
+//retires the harvest market, the open orders have to be settled first; the rent goes to the owner
 #[derive(Accounts, Clone)]
-#[instruction(min_deposit: u64)]
-pub struct ChangeMinDeposit<'info> {
-    #[account(mut, constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner)]
+#[instruction()]
+pub struct ClosePoolOpenOrders<'info> {
+    #[account(mut,
+        constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner,
+        constraint = pole_pool.load()?.serum_config.port_open_orders == port_open_orders.key() @ PoleError::InvalidPoolConfig,
+        constraint = pole_pool.load()?.serum_config.dex_program == dex_program.key() @ PoleError::InvalidDexProgram,
+    )]
     pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub pole_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub port_open_orders: AccountInfo<'info>,
+    #[account(mut, owner=dex_program.key())]
+    pub dex_market: AccountInfo<'info>,
+    #[account(mut)]
     pub owner: Signer<'info>,
+    #[account(executable)]
+    pub dex_program: AccountInfo<'info>,
 }
+
+//moves harvesting to dex_market, closing the current open orders on legacy_market unless already closed
 #[derive(Accounts, Clone)]
-pub struct MarketAccounts<'info> {
-    #[account(mut)]
-    pub market: AccountInfo<'info>,
-    #[account(mut)]
-    pub open_orders: AccountInfo<'info>,
-    #[account(mut)]
-    pub request_queue: AccountInfo<'info>,
-    #[account(mut)]
-    pub event_queue: AccountInfo<'info>,
+#[instruction()]
+pub struct MigrateMarket<'info> {
+    #[account(mut,
+        constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner,
+        constraint = pole_pool.load()?.serum_config.port_open_orders == Pubkey::default()
+            || pole_pool.load()?.serum_config.port_open_orders == legacy_open_orders.key() @ PoleError::InvalidPoolConfig,
+        constraint = pole_pool.load()?.serum_config.dex_program == dex_program.key() @ PoleError::InvalidDexProgram,
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub pole_authority: AccountInfo<'info>,
     #[account(mut)]
-    pub bids: AccountInfo<'info>,
+    pub legacy_open_orders: AccountInfo<'info>,
     #[account(mut)]
-    pub asks: AccountInfo<'info>,
+    pub legacy_market: AccountInfo<'info>,
+    #[account(init, payer=owner, owner=dex::ID, space=size_of::<OpenOrders>() + SERUM_PADDING,)]
+    pub port_open_orders: AccountInfo<'info>,
+    #[account(mut, owner=dex_program.key())]
+    pub dex_market: AccountInfo<'info>,
     #[account(mut)]
-    pub coin_vault: AccountInfo<'info>,
+    pub owner: Signer<'info>,
+    #[account(executable)]
+    pub dex_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+    pub rent: AccountInfo<'info>,
+}
+
+//reserve and obligation are refreshed in the handler when stale, remaining accounts: the reserve
+//oracle (if any); exactly port_iterate legs go in one transaction
+#[derive(Accounts, Clone)]
+#[instruction(leverage: u8)]
+pub struct AutoCompound<'info> {
+    #[account(mut,
+        constraint = pole_pool.load()?.generic_config.liquidity_supply == liquidity_supply.key() @ PoleError::InvalidLiquidityWallet,
+        constraint = pole_pool.load()?.port_config.obligation == port_accounts.obligation.key() @ PoleError::InvalidObligation,
+        constraint = pole_pool.load()?.port_config.stake_account == port_accounts.stake_account.key() @ PoleError::InvalidStakeAccount,
+        constraint = pole_pool.load()?.port_config.port_lending_program == port_accounts.port_lending_program.key() @ PoleError::InvalidPortLendingProgram,
+        constraint = pole_pool.load()?.generic_config.token_program == token_program.key() @ PoleError::InvalidTokenProgram,
+        constraint = pole_pool.load()?.port_state.deposit_verified == 0 && pole_pool.load()?.port_state.redeem_verified == 0 @ PoleError::InvalidPoolState,
+        constraint = pole_pool.load()?.port_state.leverage == leverage as u64 @ PoleError::InvalidTransaction,
+        constraint = pole_pool.load()?.withdraw_queue.pending_lp == 0 @ PoleError::WithdrawalsPending
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub pole_authority: AccountInfo<'info>,
+    #[account(mut, owner=token_program.key())]
+    pub liquidity_supply: AccountInfo<'info>,
+    #[account(constraint = port_accounts.reserve.key() == pole_pool.load()?.port_config.reserve)]
+    pub port_accounts: PortLendingAccounts<'info>,
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::id())]
+    pub transaction_info: AccountInfo<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+//assume reserve and obligation are refreshed
+#[derive(Accounts, Clone)]
+#[instruction(repay_amount: u64, withdraw_amount: u64)]
+pub struct UnwindObligation<'info> {
+    #[account(mut,
+        constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner,
+        constraint = pole_pool.load()?.generic_config.liquidity_supply == liquidity_supply.key() @ PoleError::InvalidLiquidityWallet,
+        constraint = pole_pool.load()?.port_config.obligation == port_accounts.obligation.key() @ PoleError::InvalidObligation,
+        constraint = pole_pool.load()?.port_config.stake_account == port_accounts.stake_account.key() @ PoleError::InvalidStakeAccount,
+        constraint = pole_pool.load()?.port_config.port_lending_program == port_accounts.port_lending_program.key() @ PoleError::InvalidPortLendingProgram,
+        constraint = pole_pool.load()?.generic_config.token_program == token_program.key() @ PoleError::InvalidTokenProgram,
+        constraint = pole_pool.load()?.port_state.deposit_verified == 0 && pole_pool.load()?.port_state.redeem_verified == 0 && pole_pool.load()?.port_state.leverage == 0 @ PoleError::InvalidPoolState,
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub pole_authority: AccountInfo<'info>,
+    #[account(mut, owner=token_program.key())]
+    pub liquidity_supply: AccountInfo<'info>,
+    #[account(constraint = port_accounts.reserve.key() == pole_pool.load()?.port_config.reserve)]
+    pub port_accounts: PortLendingAccounts<'info>,
+    pub owner: Signer<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts, Clone)]
+#[instruction()]
+pub struct InitWithdrawQueue<'info> {
+    #[account(mut,
+        constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner,
+        constraint = !pole_pool.load()?.withdraw_queue.is_initialized() @ PoleError::InvalidPoolState,
+        constraint = pole_pool.load()?.generic_config.lp_mint == lp_mint.key() @ PoleError::InvalidLPMint,
+        constraint = pole_pool.load()?.generic_config.liquidity_supply == liquidity_supply.key() @ PoleError::InvalidLiquidityWallet,
+        constraint = pole_pool.load()?.generic_config.token_program == token_program.key() @ PoleError::InvalidTokenProgram,
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub pole_authority: AccountInfo<'info>,
+    pub lp_mint: AccountInfo<'info>,
+    #[account(constraint = mint(&liquidity_supply)? == liquidity_mint.key() @ PoleError::InvalidLiquidityWallet)]
+    pub liquidity_mint: AccountInfo<'info>,
+    pub liquidity_supply: AccountInfo<'info>,
+    #[account(init, payer=owner, token::authority=pole_authority, token::mint=lp_mint)]
+    pub lp_escrow: AccountInfo<'info>,
+    #[account(init, payer=owner, token::authority=pole_authority, token::mint=liquidity_mint)]
+    pub settled_liquidity: AccountInfo<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+    pub rent: AccountInfo<'info>,
+}
+
+//one pending or unclaimed request per user and pool,
+//pass the user's UserPosition (if tracked) as remaining account
+#[derive(Accounts, Clone)]
+#[instruction(bump: u8, amount: u64)]
+pub struct RequestWithdraw<'info> {
+    #[account(mut,
+        constraint = pole_pool.load()?.withdraw_queue.is_initialized() @ PoleError::WithdrawQueueNotInitialized,
+        constraint = pole_pool.load()?.withdraw_queue.lp_escrow == lp_escrow.key() @ PoleError::InvalidLPMint,
+        constraint = pole_pool.load()?.generic_config.token_program == token_program.key() @ PoleError::InvalidTokenProgram,
+        constraint = amount > 0 @ PoleError::RedeemAmountInvalid,
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(init, seeds = [WITHDRAW_REQUEST_SEED, pole_pool.key().as_ref(), user.key().as_ref()], bump = bump, payer = user, space = size_of::<WithdrawRequest>() + DISCRIMINATOR_SIZE)]
+    pub withdraw_request: Account<'info, WithdrawRequest>,
+    #[account(mut)]
+    pub user_lp_wallet: AccountInfo<'info>,
+    #[account(mut)]
+    pub lp_escrow: AccountInfo<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+//permissionless: only frees what pending requests are short of, deposit_liquidity and
+//auto_compound refuse to re-deposit it while requests are pending
+//assume reserve and obligation are refreshed
+#[derive(Accounts, Clone)]
+#[instruction(repay_amount: u64, withdraw_amount: u64)]
+pub struct UnwindForWithdrawals<'info> {
+    #[account(mut,
+        constraint = pole_pool.load()?.withdraw_queue.pending_lp > 0 @ PoleError::InvalidWithdrawRequest,
+        constraint = pole_pool.load()?.generic_config.liquidity_supply == liquidity_supply.key() @ PoleError::InvalidLiquidityWallet,
+        constraint = pole_pool.load()?.port_config.obligation == port_accounts.obligation.key() @ PoleError::InvalidObligation,
+        constraint = pole_pool.load()?.port_config.stake_account == port_accounts.stake_account.key() @ PoleError::InvalidStakeAccount,
+        constraint = pole_pool.load()?.port_config.port_lending_program == port_accounts.port_lending_program.key() @ PoleError::InvalidPortLendingProgram,
+        constraint = pole_pool.load()?.generic_config.token_program == token_program.key() @ PoleError::InvalidTokenProgram,
+        constraint = pole_pool.load()?.port_state.deposit_verified == 0 && pole_pool.load()?.port_state.redeem_verified == 0 && pole_pool.load()?.port_state.leverage == 0 @ PoleError::InvalidPoolState,
+        constraint = !is_obligation_stale(&port_accounts.obligation)? @ PoleError::ObligationStale,
+        constraint = !is_reserve_stale(&port_accounts.reserve)? @ PoleError::ReserveStale
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub pole_authority: AccountInfo<'info>,
+    #[account(mut, owner=token_program.key())]
+    pub liquidity_supply: AccountInfo<'info>,
+    #[account(constraint = port_accounts.reserve.key() == pole_pool.load()?.port_config.reserve)]
+    pub port_accounts: PortLendingAccounts<'info>,
+    pub keeper: Signer<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+//permissionless: settles one request from available liquidity, batch several per transaction;
+//pass the request's referrer (if it has one) as remaining account
+//assume reserve and obligation are refreshed
+#[derive(Accounts, Clone)]
+#[instruction()]
+pub struct ProcessWithdrawRequest<'info> {
+    #[account(mut,
+        constraint = pole_pool.load()?.generic_config.lp_mint == lp_mint.key() @ PoleError::InvalidLPMint,
+        constraint = pole_pool.load()?.generic_config.liquidity_supply == liquidity_supply.key() @ PoleError::InvalidLiquidityWallet,
+        constraint = pole_pool.load()?.generic_config.fee_receiver == pole_fee_account.key() @ PoleError::InvalidFeeAccount,
+        constraint = pole_pool.load()?.generic_config.token_program == token_program.key() @ PoleError::InvalidTokenProgram,
+        constraint = pole_pool.load()?.withdraw_queue.lp_escrow == lp_escrow.key() @ PoleError::InvalidLPMint,
+        constraint = pole_pool.load()?.withdraw_queue.settled_liquidity == settled_liquidity.key() @ PoleError::InvalidLiquidityWallet,
+        constraint = pole_pool.load()?.port_config.obligation == obligation.key() @ PoleError::InvalidObligation,
+        constraint = pole_pool.load()?.port_config.reserve == reserve.key() @ PoleError::InvalidPoolConfig,
+        constraint = pole_pool.load()?.port_state.deposit_verified == 0 && pole_pool.load()?.port_state.redeem_verified == 0 && pole_pool.load()?.port_state.leverage == 0 @ PoleError::InvalidPoolState,
+        constraint = pole_pool.load()?.basic_state.lp_amount == supply(&lp_mint)? @ PoleError::LPMintAmountNotMatch,
+        constraint = !is_obligation_stale(&obligation)? @ PoleError::ObligationStale,
+        constraint = !is_reserve_stale(&reserve)? @ PoleError::ReserveStale
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub pole_authority: AccountInfo<'info>,
+    #[account(mut,
+        constraint = withdraw_request.pool == pole_pool.key() @ PoleError::InvalidWithdrawRequest,
+        constraint = withdraw_request.status == WITHDRAW_PENDING @ PoleError::InvalidWithdrawRequest,
+    )]
+    pub withdraw_request: Account<'info, WithdrawRequest>,
+    #[account(mut)]
+    pub lp_mint: AccountInfo<'info>,
+    #[account(mut)]
+    pub lp_escrow: AccountInfo<'info>,
+    #[account(mut)]
+    pub liquidity_supply: AccountInfo<'info>,
+    #[account(mut)]
+    pub settled_liquidity: AccountInfo<'info>,
+    #[account(mut)]
+    pub pole_fee_account: AccountInfo<'info>,
+    pub reserve: AccountInfo<'info>,
+    pub obligation: AccountInfo<'info>,
+    pub keeper: Signer<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts, Clone)]
+#[instruction()]
+pub struct ClaimWithdraw<'info> {
+    #[account(mut,
+        constraint = pole_pool.load()?.withdraw_queue.settled_liquidity == settled_liquidity.key() @ PoleError::InvalidLiquidityWallet,
+        constraint = pole_pool.load()?.generic_config.token_program == token_program.key() @ PoleError::InvalidTokenProgram,
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub pole_authority: AccountInfo<'info>,
+    #[account(mut, close = user, has_one = user,
+        constraint = withdraw_request.pool == pole_pool.key() @ PoleError::InvalidWithdrawRequest,
+        constraint = withdraw_request.status == WITHDRAW_SETTLED @ PoleError::InvalidWithdrawRequest,
+    )]
+    pub withdraw_request: Account<'info, WithdrawRequest>,
+    #[account(mut)]
+    pub settled_liquidity: AccountInfo<'info>,
+    #[account(mut)]
+    pub user_liquidity_wallet: AccountInfo<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+}
+
+//pass the user's UserPosition as remaining account when the request released principal
+#[derive(Accounts, Clone)]
+#[instruction()]
+pub struct CancelWithdrawRequest<'info> {
+    #[account(mut,
+        constraint = pole_pool.load()?.withdraw_queue.lp_escrow == lp_escrow.key() @ PoleError::InvalidLPMint,
+        constraint = pole_pool.load()?.generic_config.token_program == token_program.key() @ PoleError::InvalidTokenProgram,
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub pole_authority: AccountInfo<'info>,
+    #[account(mut, close = user, has_one = user,
+        constraint = withdraw_request.pool == pole_pool.key() @ PoleError::InvalidWithdrawRequest,
+        constraint = withdraw_request.status == WITHDRAW_PENDING @ PoleError::InvalidWithdrawRequest,
+    )]
+    pub withdraw_request: Account<'info, WithdrawRequest>,
+    #[account(mut)]
+    pub lp_escrow: AccountInfo<'info>,
+    #[account(mut)]
+    pub user_lp_wallet: AccountInfo<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+}
+
+//moves a pool created under the global seeds = [] authority to its own authority,
+//the obligation has to be unwound first and is re-created with init_port_accounts afterwards;
+//the stake account can't change owner either, its rewards are claimed before it is dropped.
+//Pass the pool's SubReward accounts as remaining accounts to claim the sub reward too
+#[derive(Accounts, Clone)]
+#[instruction(authority_bump: u8)]
+pub struct MigrateAuthority<'info> {
+    #[account(mut,
+        constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner,
+        constraint = pole_pool.load()?.authority_version == LEGACY_AUTHORITY @ PoleError::InvalidPoolState,
+        constraint = pole_pool.load()?.port_state.deposit_verified == 0 && pole_pool.load()?.port_state.redeem_verified == 0 && pole_pool.load()?.port_state.leverage == 0 @ PoleError::InvalidPoolState,
+        constraint = pole_pool.load()?.generic_config.lp_mint == lp_mint.key() @ PoleError::InvalidLPMint,
+        constraint = pole_pool.load()?.generic_config.liquidity_supply == liquidity_supply.key() @ PoleError::InvalidLiquidityWallet,
+        constraint = pole_pool.load()?.port_config.port_lp_supply == port_lp_supply.key() @ PoleError::InvalidPortWallet,
+        constraint = pole_pool.load()?.port_config.port_supply == port_supply.key() @ PoleError::InvalidPortWallet,
+        constraint = pole_pool.load()?.serum_config.port_open_orders == legacy_open_orders.key() @ PoleError::InvalidPoolConfig,
+        constraint = pole_pool.load()?.serum_config.dex_program == dex_program.key() @ PoleError::InvalidDexProgram,
+        constraint = pole_pool.load()?.generic_config.token_program == token_program.key() @ PoleError::InvalidTokenProgram,
+        constraint = pole_pool.load()?.port_config.obligation == obligation.key() @ PoleError::InvalidObligation,
+        constraint = obligation.key() == Pubkey::default()
+            || (obligation_deposits_count(&obligation)? == 0 && obligation_borrows_count(&obligation)? == 0) @ PoleError::ObligationNotUnwound,
+        constraint = pole_pool.load()?.port_config.stake_account == port_accounts.stake_account.key() @ PoleError::InvalidStakeAccount,
+        constraint = pole_pool.load()?.port_config.port_staking_program == port_accounts.port_staking_program.key() @ PoleError::InvalidStakingProgram,
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(seeds = [], bump = pole_pool.load() ?.generic_config.bump as u8)]
+    pub legacy_authority: AccountInfo<'info>,
+    #[account(seeds = [pole_pool.key().as_ref()], bump = authority_bump)]
+    pub pole_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub lp_mint: AccountInfo<'info>,
+    #[account(mut)]
+    pub liquidity_supply: AccountInfo<'info>,
+    #[account(mut)]
+    pub port_lp_supply: AccountInfo<'info>,
+    #[account(mut)]
+    pub port_supply: AccountInfo<'info>,
+    #[account(mut)]
+    pub legacy_open_orders: AccountInfo<'info>,
+    #[account(init, payer=owner, owner=dex::ID, space=size_of::<OpenOrders>() + SERUM_PADDING,)]
+    pub port_open_orders: AccountInfo<'info>,
+    #[account(mut, owner=dex_program.key())]
+    pub dex_market: AccountInfo<'info>,
+    pub obligation: AccountInfo<'info>,
+    pub port_accounts: PortStakingAccounts<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+    #[account(executable)]
+    pub dex_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+    pub rent: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+}
+
+//the data length is checked before anything loads the zero-copy pool
+#[derive(Accounts, Clone)]
+#[instruction()]
+pub struct MigratePool<'info> {
+    #[account(mut,
+        constraint = pole_pool.to_account_info().data_len() >= size_of::<PolePortPool>() + DISCRIMINATOR_SIZE @ PoleError::PoolNeedsRealloc,
+        constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner,
+        constraint = pole_pool.load()?.layout_version < CURRENT_LAYOUT_VERSION @ PoleError::InvalidLayoutVersion,
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    pub owner: Signer<'info>,
+}
+
+//the pool is read raw, it is smaller than PolePortPool
+#[derive(Accounts, Clone)]
+#[instruction(bump: u8)]
+pub struct BeginPoolRealloc<'info> {
+    #[account(mut, owner = crate::ID,
+        constraint = pole_pool.data_len() < size_of::<PolePortPool>() + DISCRIMINATOR_SIZE @ PoleError::InvalidPoolState,
+        constraint = GenericPoolConfig::of_pool_data(&pole_pool.try_borrow_data()?)?.owner == owner.key() @ PoleError::InvalidOwner,
+    )]
+    pub pole_pool: AccountInfo<'info>,
+    #[account(init, seeds = [REALLOC_SEED, pole_pool.key().as_ref()], bump = bump, payer = owner, space = PoolRealloc::space(pole_pool.data_len()))]
+    pub pool_realloc: Account<'info, PoolRealloc>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts, Clone)]
+#[instruction(name: String, pda_bump: u8)]
+pub struct FinishPoolRealloc<'info> {
+    #[account(init, seeds = [name.as_ref()], bump = pda_bump, payer = owner, space = size_of::<PolePortPool>() + DISCRIMINATOR_SIZE)]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(mut, close = owner, has_one = owner,
+        constraint = pool_realloc.pool == pole_pool.key() @ PoleError::InvalidPoolState,
+    )]
+    pub pool_realloc: Account<'info, PoolRealloc>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts, Clone)]
+#[instruction(new_owner: Pubkey)]
+pub struct ChangeOwner<'info> {
+    #[account(mut, constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner)]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    pub owner: Signer<'info>,
+}
+
+//the legacy authority is shared by every legacy pool, so only per-pool authorities are swept;
+//the sell route and sub reward PDAs are read, when they exist, to refuse their wallets too
+#[derive(Accounts, Clone)]
+pub struct Sweep<'info> {
+    #[account(
+        constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner,
+        constraint = pole_pool.load()?.authority_version == POOL_AUTHORITY @ PoleError::InvalidPoolState,
+        constraint = pole_pool.load()?.generic_config.token_program == token_program.key() @ PoleError::InvalidTokenProgram,
+        constraint = !pole_pool.load()?.is_config_account(&source.key()) @ PoleError::InvalidSweepAccount,
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub pole_authority: AccountInfo<'info>,
+    #[account(mut, owner=token_program.key(), constraint = authority(&source)? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub source: AccountInfo<'info>,
+    #[account(mut, owner=token_program.key())]
+    pub target: AccountInfo<'info>,
+    #[account(constraint = sell_route.key() == SellRoute::address(&pole_pool.key()) @ PoleError::InvalidSellRoute)]
+    pub sell_route: AccountInfo<'info>,
+    #[account(constraint = sub_reward.key() == SubReward::address(&pole_pool.key()) @ PoleError::InvalidSubReward)]
+    pub sub_reward: AccountInfo<'info>,
+    pub owner: Signer<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+}
+
+impl<'info> Sweep<'info> {
+    pub fn create_transfer_cpi<'a, 'b, 'c>(
+        &self,
+        seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.source.clone(),
+            to: self.target.clone(),
+            authority: self.pole_authority.clone(),
+        };
+        CpiContext::new_with_signer(self.token_program.clone(), cpi_accounts, seeds)
+    }
+}
+
+#[derive(Accounts, Clone)]
+#[instruction(new_fee_receiver: Pubkey)]
+pub struct ChangeFeeReceiver<'info> {
+    #[account(mut, constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner)]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    //once split, fees go to the vault and change_fee_split picks who receives them
+    #[account(
+        constraint = fee_split.key() == FeeSplit::address(&pole_pool.key()) @ PoleError::InvalidFeeSplit,
+        constraint = fee_split.data_is_empty() @ PoleError::FeeSplitActive,
+    )]
+    pub fee_split: AccountInfo<'info>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts, Clone)]
+#[instruction(cap: u64)]
+pub struct ChangeLiquidityCap<'info> {
+    #[account(mut, constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner, constraint = cap >= MIN_LIQUIDITY_CAP @ PoleError::InvalidPoolConfig,)]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts, Clone)]
+#[instruction(bips: u8)]
+pub struct ChangeWithdrawFee<'info> {
+    #[account(mut, constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner)]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts, Clone)]
+#[instruction(min_deposit: u64)]
+pub struct ChangeMinDeposit<'info> {
+    #[account(mut, constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner)]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    pub owner: Signer<'info>,
+}
+#[derive(Accounts, Clone)]
+#[instruction(stale_slot: u64, min_harvest_interval: u64, harvest_bounty_bips: u64)]
+pub struct ChangeHarvestConfig<'info> {
+    #[account(mut, constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner,
+        constraint = stale_slot > 0 && min_harvest_interval < stale_slot @ PoleError::InvalidPoolConfig,
+        constraint = harvest_bounty_bips <= MAX_HARVEST_BOUNTY_BIPS @ PoleError::InvalidPoolConfig,
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    pub owner: Signer<'info>,
+}
+//the current fee_receiver keeps the whole fee until change_fee_split
+#[derive(Accounts, Clone)]
+#[instruction(bump: u8)]
+pub struct InitFeeSplit<'info> {
+    #[account(mut,
+        constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner,
+        constraint = pole_pool.load()?.generic_config.liquidity_supply == liquidity_supply.key() @ PoleError::InvalidLiquidityWallet,
+        constraint = pole_pool.load()?.generic_config.token_program == token_program.key() @ PoleError::InvalidTokenProgram,
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub pole_authority: AccountInfo<'info>,
+    #[account(init, seeds = [FEE_SPLIT_SEED, pole_pool.key().as_ref()], bump = bump, payer = owner, space = size_of::<FeeSplit>() + DISCRIMINATOR_SIZE)]
+    pub fee_split: Account<'info, FeeSplit>,
+    #[account(init, payer=owner, token::authority=pole_authority, token::mint=liquidity_mint)]
+    pub fee_vault: AccountInfo<'info>,
+    #[account(constraint = mint(&liquidity_supply)? == liquidity_mint.key() @ PoleError::InvalidLiquidityWallet)]
+    pub liquidity_mint: AccountInfo<'info>,
+    pub liquidity_supply: AccountInfo<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+    pub rent: AccountInfo<'info>,
+}
+
+#[derive(Accounts, Clone)]
+#[instruction(recipients: Vec<FeeRecipient>)]
+pub struct ChangeFeeSplit<'info> {
+    #[account(constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner,
+        constraint = !recipients.is_empty() && recipients.len() <= MAX_FEE_RECIPIENTS @ PoleError::InvalidFeeSplit,
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(mut, constraint = fee_split.pool == pole_pool.key() @ PoleError::InvalidFeeSplit)]
+    pub fee_split: Account<'info, FeeSplit>,
+    pub owner: Signer<'info>,
+}
+
+//permissionless: pass the fee_split recipients, in order, as remaining accounts
+#[derive(Accounts, Clone)]
+#[instruction()]
+pub struct DistributeFees<'info> {
+    #[account(constraint = pole_pool.load()?.generic_config.token_program == token_program.key() @ PoleError::InvalidTokenProgram)]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(constraint = pole_pool.load()?.authority(&pole_pool.key())? == pole_authority.key() @ PoleError::InvalidAuthority)]
+    pub pole_authority: AccountInfo<'info>,
+    #[account(constraint = fee_split.pool == pole_pool.key() @ PoleError::InvalidFeeSplit)]
+    pub fee_split: Account<'info, FeeSplit>,
+    #[account(mut, constraint = fee_split.fee_vault == fee_vault.key() @ PoleError::InvalidFeeAccount)]
+    pub fee_vault: AccountInfo<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts, Clone)]
+#[instruction(bips: u64)]
+pub struct ChangeReferralFee<'info> {
+    #[account(mut, constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner,
+        constraint = bips <= MAX_REFERRAL_FEE_BIPS @ PoleError::InvalidPoolConfig,
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts, Clone)]
+#[instruction(cap: u64)]
+pub struct ChangeWalletCap<'info> {
+    #[account(mut, constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner)]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts, Clone)]
+#[instruction(enabled: bool)]
+pub struct ChangeAllowlistMode<'info> {
+    #[account(mut, constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner)]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts, Clone)]
+#[instruction(bump: u8, wallet: Pubkey)]
+pub struct AddAllowlistEntry<'info> {
+    #[account(constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner)]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(init, seeds = [ALLOWLIST_SEED, pole_pool.key().as_ref(), wallet.as_ref()], bump = bump, payer = owner, space = size_of::<AllowlistEntry>() + DISCRIMINATOR_SIZE)]
+    pub allowlist_entry: Account<'info, AllowlistEntry>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts, Clone)]
+#[instruction()]
+pub struct RemoveAllowlistEntry<'info> {
+    #[account(constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner)]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(mut, close = owner, constraint = allowlist_entry.pool == pole_pool.key() @ PoleError::NotAllowlisted)]
+    pub allowlist_entry: Account<'info, AllowlistEntry>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts, Clone)]
+#[instruction(tiers: Vec<FeeTier>)]
+pub struct ChangeFeeSchedule<'info> {
+    #[account(mut, constraint = pole_pool.load()?.generic_config.owner == owner.key() @ PoleError::InvalidOwner,
+        constraint = tiers.len() <= MAX_FEE_TIERS @ PoleError::InvalidPoolConfig,
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    pub owner: Signer<'info>,
+}
+#[derive(Accounts, Clone)]
+pub struct MarketAccounts<'info> {
+    #[account(mut)]
+    pub market: AccountInfo<'info>,
+    #[account(mut)]
+    pub open_orders: AccountInfo<'info>,
+    #[account(mut)]
+    pub request_queue: AccountInfo<'info>,
+    #[account(mut)]
+    pub event_queue: AccountInfo<'info>,
+    #[account(mut)]
+    pub bids: AccountInfo<'info>,
+    #[account(mut)]
+    pub asks: AccountInfo<'info>,
+    #[account(mut)]
+    pub coin_vault: AccountInfo<'info>,
+    #[account(mut)]
+    pub pc_vault: AccountInfo<'info>,
+    pub vault_signer: AccountInfo<'info>,
+}
+
+impl<'info> MarketAccounts<'info> {
+    //order_payer funds the order, coin_wallet pays an ask or receives a bid
+    pub(crate) fn swap_market(
+        &self,
+        order_payer: &AccountInfo<'info>,
+        coin_wallet: &AccountInfo<'info>,
+    ) -> SwapMarketAccounts<'info> {
+        SwapMarketAccounts {
+            market: self.market.clone(),
+            open_orders: self.open_orders.clone(),
+            request_queue: self.request_queue.clone(),
+            event_queue: self.event_queue.clone(),
+            bids: self.bids.clone(),
+            asks: self.asks.clone(),
+            order_payer_token_account: order_payer.clone(),
+            coin_vault: self.coin_vault.clone(),
+            pc_vault: self.pc_vault.clone(),
+            vault_signer: self.vault_signer.clone(),
+            coin_wallet: coin_wallet.clone(),
+        }
+    }
+
+    //a user swap pays from `paid` into `received`, as coin into pc on an ask and the other way
+    //on a bid; returns the market accounts and the pc wallet
+    pub(crate) fn user_swap_market(
+        &self,
+        paid: &AccountInfo<'info>,
+        received: &AccountInfo<'info>,
+        ask: bool,
+    ) -> (SwapMarketAccounts<'info>, AccountInfo<'info>) {
+        let (coin_wallet, pc_wallet) = if ask {
+            (paid, received)
+        } else {
+            (received, paid)
+        };
+        (self.swap_market(paid, coin_wallet), pc_wallet.clone())
+    }
+}
+
+//trail the remaining accounts of a redeem_liquidity that swaps its payout,
+//market_accounts.open_orders is the user's own open orders on that market
+#[derive(Accounts, Clone)]
+pub struct ZapOutAccounts<'info> {
+    pub market_accounts: MarketAccounts<'info>,
+    #[account(mut)]
+    pub user_output_wallet: AccountInfo<'info>,
+    #[account(executable)]
+    pub dex_program: AccountInfo<'info>,
+    #[account(executable)]
+    pub swap_program: AccountInfo<'info>,
+    pub rent: AccountInfo<'info>,
+}
+
+//trail the remaining accounts of the first deposit_liquidity leg when it swaps into the
+//liquidity, market_accounts.open_orders is the user's own open orders on that market
+#[derive(Accounts, Clone)]
+pub struct ZapInAccounts<'info> {
+    pub market_accounts: MarketAccounts<'info>,
     #[account(mut)]
-    pub pc_vault: AccountInfo<'info>,
-    pub vault_signer: AccountInfo<'info>,
+    pub user_source_wallet: AccountInfo<'info>,
+    #[account(constraint = mint(&user_source_wallet)? == source_mint.key() @ PoleError::InvalidSwapMarket)]
+    pub source_mint: AccountInfo<'info>,
+    #[account(executable)]
+    pub dex_program: AccountInfo<'info>,
+    #[account(executable)]
+    pub swap_program: AccountInfo<'info>,
+    pub rent: AccountInfo<'info>,
 }
 
 #[derive(Accounts, Clone)]
@@ -403,6 +1381,42 @@ pub struct CreateUserBalance<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts, Clone)]
+#[instruction()]
+pub struct SetReferrer<'info> {
+    #[account(
+        constraint = pole_pool.load()?.generic_config.liquidity_supply == liquidity_supply.key() @ PoleError::InvalidLiquidityWallet,
+        constraint = pole_pool.load()?.generic_config.token_program == token_program.key() @ PoleError::InvalidTokenProgram,
+    )]
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    #[account(mut,
+        constraint = user_position.pool == pole_pool.key() @ PoleError::InvalidUserPosition,
+        constraint = user_position.user == user.key() @ PoleError::InvalidUserPosition,
+    )]
+    pub user_position: Account<'info, UserPosition>,
+    #[account(owner = token_program.key(),
+        constraint = mint(&referrer_fee_account)? == mint(&liquidity_supply)? @ PoleError::InvalidReferrer,
+    )]
+    pub referrer_fee_account: AccountInfo<'info>,
+    pub liquidity_supply: AccountInfo<'info>,
+    pub user: Signer<'info>,
+    #[account(executable)]
+    pub token_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts, Clone)]
+#[instruction(bump: u8)]
+pub struct CreateUserPosition<'info> {
+    pub pole_pool: AccountLoader<'info, PolePortPool>,
+    //keyed by the owner of the liquidity wallet the deposits come from, as deposit and redeem check
+    #[account(init, payer = user, seeds = [POSITION_SEED, pole_pool.key().as_ref(), anchor_spl::token::accessor::authority(&user_liquidity_wallet)?.as_ref()], bump = bump, space = size_of::<UserPosition>() + DISCRIMINATOR_SIZE)]
+    pub user_position: Account<'info, UserPosition>,
+    pub user_liquidity_wallet: AccountInfo<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts, Clone)]
 #[instruction(amount: u64)]
 pub struct AddBalance<'info> {
@@ -513,6 +1527,283 @@ impl<'a> PolePortAccounts for ChangeWithdrawFee<'a> {
         self.pole_pool.load()
     }
 }
+impl<'a> PolePortAccounts for ChangeHarvestConfig<'a> {
+    fn get_pole_pool(&self) -> Result<Ref<PolePortPool>, ProgramError> {
+        self.pole_pool.load()
+    }
+}
+
+impl<'a> PolePortAccounts for UnwindObligation<'a> {
+    fn get_pole_pool(&self) -> Result<Ref<PolePortPool>, ProgramError> {
+        self.pole_pool.load()
+    }
+}
+
+impl<'info> ClosePool<'info> {
+    pub fn create_transfer_cpi<'a, 'b, 'c>(
+        &self,
+        from: AccountInfo<'info>,
+        to: AccountInfo<'info>,
+        seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from,
+            to,
+            authority: self.pole_authority.clone(),
+        };
+        CpiContext::new_with_signer(self.token_program.clone(), cpi_accounts, seeds)
+    }
+
+    pub fn create_close_account_cpi<'a, 'b, 'c>(
+        &self,
+        account: AccountInfo<'info>,
+        seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, CloseAccount<'info>> {
+        let cpi_accounts = CloseAccount {
+            account,
+            destination: self.owner.to_account_info(),
+            authority: self.pole_authority.clone(),
+        };
+        CpiContext::new_with_signer(self.token_program.clone(), cpi_accounts, seeds)
+    }
+
+    pub fn create_set_authority_cpi<'a, 'b, 'c>(
+        &self,
+        seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, SetAuthority<'info>> {
+        let cpi_accounts = SetAuthority {
+            current_authority: self.pole_authority.clone(),
+            account_or_mint: self.lp_mint.clone(),
+        };
+        CpiContext::new_with_signer(self.token_program.clone(), cpi_accounts, seeds)
+    }
+
+    pub fn create_close_open_orders_cpi<'a, 'b, 'c>(
+        &self,
+        open_orders: AccountInfo<'info>,
+        market: AccountInfo<'info>,
+        seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, CloseOpenOrders<'info>> {
+        let close = CloseOpenOrders {
+            open_orders,
+            authority: self.pole_authority.clone(),
+            destination: self.owner.to_account_info(),
+            market,
+        };
+        CpiContext::new_with_signer(self.dex_program.clone(), close, seeds)
+    }
+
+    //moves what is left in a pool authority wallet to dust_wallet, then closes it
+    pub fn empty_and_close(
+        &self,
+        account: &AccountInfo<'info>,
+        dust_wallet: Option<&AccountInfo<'info>>,
+        seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let dust = anchor_spl::token::accessor::amount(account)?;
+        if let (Some(dust_wallet), true) = (dust_wallet, dust > 0) {
+            anchor_spl::token::transfer(
+                self.create_transfer_cpi(account.clone(), dust_wallet.clone(), seeds),
+                dust,
+            )?;
+        }
+        anchor_spl::token::close_account(self.create_close_account_cpi(account.clone(), seeds))
+    }
+}
+
+impl<'info> MigrateAuthority<'info> {
+    pub fn create_set_authority_cpi<'a, 'b, 'c>(
+        &self,
+        account_or_mint: AccountInfo<'info>,
+        seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, SetAuthority<'info>> {
+        let cpi_accounts = SetAuthority {
+            current_authority: self.legacy_authority.clone(),
+            account_or_mint,
+        };
+        CpiContext::new_with_signer(self.token_program.clone(), cpi_accounts, seeds)
+    }
+
+    pub fn create_close_open_orders_cpi<'a, 'b, 'c>(
+        &self,
+        seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, CloseOpenOrders<'info>> {
+        let close = CloseOpenOrders {
+            open_orders: self.legacy_open_orders.clone(),
+            authority: self.legacy_authority.clone(),
+            destination: self.owner.to_account_info(),
+            market: self.dex_market.clone(),
+        };
+        CpiContext::new_with_signer(self.dex_program.clone(), close, seeds)
+    }
+
+    pub fn create_init_open_orders_cpi<'a, 'b, 'c>(
+        &self,
+        seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, InitOpenOrders<'info>> {
+        let init = InitOpenOrders {
+            open_orders: self.port_open_orders.clone(),
+            authority: self.pole_authority.clone(),
+            market: self.dex_market.clone(),
+            rent: self.rent.clone(),
+        };
+        CpiContext::new_with_signer(self.dex_program.clone(), init, seeds)
+    }
+}
+
+impl<'info> InitSubReward<'info> {
+    pub fn create_init_open_orders_cpi<'a, 'b, 'c>(
+        &self,
+        seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, InitOpenOrders<'info>> {
+        let init = InitOpenOrders {
+            open_orders: self.sub_reward_open_orders.clone(),
+            authority: self.pole_authority.clone(),
+            market: self.dex_market.clone(),
+            rent: self.rent.clone(),
+        };
+        CpiContext::new_with_signer(self.dex_program.clone(), init, seeds)
+    }
+}
+
+impl<'a> PolePortAccounts for SellSubReward<'a> {
+    fn get_pole_pool(&self) -> Result<Ref<PolePortPool>, ProgramError> {
+        self.pole_pool.load()
+    }
+}
+
+impl<'info, 'a, 'b, 'c> SellSubReward<'info> {
+    pub(crate) fn create_bounty_transfer_context(
+        &self,
+        seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.liquidity_supply.clone(),
+            to: self.harvester_liquidity_wallet.clone(),
+            authority: self.pole_authority.clone(),
+        };
+        CpiContext::new_with_signer(self.token_program.clone(), cpi_accounts, seeds)
+    }
+    pub(crate) fn create_swap_context(
+        &self,
+        seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, Swap<'info>> {
+        let cpi_accounts = Swap {
+            market: self
+                .market_accounts
+                .swap_market(&self.sub_reward_supply, &self.sub_reward_supply),
+            authority: self.pole_authority.clone(),
+            pc_wallet: self.liquidity_supply.clone(),
+            dex_program: self.dex_program.clone(),
+            token_program: self.token_program.clone(),
+            rent: self.rent.clone(),
+        };
+        CpiContext::new_with_signer(self.swap_program.clone(), cpi_accounts, seeds)
+    }
+}
+
+impl<'info> InitSellRoute<'info> {
+    pub fn create_init_open_orders_cpi<'a, 'b, 'c>(
+        &self,
+        seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, InitOpenOrders<'info>> {
+        let init = InitOpenOrders {
+            open_orders: self.route_open_orders.clone(),
+            authority: self.pole_authority.clone(),
+            market: self.route_market.clone(),
+            rent: self.rent.clone(),
+        };
+        CpiContext::new_with_signer(self.dex_program.clone(), init, seeds)
+    }
+}
+
+impl<'info> SettleOpenOrders<'info> {
+    pub fn create_settle_funds_cpi<'a, 'b, 'c>(
+        &self,
+        seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, SettleFunds<'info>> {
+        let settle = SettleFunds {
+            market: self.market_accounts.market.clone(),
+            open_orders: self.market_accounts.open_orders.clone(),
+            open_orders_authority: self.pole_authority.clone(),
+            coin_vault: self.market_accounts.coin_vault.clone(),
+            pc_vault: self.market_accounts.pc_vault.clone(),
+            coin_wallet: self.coin_wallet.clone(),
+            pc_wallet: self.pc_wallet.clone(),
+            vault_signer: self.market_accounts.vault_signer.clone(),
+            token_program: self.token_program.clone(),
+        };
+        CpiContext::new_with_signer(self.dex_program.clone(), settle, seeds)
+    }
+}
+
+impl<'info> ClosePoolOpenOrders<'info> {
+    pub fn create_close_open_orders_cpi<'a, 'b, 'c>(
+        &self,
+        seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, CloseOpenOrders<'info>> {
+        let close = CloseOpenOrders {
+            open_orders: self.port_open_orders.clone(),
+            authority: self.pole_authority.clone(),
+            destination: self.owner.to_account_info(),
+            market: self.dex_market.clone(),
+        };
+        CpiContext::new_with_signer(self.dex_program.clone(), close, seeds)
+    }
+}
+
+impl<'info> MigrateMarket<'info> {
+    pub fn create_close_open_orders_cpi<'a, 'b, 'c>(
+        &self,
+        seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, CloseOpenOrders<'info>> {
+        let close = CloseOpenOrders {
+            open_orders: self.legacy_open_orders.clone(),
+            authority: self.pole_authority.clone(),
+            destination: self.owner.to_account_info(),
+            market: self.legacy_market.clone(),
+        };
+        CpiContext::new_with_signer(self.dex_program.clone(), close, seeds)
+    }
+
+    pub fn create_init_open_orders_cpi<'a, 'b, 'c>(
+        &self,
+        seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, InitOpenOrders<'info>> {
+        let init = InitOpenOrders {
+            open_orders: self.port_open_orders.clone(),
+            authority: self.pole_authority.clone(),
+            market: self.dex_market.clone(),
+            rent: self.rent.clone(),
+        };
+        CpiContext::new_with_signer(self.dex_program.clone(), init, seeds)
+    }
+}
+
+impl<'info> RecoverPortState<'info> {
+    //deposit approves the user liquidity wallet, the LP goes to a wallet of the same owner
+    pub(crate) fn check_deposit_wallets(&self, port_state: &PortState) -> ProgramResult {
+        let approved_wallet = port_state.approved_wallet;
+        if approved_wallet != self.pole_liquidity_accounts.user_liquidity_wallet.key()
+            || authority(&self.pole_liquidity_accounts.user_liquidity_wallet)?
+                != authority(&self.pole_lp_accounts.user_lp_wallet)?
+        {
+            return Err(PoleError::WrongWallet.into());
+        }
+        Ok(())
+    }
+    //redeem approves the user LP wallet, the liquidity goes to a wallet of the same owner
+    pub(crate) fn check_redeem_wallets(&self, port_state: &PortState) -> ProgramResult {
+        let approved_wallet = port_state.approved_wallet;
+        if approved_wallet != self.pole_lp_accounts.user_lp_wallet.key()
+            || authority(&self.pole_lp_accounts.user_lp_wallet)?
+                != authority(&self.pole_liquidity_accounts.user_liquidity_wallet)?
+        {
+            return Err(PoleError::WrongWallet.into());
+        }
+        Ok(())
+    }
+}
 
 impl<'info> PoleLPAccounts<'info> {
     pub(crate) fn create_mint_to_context<'a, 'b, 'c>(
@@ -747,25 +2038,62 @@ impl<'a> PolePortAccounts for ClaimAndSell<'a> {
     }
 }
 
+impl<'a> PolePortAccounts for AutoCompound<'a> {
+    fn get_pole_pool(&self) -> Result<Ref<PolePortPool>, ProgramError> {
+        self.pole_pool.load()
+    }
+}
+
+impl<'a> PolePortAccounts for RequestWithdraw<'a> {
+    fn get_pole_pool(&self) -> Result<Ref<PolePortPool>, ProgramError> {
+        self.pole_pool.load()
+    }
+}
+
+impl<'a> PolePortAccounts for UnwindForWithdrawals<'a> {
+    fn get_pole_pool(&self) -> Result<Ref<PolePortPool>, ProgramError> {
+        self.pole_pool.load()
+    }
+}
+
+impl<'a> PolePortAccounts for ProcessWithdrawRequest<'a> {
+    fn get_pole_pool(&self) -> Result<Ref<PolePortPool>, ProgramError> {
+        self.pole_pool.load()
+    }
+}
+
+impl<'a> PolePortAccounts for ClosePool<'a> {
+    fn get_pole_pool(&self) -> Result<Ref<PolePortPool>, ProgramError> {
+        self.pole_pool.load()
+    }
+}
+
+impl<'a> PolePortAccounts for Sweep<'a> {
+    fn get_pole_pool(&self) -> Result<Ref<PolePortPool>, ProgramError> {
+        self.pole_pool.load()
+    }
+}
+
 impl<'info, 'a, 'b, 'c> ClaimAndSell<'info> {
+    pub(crate) fn create_bounty_transfer_context(
+        &self,
+        seeds: &'a [&'b [&'c [u8]]],
+    ) -> CpiContext<'a, 'b, 'c, 'info, Transfer<'info>> {
+        let cpi_accounts = Transfer {
+            from: self.liquidity_supply.clone(),
+            to: self.harvester_liquidity_wallet.clone(),
+            authority: self.pole_authority.clone(),
+        };
+        CpiContext::new_with_signer(self.token_program.clone(), cpi_accounts, seeds)
+    }
     pub(crate) fn create_swap_context(
         &self,
         seeds: &'a [&'b [&'c [u8]]],
     ) -> CpiContext<'a, 'b, 'c, 'info, Swap<'info>> {
         let cpi_accounts = Swap {
-            market: SwapMarketAccounts {
-                market: self.market_accounts.market.clone(),
-                open_orders: self.market_accounts.open_orders.clone(),
-                request_queue: self.market_accounts.request_queue.clone(),
-                event_queue: self.market_accounts.event_queue.clone(),
-                bids: self.market_accounts.bids.clone(),
-                asks: self.market_accounts.asks.clone(),
-                order_payer_token_account: self.port_supply.clone(),
-                coin_vault: self.market_accounts.coin_vault.clone(),
-                pc_vault: self.market_accounts.pc_vault.clone(),
-                vault_signer: self.market_accounts.vault_signer.clone(),
-                coin_wallet: self.port_supply.clone(),
-            },
+            market: self
+                .market_accounts
+                .swap_market(&self.port_supply, &self.port_supply),
             authority: self.pole_authority.clone(),
             pc_wallet: self.liquidity_supply.clone(),
             dex_program: self.dex_program.clone(),
@@ -774,4 +2102,103 @@ impl<'info, 'a, 'b, 'c> ClaimAndSell<'info> {
         };
         CpiContext::new_with_signer(self.swap_program.clone(), cpi_accounts, seeds)
     }
+
+    //route is the pool's SellRoute, the second hop's market accounts in MarketAccounts order,
+    //then the route wallet
+    pub(crate) fn create_swap_transitive_context(
+        &self,
+        route: &[AccountInfo<'info>],
+        seeds: &'a [&'b [&'c [u8]]],
+    ) -> Result<CpiContext<'a, 'b, 'c, 'info, SwapTransitive<'info>>, ProgramError> {
+        if route.len() != SELL_ROUTE_ACCOUNTS {
+            return Err(PoleError::InvalidSellRoute.into());
+        }
+        let sell_route: Account<SellRoute> = Account::try_from(&route[0])?;
+        if sell_route.pool != self.pole_pool.key()
+            || sell_route.market != route[1].key()
+            || sell_route.open_orders != route[2].key()
+            || sell_route.wallet != route[10].key()
+        {
+            return Err(PoleError::InvalidSellRoute.into());
+        }
+        let route_wallet = &route[10];
+        let cpi_accounts = SwapTransitive {
+            from: self
+                .market_accounts
+                .swap_market(&self.port_supply, &self.port_supply),
+            to: SwapMarketAccounts {
+                market: route[1].clone(),
+                open_orders: route[2].clone(),
+                request_queue: route[3].clone(),
+                event_queue: route[4].clone(),
+                bids: route[5].clone(),
+                asks: route[6].clone(),
+                order_payer_token_account: route_wallet.clone(),
+                coin_vault: route[7].clone(),
+                pc_vault: route[8].clone(),
+                vault_signer: route[9].clone(),
+                coin_wallet: self.liquidity_supply.clone(),
+            },
+            authority: self.pole_authority.clone(),
+            pc_wallet: route_wallet.clone(),
+            dex_program: self.dex_program.clone(),
+            token_program: self.token_program.clone(),
+            rent: self.rent.clone(),
+        };
+        Ok(CpiContext::new_with_signer(
+            self.swap_program.clone(),
+            cpi_accounts,
+            seeds,
+        ))
+    }
+}
+
+impl<'info, 'a, 'b, 'c> ZapOutAccounts<'info> {
+    pub(crate) fn create_swap_context(
+        &self,
+        user_liquidity_wallet: &AccountInfo<'info>,
+        user_transfer_authority: AccountInfo<'info>,
+        token_program: &AccountInfo<'info>,
+        ask: bool,
+    ) -> CpiContext<'a, 'b, 'c, 'info, Swap<'info>> {
+        let (market, pc_wallet) = self.market_accounts.user_swap_market(
+            user_liquidity_wallet,
+            &self.user_output_wallet,
+            ask,
+        );
+        let cpi_accounts = Swap {
+            market,
+            authority: user_transfer_authority,
+            pc_wallet,
+            dex_program: self.dex_program.clone(),
+            token_program: token_program.clone(),
+            rent: self.rent.clone(),
+        };
+        CpiContext::new(self.swap_program.clone(), cpi_accounts)
+    }
+}
+
+impl<'info, 'a, 'b, 'c> ZapInAccounts<'info> {
+    pub(crate) fn create_swap_context(
+        &self,
+        user_liquidity_wallet: &AccountInfo<'info>,
+        user_transfer_authority: AccountInfo<'info>,
+        token_program: &AccountInfo<'info>,
+        ask: bool,
+    ) -> CpiContext<'a, 'b, 'c, 'info, Swap<'info>> {
+        let (market, pc_wallet) = self.market_accounts.user_swap_market(
+            &self.user_source_wallet,
+            user_liquidity_wallet,
+            ask,
+        );
+        let cpi_accounts = Swap {
+            market,
+            authority: user_transfer_authority,
+            pc_wallet,
+            dex_program: self.dex_program.clone(),
+            token_program: token_program.clone(),
+            rent: self.rent.clone(),
+        };
+        CpiContext::new(self.swap_program.clone(), cpi_accounts)
+    }
 }
@@ -12,25 +12,32 @@ pub mod states;
 declare_id!("PoLEr5uRhLSpEZgmBaSmzTUVbEANuFp4vBARZbKsqnu");
 const DEPOSIT_LIQUIDITY_SIGHASH: [u8; 8] = [245, 99, 59, 25, 151, 71, 233, 249]; //update this when update the instruction name
 const REDEEM_LIQUIDITY_SIGHASH: [u8; 8] = [180, 117, 142, 137, 227, 225, 97, 211]; //update this when update the instruction name
+const AUTO_COMPOUND_SIGHASH: [u8; 8] = [190, 236, 229, 204, 126, 66, 94, 179]; //update this when update the instruction name
 #[program]
 pub mod pole {
-    use anchor_spl::dex::init_open_orders;
+    use anchor_spl::dex;
     use anchor_spl::token;
-    use anchor_spl::token::{burn, transfer, Transfer};
+    use anchor_spl::token::{burn, transfer};
     use serum_swap::{ExchangeRate, Side};
     use std::convert::TryFrom;
 
     use crate::adaptors::decimal;
     use crate::error::PoleError;
-    use crate::event::{DidDeposit, DidRedeem, DidSell};
+    use crate::event::{DidCompound, DidRedeem, DidRefer, DidSell, DidZap, DidZapOut};
     use crate::helpers::*;
-    use crate::states::{BasicState, GenericPoolConfig, PortConfig, PortState, SerumConfig};
+    use crate::states::{
+        BasicState, FeeSchedule, FeeSplit, GenericPoolConfig, HarvestConfig, PortConfig, PortState,
+        RecoverLeg, RegistryEntry, SellRoute, SerumConfig, SubReward, WithdrawQueue,
+        CURRENT_LAYOUT_VERSION, DISCRIMINATOR_SIZE, MAX_FEE_RECIPIENTS, MAX_FEE_TIERS, POOL_ACTIVE,
+        POOL_AUTHORITY, POOL_DEPRECATED, WITHDRAW_PENDING, WITHDRAW_SETTLED,
+    };
     use anchor_lang::solana_program::sysvar::instructions::get_instruction_relative;
     use anchor_spl::dex::serum_dex::state::MarketState;
-    use anchor_spl::token::accessor::amount;
+    use anchor_spl::token::accessor::{amount, mint};
     use port_anchor_adaptor::port_accessor::exchange_rate;
     use port_anchor_adaptor::*;
-    use solana_maths::{Decimal, Rate, TryAdd, TryDiv, TryMul, TrySub, U128, U192};
+    use solana_maths::{Decimal, Rate, TryAdd, TryDiv, TryMul};
+    use spl_token::instruction::AuthorityType;
 
     use super::*;
 
@@ -75,33 +82,96 @@ pub mod pole {
         pole_pool.serum_config = serum_config;
         pole_pool.basic_state = BasicState::default();
         pole_pool.port_state = PortState::default();
+        pole_pool.harvest_config = HarvestConfig::default();
+        pole_pool.authority_version = POOL_AUTHORITY;
+        pole_pool.layout_version = CURRENT_LAYOUT_VERSION;
 
-        init_open_orders(ctx.accounts.create_init_open_orders_cpi(
+        if let Some(registry_page) = split_registry_page(ctx.remaining_accounts)? {
+            registry_page.load_mut()?.register(RegistryEntry {
+                pool: ctx.accounts.pole_pool.key(),
+                liquidity_mint: ctx.accounts.liquidity_mint.key(),
+                reserve: init_params.reserve,
+                status: POOL_ACTIVE,
+                created_slot: ctx.accounts.clock.slot,
+            })?;
+        }
+
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [authority_bump];
+        dex::init_open_orders(ctx.accounts.create_init_open_orders_cpi(
             ctx.accounts.port_open_orders.clone(),
-            &[&[&[pole_pool.generic_config.bump as u8]]],
+            &[&pole_pool.authority_seeds(&pole_pool_key, &bump)[..]],
         ))?;
 
         Ok(())
     }
 
+    pub fn init_registry(ctx: Context<InitRegistry>, bump: u8, page_bump: u8) -> ProgramResult {
+        let registry = &mut ctx.accounts.registry.load_init()?;
+        registry.owner = ctx.accounts.owner.key();
+        registry.bump = bump as u64;
+        registry.page_count = 1;
+        let registry_page = &mut ctx.accounts.registry_page.load_init()?;
+        registry_page.index = 0;
+        registry_page.bump = page_bump as u64;
+        Ok(())
+    }
+
+    //pools go to any page with room, usually the last one
+    pub fn add_registry_page(ctx: Context<AddRegistryPage>, bump: u8) -> ProgramResult {
+        let registry = &mut ctx.accounts.registry.load_mut()?;
+        let registry_page = &mut ctx.accounts.registry_page.load_init()?;
+        registry_page.index = registry.page_count;
+        registry_page.bump = bump as u64;
+        registry.page_count = registry
+            .page_count
+            .checked_add(1)
+            .ok_or(PoleError::MathOverflow)?;
+        Ok(())
+    }
+
+    //the creation slot of a pool that predates the registry is unknown, it gets the current one
+    pub fn register_pool(ctx: Context<RegisterPool>) -> ProgramResult {
+        let pole_pool = ctx.accounts.pole_pool.load()?;
+        ctx.accounts
+            .registry_page
+            .load_mut()?
+            .register(RegistryEntry {
+                pool: ctx.accounts.pole_pool.key(),
+                liquidity_mint: mint(&ctx.accounts.liquidity_supply)?,
+                reserve: pole_pool.port_config.reserve,
+                status: POOL_ACTIVE,
+                created_slot: ctx.accounts.clock.slot,
+            })
+    }
+
+    //the pool stays listed so existing depositors can find it, frontends hide it
+    pub fn deprecate_pool(ctx: Context<DeprecatePool>) -> ProgramResult {
+        ctx.accounts
+            .registry_page
+            .load_mut()?
+            .set_status(&ctx.accounts.pole_pool.key(), POOL_DEPRECATED)
+    }
+
+    pub fn unregister_pool(ctx: Context<UnregisterPool>, pool: Pubkey) -> ProgramResult {
+        ctx.accounts.registry_page.load_mut()?.unregister(&pool)
+    }
+
     #[access_control(valid_pole_pool(&ctx))]
     pub fn init_port_accounts(ctx: Context<InitPortAccounts>) -> ProgramResult {
         let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [pole_pool.generic_config.bump as u8];
+        let seeds = pole_pool.authority_seeds(&pole_pool_key, &bump);
 
         let port_config = &mut pole_pool.port_config;
         port_config.obligation = ctx.accounts.obligation.key();
         port_config.stake_account = ctx.accounts.stake_account.key();
         port_config.port_lending_program = ctx.accounts.port_lending_program.key();
         port_config.port_staking_program = ctx.accounts.port_staking_program.key();
-        init_obligation(
-            ctx.accounts
-                .create_init_obligation_cpi(&[&[&[pole_pool.generic_config.bump as u8]]]),
-        )?;
+        init_obligation(ctx.accounts.create_init_obligation_cpi(&[&seeds[..]]))?;
 
-        create_stake_account(
-            ctx.accounts
-                .create_create_stake_account_cpi(&[&[&[pole_pool.generic_config.bump as u8]]]),
-        )?;
+        create_stake_account(ctx.accounts.create_create_stake_account_cpi(&[&seeds[..]]))?;
         Ok(())
     }
 
@@ -127,7 +197,7 @@ pub mod pole {
                         .map_err(|_| {
                             anchor_lang::__private::ErrorCode::InstructionDidNotDeserialize
                         })?;
-                    let crate::instruction::DepositLiquidity { amount } = ix;
+                    let crate::instruction::DepositLiquidity { amount, .. } = ix;
                     if (amount > 0) ^ (count == 0) {
                         return Err(PoleError::DepositAmountInvalid.into());
                     }
@@ -149,6 +219,14 @@ pub mod pole {
             return Err(PoleError::InvalidTransaction.into());
         }
 
+        if pole_pool.allowlist_enabled == 1 {
+            verify_allowlisted(
+                &ctx.accounts.pole_pool.key(),
+                &approved_wallet,
+                ctx.remaining_accounts,
+            )?;
+        }
+
         pole_pool.port_state.deposit_verified = 1;
         pole_pool.port_state.approved_wallet = approved_wallet;
         Ok(())
@@ -177,7 +255,7 @@ pub mod pole {
                         .map_err(|_| {
                             anchor_lang::__private::ErrorCode::InstructionDidNotDeserialize
                         })?;
-                    let crate::instruction::RedeemLiquidity { amount } = ix;
+                    let crate::instruction::RedeemLiquidity { amount, .. } = ix;
 
                     if redeem_amount == 0 {
                         redeem_amount = amount;
@@ -208,19 +286,107 @@ pub mod pole {
         pole_pool.port_state.approved_wallet = approved_wallet;
         Ok(())
     }
-    //assume reserve and obligation are refreshed
+    //min_lp_out and zap_in_min_liquidity_out are only read on the first leg, which with the
+    //latter swaps amount of the zap in's source into the user wallet and deposits what it returned
     #[access_control(valid_pole_pool(&ctx))]
-    pub fn deposit_liquidity(ctx: Context<DepositLiquidity>, amount: u64) -> ProgramResult {
+    pub fn deposit_liquidity(
+        ctx: Context<DepositLiquidity>,
+        amount: u64,
+        min_lp_out: u64,
+        zap_in_min_liquidity_out: Option<u64>,
+    ) -> ProgramResult {
+        let (zap_in, remaining_accounts) = split_zap_in(
+            ctx.program_id,
+            ctx.remaining_accounts,
+            zap_in_min_liquidity_out.is_some(),
+        )?;
+        let (mut user_position, oracles) = split_user_position(
+            &ctx.accounts.pole_pool.key(),
+            &token::accessor::authority(
+                &ctx.accounts.pole_liquidity_accounts.user_liquidity_wallet,
+            )?,
+            remaining_accounts,
+        )?;
+        //only the first leg reads the obligation, later legs refresh it before borrowing
+        refresh_port_if_stale(
+            &ctx.accounts.port_accounts,
+            &PortLendingLeveragingParams {
+                user_liquidity: &ctx.accounts.pole_liquidity_accounts.pole_liquidity_wallet,
+                obligation_owner: &ctx.accounts.pole_authority,
+                transfer_authority: &ctx.accounts.pole_authority,
+                clock: &ctx.accounts.clock.to_account_info(),
+                token_program: &ctx.accounts.token_program,
+            },
+            oracles,
+            amount != 0,
+        )?;
         let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [pole_pool.generic_config.bump as u8];
+        let seeds = pole_pool.authority_seeds(&pole_pool_key, &bump);
         if pole_pool.port_state.leverage == 0 {
+            let amount = match (zap_in.as_ref(), zap_in_min_liquidity_out) {
+                (Some(zap_in), Some(min_liquidity_out)) => {
+                    if pole_pool.serum_config.dex_program != zap_in.dex_program.key() {
+                        return Err(PoleError::InvalidDexProgram.into());
+                    }
+                    if pole_pool.serum_config.swap_program != zap_in.swap_program.key() {
+                        return Err(PoleError::InvalidSwapProgram.into());
+                    }
+                    let user_liquidity_wallet =
+                        &ctx.accounts.pole_liquidity_accounts.user_liquidity_wallet;
+                    let source_mint = zap_in.source_mint.key();
+                    let ask = {
+                        let market = MarketState::load(
+                            &zap_in.market_accounts.market,
+                            zap_in.dex_program.key,
+                        )?;
+                        is_ask_into(&market, &source_mint, &mint(user_liquidity_wallet)?)?
+                    };
+                    let before_liquidity = token::accessor::amount(user_liquidity_wallet)?;
+                    serum_swap::cpi::swap(
+                        zap_in.create_swap_context(
+                            user_liquidity_wallet,
+                            ctx.accounts.user_transfer_authority.to_account_info(),
+                            &ctx.accounts.token_program,
+                            ask,
+                        ),
+                        if ask { Side::Ask } else { Side::Bid },
+                        amount,
+                        ExchangeRate {
+                            rate: 0,
+                            from_decimals: decimal(&zap_in.source_mint)?,
+                            quote_decimals: 0,
+                            strict: false,
+                        },
+                    )?;
+                    let liquidity_amount = token::accessor::amount(user_liquidity_wallet)?
+                        .saturating_sub(before_liquidity);
+                    if liquidity_amount < min_liquidity_out {
+                        return Err(PoleError::SwapOutTooSmall.into());
+                    }
+
+                    emit!(DidZap {
+                        source_mint,
+                        source_amount: amount,
+                        liquidity_amount,
+                    });
+                    liquidity_amount
+                }
+                _ => amount,
+            };
             let port_exchange_rate = exchange_rate(&ctx.accounts.port_accounts.reserve)?;
 
             let port_liquidity =
                 get_port_liquidity(&port_exchange_rate, &ctx.accounts.port_accounts.obligation)?;
 
+            //the liquidity unwind_for_withdrawals freed for the queue stays in the pole wallet
+            //until process_withdraw_request pays it out, the leverage steps leave it there
             let pole_init_liquidity = token::accessor::amount(
                 &ctx.accounts.pole_liquidity_accounts.pole_liquidity_wallet,
-            )?;
+            )?
+            .checked_sub(pole_pool.reserved_liquidity)
+            .ok_or(PoleError::MathOverflow)?;
 
             if port_liquidity
                 .try_add(pole_init_liquidity.into())?
@@ -229,6 +395,13 @@ pub mod pole {
             {
                 return Err(PoleError::MeetDepositLimit.into());
             }
+            match user_position.as_mut() {
+                Some(user_position) => user_position.add_principal(amount, pole_pool.wallet_cap)?,
+                None if pole_pool.wallet_cap != 0 => {
+                    return Err(PoleError::UserPositionRequired.into())
+                }
+                None => {}
+            }
 
             let user_liquidity_percentage =
                 Decimal::from(amount).try_div(amount + pole_init_liquidity)?;
@@ -239,7 +412,7 @@ pub mod pole {
                     .create_transfer_user_to_pole_context(
                         ctx.accounts.user_transfer_authority.to_account_info(),
                         ctx.accounts.token_program.clone(),
-                        &[&[&[pole_pool.generic_config.bump as u8]]],
+                        &[&seeds[..]],
                     ),
                 amount,
             )?;
@@ -247,90 +420,86 @@ pub mod pole {
 
             pole_pool.port_state.user_liquidity_percentage =
                 Rate::try_from(user_liquidity_percentage)?.0 .0;
+            pole_pool.port_state.min_amount_out = min_lp_out;
         }
 
-        let ltv = port_accessor::reserve_ltv(&ctx.accounts.port_accounts.reserve)?;
-        let lending_leveraging_params = PortLendingLeveragingParams {
-            user_liquidity: &ctx.accounts.pole_liquidity_accounts.pole_liquidity_wallet,
-            obligation_owner: &ctx.accounts.pole_authority,
-            transfer_authority: &ctx.accounts.pole_authority,
-            clock: &ctx.accounts.clock.to_account_info(),
-            token_program: &ctx.accounts.token_program,
-        };
-
-        port_lending_leveraging(
+        deposit_leverage_step(
+            pole_pool,
             &ctx.accounts.port_accounts,
-            &lending_leveraging_params,
-            (ltv as u64)
-                .checked_sub(pole_pool.port_config.port_reserve_percentage)
-                .ok_or(PoleError::MathOverflow)?,
-            pole_pool.port_config.port_iterate as u8,
-            pole_pool.port_state.leverage as u8,
-            pole_pool.generic_config.bump as u8,
+            &ctx.accounts.pole_liquidity_accounts.pole_liquidity_wallet,
+            &ctx.accounts.pole_authority,
+            &ctx.accounts.token_program,
+            &ctx.accounts.clock,
+            &seeds,
         )?;
-
-        pole_pool.port_state.leverage = pole_pool
-            .port_state
-            .leverage
-            .checked_add(1)
-            .ok_or(PoleError::MathOverflow)?;
         if pole_pool.port_state.leverage == pole_pool.port_config.port_iterate {
-            let port_exchange_rate = exchange_rate(&ctx.accounts.port_accounts.reserve)?;
-            let init_port_liquidity = Decimal(U192(pole_pool.port_state.init_port_liquidity));
-
-            let after_port_liquidity =
-                get_port_liquidity(&port_exchange_rate, &ctx.accounts.port_accounts.obligation)?;
-
-            assert_eq!(
-                token::accessor::amount(
-                    &ctx.accounts.pole_liquidity_accounts.pole_liquidity_wallet
-                )?,
-                0u64
-            );
-
-            let user_liquidity_rate = Rate(U128(pole_pool.port_state.user_liquidity_percentage));
-
-            let user_liquidity_gain = after_port_liquidity
-                .try_sub(init_port_liquidity)?
-                .try_mul(user_liquidity_rate)?
-                .try_floor_u64()?;
-            let exchange_rate = pole_pool
-                .basic_state
-                .exchange_rate(after_port_liquidity.try_sub(user_liquidity_gain.into())?)?;
-
-            let mint_amount = exchange_rate.liquidity_to_lp(user_liquidity_gain)?;
-            pole_pool.basic_state.lp_amount = pole_pool
-                .basic_state
-                .lp_amount
-                .checked_add(mint_amount)
-                .ok_or(PoleError::MathOverflow)?;
-            token::mint_to(
-                ctx.accounts.pole_lp_accounts.create_mint_to_context(
-                    ctx.accounts.pole_authority.clone(),
-                    ctx.accounts.token_program.clone(),
-                    &[&[&[pole_pool.generic_config.bump as u8]]],
-                ),
-                mint_amount,
+            let lp_minted = finish_deposit(
+                pole_pool,
+                &ctx.accounts.port_accounts,
+                &ctx.accounts.pole_liquidity_accounts,
+                &ctx.accounts.pole_lp_accounts,
+                &ctx.accounts.pole_authority,
+                &ctx.accounts.token_program,
+                &ctx.accounts.clock,
+                &seeds,
             )?;
-            //reset deposit state
-            pole_pool.port_state.reset();
-            let last_sold_slot = pole_pool.port_state.last_sold_slot;
-            if last_sold_slot == 0 {
-                pole_pool.port_state.last_sold_slot = ctx.accounts.clock.slot
-            };
-
-            emit!(DidDeposit {
-                liquidity_amount_deposited: user_liquidity_gain,
-                lp_amount_minted: mint_amount,
-            });
+            if let Some(user_position) = user_position.as_mut() {
+                user_position.deposit(lp_minted, ctx.accounts.clock.slot)?;
+            }
+        }
+        match user_position {
+            Some(user_position) => user_position.exit(ctx.program_id),
+            None => Ok(()),
         }
-        Ok(())
     }
 
-    //assume reserve and obligation are refreshed
-    #[access_control(valid_pole_pool(&ctx))]
-    pub fn redeem_liquidity(ctx: Context<RedeemLiquidity>, amount: u64) -> ProgramResult {
+    //min_liquidity_out is only read on the first leg, zap_out_min_output by the leg paying out,
+    //which then swaps exactly that payout into the zap out's output wallet
+    #[access_control(valid_outdated_pole_pool(&ctx))]
+    pub fn redeem_liquidity(
+        ctx: Context<RedeemLiquidity>,
+        amount: u64,
+        min_liquidity_out: u64,
+        zap_out_min_output: Option<u64>,
+    ) -> ProgramResult {
+        let (zap_out, remaining_accounts) = split_zap_out(
+            ctx.program_id,
+            ctx.remaining_accounts,
+            zap_out_min_output.is_some(),
+        )?;
+        let (mut user_position, remaining_accounts) = split_user_position(
+            &ctx.accounts.pole_pool.key(),
+            &token::accessor::authority(
+                &ctx.accounts.pole_liquidity_accounts.user_liquidity_wallet,
+            )?,
+            remaining_accounts,
+        )?;
+        let referrer = user_position
+            .as_ref()
+            .map_or(Pubkey::default(), |user_position| user_position.referrer);
+        let referrer_fee_account =
+            active_referrer(&referrer, remaining_accounts, &ctx.accounts.token_program)?;
+        let oracles = if referrer == Pubkey::default() {
+            remaining_accounts
+        } else {
+            &remaining_accounts[..remaining_accounts.len() - 1]
+        };
+        refresh_port_if_stale(
+            &ctx.accounts.port_accounts,
+            &PortLendingLeveragingParams {
+                user_liquidity: &ctx.accounts.pole_liquidity_accounts.pole_liquidity_wallet,
+                obligation_owner: &ctx.accounts.pole_authority,
+                transfer_authority: &ctx.accounts.pole_authority,
+                clock: &ctx.accounts.clock.to_account_info(),
+                token_program: &ctx.accounts.token_program,
+            },
+            oracles,
+            true,
+        )?;
         let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [pole_pool.generic_config.bump as u8];
+        let seeds = pole_pool.authority_seeds(&pole_pool_key, &bump);
         if pole_pool.port_state.is_redeemed != 1 {
             if pole_pool.port_state.leverage == 0 {
                 let port_exchange_rate = exchange_rate(&ctx.accounts.port_accounts.reserve)?;
@@ -351,17 +520,36 @@ pub mod pole {
 
                 let redeem_liquidity_amount = exchange_rate.lp_to_liquidity(amount)?;
 
+                pole_pool.redeem_fee_bips = match user_position.as_mut() {
+                    Some(user_position) => {
+                        user_position.cap_lp(token::accessor::amount(
+                            &ctx.accounts.pole_lp_accounts.user_lp_wallet,
+                        )?);
+                        let fee_bips = pole_pool.scheduled_fee_bips(
+                            amount,
+                            user_position.lp_amount,
+                            user_position.held_slots(ctx.accounts.clock.slot),
+                        )?;
+                        user_position.redeem(amount);
+                        fee_bips
+                    }
+                    None => pole_pool.scheduled_fee_bips(amount, 0, 0)?,
+                };
+
+                pole_pool.redeem_lp_amount = amount;
                 pole_pool.port_state.redeem_amount = redeem_liquidity_amount;
-                pole_pool.port_state.amount_to_unroll = pole_pool
-                    .port_state
-                    .redeem_amount
-                    .saturating_sub(available_liquidity);
+                pole_pool.port_state.min_amount_out = min_liquidity_out;
+                //the liquidity reserved for queued withdrawals is not the redeem's to take
+                pole_pool.port_state.amount_to_unroll =
+                    pole_pool.port_state.redeem_amount.saturating_sub(
+                        available_liquidity.saturating_sub(pole_pool.reserved_liquidity),
+                    );
                 //msg!("amount to unroll {:?}", pole_pool.port_state.amount_to_unroll);
                 burn(
                     ctx.accounts.pole_lp_accounts.create_burn_context(
                         ctx.accounts.user_transfer_authority.to_account_info(),
                         ctx.accounts.token_program.clone(),
-                        &[&[&[pole_pool.generic_config.bump as u8]]],
+                        &[&seeds[..]],
                     ),
                     amount,
                 )?;
@@ -372,90 +560,168 @@ pub mod pole {
                     .ok_or(PoleError::MathOverflow)?;
             }
 
-            if pole_pool.port_state.leverage > 0
-                || (pole_pool.port_state.leverage == 0
-                    && pole_pool.port_state.amount_to_unroll != 0)
-            {
-                let ltv = port_accessor::reserve_ltv(&ctx.accounts.port_accounts.reserve)?;
-                let lending_leveraging_params = PortLendingLeveragingParams {
-                    user_liquidity: &ctx.accounts.pole_liquidity_accounts.pole_liquidity_wallet,
-                    obligation_owner: &ctx.accounts.pole_authority,
-                    transfer_authority: &ctx.accounts.pole_authority,
-                    clock: &ctx.accounts.clock.to_account_info(),
-                    token_program: &ctx.accounts.token_program,
-                };
+            redeem_unroll_step(
+                pole_pool,
+                &ctx.accounts.port_accounts,
+                &ctx.accounts.pole_liquidity_accounts.pole_liquidity_wallet,
+                &ctx.accounts.pole_authority,
+                &ctx.accounts.token_program,
+                &ctx.accounts.clock,
+                &seeds,
+            )?;
 
-                let repay_ratio = Decimal::from_percent(
-                    ltv.checked_sub(pole_pool.port_config.port_reserve_percentage as u8)
-                        .ok_or(PoleError::MathOverflow)?,
-                );
-                port_lending_unroll(
-                    &ctx.accounts.port_accounts,
-                    &lending_leveraging_params,
-                    repay_ratio,
-                    pole_pool.port_config.port_iterate as u8,
-                    pole_pool.port_state.leverage as u8,
-                    pole_pool.generic_config.bump as u8,
-                    pole_pool.port_state.amount_to_unroll,
+            if pole_pool.is_redeem_unrolled()? {
+                let redeemed_liquidity = finish_redeem(
+                    pole_pool,
+                    &ctx.accounts.pole_liquidity_accounts,
+                    &ctx.accounts.pole_fee_account,
+                    referrer_fee_account,
+                    &ctx.accounts.pole_authority,
+                    &ctx.accounts.token_program,
+                    &seeds,
                 )?;
-            }
+                if let (Some(zap_out), Some(min_output)) = (zap_out.as_ref(), zap_out_min_output) {
+                    if pole_pool.serum_config.dex_program != zap_out.dex_program.key() {
+                        return Err(PoleError::InvalidDexProgram.into());
+                    }
+                    if pole_pool.serum_config.swap_program != zap_out.swap_program.key() {
+                        return Err(PoleError::InvalidSwapProgram.into());
+                    }
+                    let user_liquidity_wallet =
+                        &ctx.accounts.pole_liquidity_accounts.user_liquidity_wallet;
+                    let output_mint = mint(&zap_out.user_output_wallet)?;
+                    let ask = {
+                        let market = MarketState::load(
+                            &zap_out.market_accounts.market,
+                            zap_out.dex_program.key,
+                        )?;
+                        is_ask_into(&market, &mint(user_liquidity_wallet)?, &output_mint)?
+                    };
+                    let before_output = token::accessor::amount(&zap_out.user_output_wallet)?;
+                    serum_swap::cpi::swap(
+                        zap_out.create_swap_context(
+                            user_liquidity_wallet,
+                            ctx.accounts.user_transfer_authority.to_account_info(),
+                            &ctx.accounts.token_program,
+                            ask,
+                        ),
+                        if ask { Side::Ask } else { Side::Bid },
+                        redeemed_liquidity,
+                        ExchangeRate {
+                            rate: 0,
+                            from_decimals: decimal(&ctx.accounts.port_accounts.liquidity_mint)?,
+                            quote_decimals: 0,
+                            strict: false,
+                        },
+                    )?;
+                    let output_amount = token::accessor::amount(&zap_out.user_output_wallet)?
+                        .saturating_sub(before_output);
+                    if output_amount < min_output {
+                        return Err(PoleError::SwapOutTooSmall.into());
+                    }
 
-            if pole_pool.port_state.leverage
-                == pole_pool
-                    .port_config
-                    .port_iterate
-                    .checked_sub(1)
-                    .ok_or(PoleError::MathOverflow)?
-                || (pole_pool.port_state.leverage == 0
-                    && pole_pool.port_state.amount_to_unroll == 0)
-            {
-                let fee_rate = Decimal::from_bips(pole_pool.generic_config.withdraw_fee_bips);
-
-                let fee = fee_rate
-                    .try_mul(pole_pool.port_state.redeem_amount)?
-                    .try_ceil_u64()?;
-                let redeem_exclude_fee = pole_pool
-                    .port_state
-                    .redeem_amount
-                    .checked_sub(fee)
-                    .ok_or(PoleError::MathOverflow)?;
+                    emit!(DidZapOut {
+                        output_mint,
+                        liquidity_amount: redeemed_liquidity,
+                        output_amount,
+                    });
+                }
+            }
+        };
+        pole_pool.port_state.leverage = pole_pool
+            .port_state
+            .leverage
+            .checked_add(1)
+            .ok_or(PoleError::MathOverflow)?;
+        if pole_pool.port_state.leverage == pole_pool.port_config.port_iterate {
+            pole_pool.port_state.reset();
+        }
+        match user_position {
+            Some(user_position) => user_position.exit(ctx.program_id),
+            None => Ok(()),
+        }
+    }
 
-                transfer(
-                    {
-                        let cpi_accounts = Transfer {
-                            from: ctx
-                                .accounts
-                                .pole_liquidity_accounts
-                                .pole_liquidity_wallet
-                                .clone(),
-                            to: ctx.accounts.pole_fee_account.clone(),
-                            authority: ctx.accounts.pole_authority.clone(),
-                        };
-                        CpiContext::new_with_signer(
-                            ctx.accounts.token_program.clone(),
-                            cpi_accounts,
-                            &[&[&[pole_pool.generic_config.bump as u8]]],
-                        )
-                    },
-                    fee,
-                )?;
-                transfer(
-                    ctx.accounts
-                        .pole_liquidity_accounts
-                        .create_transfer_pole_to_user_context(
-                            ctx.accounts.pole_authority.clone(),
-                            ctx.accounts.token_program.clone(),
-                            &[&[&[pole_pool.generic_config.bump as u8]]],
-                        ),
-                    redeem_exclude_fee,
+    /// Drives a deposit or redeem left half-way by an earlier transaction one leg forward,
+    /// exactly as the missing `deposit_liquidity`/`redeem_liquidity` leg would have.
+    /// Owner only; call repeatedly (with the reserve refreshed) until the port state is reset.
+    pub fn recover_port_state(ctx: Context<RecoverPortState>) -> ProgramResult {
+        let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [pole_pool.generic_config.bump as u8];
+        let seeds = pole_pool.authority_seeds(&pole_pool_key, &bump);
+        let available_liquidity =
+            token::accessor::amount(&ctx.accounts.pole_liquidity_accounts.pole_liquidity_wallet)?;
+        let leg = pole_pool.port_state.recover_leg(available_liquidity)?;
+        match leg {
+            RecoverLeg::Reset => {
+                pole_pool.port_state.reset();
+                return Ok(());
+            }
+            RecoverLeg::DepositStep | RecoverLeg::FinishDeposit => {
+                ctx.accounts.check_deposit_wallets(&pole_pool.port_state)?;
+                if leg == RecoverLeg::DepositStep {
+                    deposit_leverage_step(
+                        pole_pool,
+                        &ctx.accounts.port_accounts,
+                        &ctx.accounts.pole_liquidity_accounts.pole_liquidity_wallet,
+                        &ctx.accounts.pole_authority,
+                        &ctx.accounts.token_program,
+                        &ctx.accounts.clock,
+                        &seeds,
+                    )?;
+                } else {
+                    //the borrowed liquidity is already in the obligation, skip to the final leg
+                    pole_pool.port_state.leverage = pole_pool.port_config.port_iterate;
+                }
+                if pole_pool.port_state.leverage == pole_pool.port_config.port_iterate {
+                    let lp_minted = finish_deposit(
+                        pole_pool,
+                        &ctx.accounts.port_accounts,
+                        &ctx.accounts.pole_liquidity_accounts,
+                        &ctx.accounts.pole_lp_accounts,
+                        &ctx.accounts.pole_authority,
+                        &ctx.accounts.token_program,
+                        &ctx.accounts.clock,
+                        &seeds,
+                    )?;
+                    //the depositor's position is credited as the final deposit leg would have
+                    if let (Some(mut user_position), _) = split_user_position(
+                        &pole_pool_key,
+                        &token::accessor::authority(&ctx.accounts.pole_lp_accounts.user_lp_wallet)?,
+                        ctx.remaining_accounts,
+                    )? {
+                        user_position.deposit(lp_minted, ctx.accounts.clock.slot)?;
+                        user_position.exit(ctx.program_id)?;
+                    }
+                }
+                return Ok(());
+            }
+            RecoverLeg::RedeemStep => {
+                ctx.accounts.check_redeem_wallets(&pole_pool.port_state)?;
+                redeem_unroll_step(
+                    pole_pool,
+                    &ctx.accounts.port_accounts,
+                    &ctx.accounts.pole_liquidity_accounts.pole_liquidity_wallet,
+                    &ctx.accounts.pole_authority,
+                    &ctx.accounts.token_program,
+                    &ctx.accounts.clock,
+                    &seeds,
                 )?;
-                pole_pool.port_state.is_redeemed = 1;
-                emit!(DidRedeem {
-                    liquidity_amount_redeemed: pole_pool.port_state.redeem_amount,
-                    lp_amount_burned: amount,
-                });
+                if pole_pool.is_redeem_unrolled()? {
+                    finish_redeem(
+                        pole_pool,
+                        &ctx.accounts.pole_liquidity_accounts,
+                        &ctx.accounts.pole_fee_account,
+                        None,
+                        &ctx.accounts.pole_authority,
+                        &ctx.accounts.token_program,
+                        &seeds,
+                    )?;
+                }
             }
-        };
+            RecoverLeg::Advance => {}
+        }
         pole_pool.port_state.leverage = pole_pool
             .port_state
             .leverage
@@ -469,16 +735,133 @@ pub mod pole {
 
     #[access_control(valid_pole_pool(&ctx))]
     pub fn claim_and_sell(ctx: Context<ClaimAndSell>) -> ProgramResult {
-        claim_reward(ctx.accounts.port_accounts.create_claim_reward_context(
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [ctx.accounts.pole_pool.load()?.generic_config.bump as u8];
+        let seeds = ctx
+            .accounts
+            .pole_pool
+            .load()?
+            .authority_seeds(&pole_pool_key, &bump);
+        //a harvest market that does not pay out the liquidity mint needs the sell route
+        let routed = {
+            let market = MarketState::load(
+                &ctx.accounts.market_accounts.market,
+                ctx.accounts.dex_program.key,
+            )?;
+            pc_mint(&market) != mint(&ctx.accounts.liquidity_supply)?
+        };
+        let (route, sub_reward) = ctx.remaining_accounts.split_at(if routed {
+            SELL_ROUTE_ACCOUNTS.min(ctx.remaining_accounts.len())
+        } else {
+            0
+        });
+        let signer = [&seeds[..]];
+        let mut claim = ctx.accounts.port_accounts.create_claim_reward_context(
             ctx.accounts.pole_authority.clone(),
             ctx.accounts.port_supply.clone(),
             ctx.accounts.clock.to_account_info(),
             ctx.accounts.token_program.clone(),
-            &[&[&[ctx.accounts.pole_pool.load()?.generic_config.bump as u8]]],
-        ))
-        .unwrap_or_else(|e| msg!("Unable to claim from port {:?}", e));
+            &signer,
+        );
+        //port staking pays the sub reward too when handed its supply and destination
+        if !sub_reward.is_empty() {
+            check_sub_reward(&pole_pool_key, sub_reward)?;
+            claim
+                .remaining_accounts
+                .extend(sub_reward[1..].iter().cloned());
+        }
+        claim_reward(claim).unwrap_or_else(|e| msg!("Unable to claim from port {:?}", e));
 
         let sell_amount = amount(&ctx.accounts.port_supply)?;
+        let before_liquidity = amount(&ctx.accounts.liquidity_supply)?;
+        let coin_lots = {
+            let market = MarketState::load(
+                &ctx.accounts.market_accounts.market,
+                ctx.accounts.dex_program.key,
+            )?;
+            sell_amount
+                .checked_div(market.coin_lot_size)
+                .ok_or(PoleError::MathOverflow)?
+        };
+        if coin_lots != 0u64 {
+            let exchange_rate = ExchangeRate {
+                rate: 0,
+                from_decimals: decimal(&ctx.accounts.port_mint)?,
+                quote_decimals: 0,
+                strict: false,
+            };
+            let swap = if !routed {
+                serum_swap::cpi::swap(
+                    ctx.accounts.create_swap_context(&[&seeds[..]]),
+                    Side::Ask,
+                    sell_amount,
+                    exchange_rate,
+                )
+            } else {
+                serum_swap::cpi::swap_transitive(
+                    ctx.accounts
+                        .create_swap_transitive_context(route, &[&seeds[..]])?,
+                    sell_amount,
+                    exchange_rate,
+                )
+            };
+            swap.unwrap_or_else(|e| msg!("Swap error {:?}", e));
+        }
+
+        let proceeds = amount(&ctx.accounts.liquidity_supply)?.saturating_sub(before_liquidity);
+        let bounty = Decimal::from_bips(
+            ctx.accounts
+                .pole_pool
+                .load()?
+                .harvest_config
+                .harvest_bounty_bips,
+        )
+        .try_mul(proceeds)?
+        .try_floor_u64()?;
+        if bounty != 0 {
+            transfer(
+                ctx.accounts.create_bounty_transfer_context(&[&seeds[..]]),
+                bounty,
+            )?;
+        }
+        ctx.accounts.pole_pool.load_mut()?.port_state.last_sold_slot = ctx.accounts.clock.slot;
+
+        emit!(DidSell {
+            reward_mint: ctx.accounts.port_mint.key(),
+            base_amount: sell_amount,
+            bounty_amount: bounty,
+            slot: ctx.accounts.clock.slot
+        });
+        Ok(())
+    }
+
+    pub fn init_sub_reward(ctx: Context<InitSubReward>, _bump: u8) -> ProgramResult {
+        let pole_pool = ctx.accounts.pole_pool.load()?;
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [pole_pool.generic_config.bump as u8];
+        let seeds = pole_pool.authority_seeds(&pole_pool_key, &bump);
+        dex::init_open_orders(ctx.accounts.create_init_open_orders_cpi(&[&seeds[..]]))?;
+
+        let sub_reward = &mut ctx.accounts.sub_reward;
+        sub_reward.pool = pole_pool_key;
+        sub_reward.mint = ctx.accounts.sub_reward_mint.key();
+        sub_reward.supply = ctx.accounts.sub_reward_supply.key();
+        sub_reward.open_orders = ctx.accounts.sub_reward_open_orders.key();
+        Ok(())
+    }
+
+    #[access_control(valid_pole_pool(&ctx))]
+    pub fn sell_sub_reward(ctx: Context<SellSubReward>) -> ProgramResult {
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [ctx.accounts.pole_pool.load()?.generic_config.bump as u8];
+        let seeds = ctx
+            .accounts
+            .pole_pool
+            .load()?
+            .authority_seeds(&pole_pool_key, &bump);
+
+        let sell_amount = amount(&ctx.accounts.sub_reward_supply)?;
+        let before_liquidity = amount(&ctx.accounts.liquidity_supply)?;
         let coin_lots = {
             let market = MarketState::load(
                 &ctx.accounts.market_accounts.market,
@@ -490,32 +873,779 @@ pub mod pole {
         };
         if coin_lots != 0u64 {
             serum_swap::cpi::swap(
-                ctx.accounts.create_swap_context(&[&[&[ctx
-                    .accounts
-                    .pole_pool
-                    .load()?
-                    .generic_config
-                    .bump as u8]]]),
+                ctx.accounts.create_swap_context(&[&seeds[..]]),
                 Side::Ask,
                 sell_amount,
                 ExchangeRate {
                     rate: 0,
-                    from_decimals: decimal(&ctx.accounts.port_mint)?,
+                    from_decimals: decimal(&ctx.accounts.sub_reward_mint)?,
                     quote_decimals: 0,
                     strict: false,
                 },
-            )
-            .unwrap_or_else(|e| msg!("Swap error {:?}", e));
+            )?;
         }
-        ctx.accounts.pole_pool.load_mut()?.port_state.last_sold_slot = ctx.accounts.clock.slot;
+
+        let proceeds = amount(&ctx.accounts.liquidity_supply)?.saturating_sub(before_liquidity);
+        let bounty = Decimal::from_bips(
+            ctx.accounts
+                .pole_pool
+                .load()?
+                .harvest_config
+                .harvest_bounty_bips,
+        )
+        .try_mul(proceeds)?
+        .try_floor_u64()?;
+        if bounty != 0 {
+            transfer(
+                ctx.accounts.create_bounty_transfer_context(&[&seeds[..]]),
+                bounty,
+            )?;
+        }
+        ctx.accounts.sub_reward.last_sold_slot = ctx.accounts.clock.slot;
 
         emit!(DidSell {
+            reward_mint: ctx.accounts.sub_reward_mint.key(),
             base_amount: sell_amount,
+            bounty_amount: bounty,
             slot: ctx.accounts.clock.slot
         });
         Ok(())
     }
 
+    //two-hop harvests sell PORT for the first market's pc, then buy the liquidity mint with it
+    pub fn init_sell_route(ctx: Context<InitSellRoute>, _bump: u8) -> ProgramResult {
+        let pole_pool = ctx.accounts.pole_pool.load()?;
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [pole_pool.generic_config.bump as u8];
+        let seeds = pole_pool.authority_seeds(&pole_pool_key, &bump);
+        dex::init_open_orders(ctx.accounts.create_init_open_orders_cpi(&[&seeds[..]]))?;
+
+        let sell_route = &mut ctx.accounts.sell_route;
+        sell_route.pool = pole_pool_key;
+        sell_route.market = ctx.accounts.route_market.key();
+        sell_route.open_orders = ctx.accounts.route_open_orders.key();
+        sell_route.wallet = ctx.accounts.route_wallet.key();
+        Ok(())
+    }
+
+    pub fn settle_open_orders(ctx: Context<SettleOpenOrders>) -> ProgramResult {
+        let pole_pool = ctx.accounts.pole_pool.load()?;
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let wallets = settle_wallets(
+            &pole_pool,
+            &pole_pool_key,
+            ctx.accounts.market_accounts.open_orders.key,
+            ctx.remaining_accounts,
+        )?;
+        if wallets != (ctx.accounts.coin_wallet.key(), ctx.accounts.pc_wallet.key()) {
+            return Err(PoleError::InvalidSettleAccounts.into());
+        }
+        let bump = [pole_pool.generic_config.bump as u8];
+        let seeds = pole_pool.authority_seeds(&pole_pool_key, &bump);
+        dex::settle_funds(ctx.accounts.create_settle_funds_cpi(&[&seeds[..]]))
+    }
+
+    pub fn close_open_orders(ctx: Context<ClosePoolOpenOrders>) -> ProgramResult {
+        let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [pole_pool.generic_config.bump as u8];
+        let seeds = pole_pool.authority_seeds(&pole_pool_key, &bump);
+        dex::close_open_orders(ctx.accounts.create_close_open_orders_cpi(&[&seeds[..]]))?;
+        pole_pool.serum_config.port_open_orders = Pubkey::default();
+        Ok(())
+    }
+
+    pub fn migrate_market(ctx: Context<MigrateMarket>) -> ProgramResult {
+        let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [pole_pool.generic_config.bump as u8];
+        let seeds = pole_pool.authority_seeds(&pole_pool_key, &bump);
+        if pole_pool.serum_config.port_open_orders != Pubkey::default() {
+            dex::close_open_orders(ctx.accounts.create_close_open_orders_cpi(&[&seeds[..]]))?;
+        }
+        dex::init_open_orders(ctx.accounts.create_init_open_orders_cpi(&[&seeds[..]]))?;
+        pole_pool.serum_config.port_open_orders = ctx.accounts.port_open_orders.key();
+        Ok(())
+    }
+
+    //owner only, for a pool with no LP left and nothing lent or borrowed on Port
+    #[access_control(valid_pole_pool(&ctx))]
+    pub fn close_pool(ctx: Context<ClosePool>) -> ProgramResult {
+        let pole_pool = &ctx.accounts.pole_pool.load()?;
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [pole_pool.generic_config.bump as u8];
+        let seeds = pole_pool.authority_seeds(&pole_pool_key, &bump);
+
+        let dust = amount(&ctx.accounts.liquidity_supply)?;
+        if dust > 0 {
+            transfer(
+                ctx.accounts.create_transfer_cpi(
+                    ctx.accounts.liquidity_supply.clone(),
+                    ctx.accounts.fee_receiver.clone(),
+                    &[&seeds[..]],
+                ),
+                dust,
+            )?;
+        }
+        let port_dust = amount(&ctx.accounts.port_supply)?;
+        if port_dust > 0 {
+            transfer(
+                ctx.accounts.create_transfer_cpi(
+                    ctx.accounts.port_supply.clone(),
+                    ctx.accounts.owner_port_wallet.clone(),
+                    &[&seeds[..]],
+                ),
+                port_dust,
+            )?;
+        }
+        for account in [
+            &ctx.accounts.liquidity_supply,
+            &ctx.accounts.port_lp_supply,
+            &ctx.accounts.port_supply,
+        ] {
+            token::close_account(
+                ctx.accounts
+                    .create_close_account_cpi(account.clone(), &[&seeds[..]]),
+            )?;
+        }
+        token::set_authority(
+            ctx.accounts.create_set_authority_cpi(&[&seeds[..]]),
+            AuthorityType::MintTokens,
+            None,
+        )?;
+
+        if pole_pool.serum_config.port_open_orders != Pubkey::default() {
+            dex::close_open_orders(ctx.accounts.create_close_open_orders_cpi(
+                ctx.accounts.port_open_orders.clone(),
+                ctx.accounts.dex_market.clone(),
+                &[&seeds[..]],
+            ))?;
+        }
+        //the optional parts each take their remaining accounts in this order
+        let owner = ctx.accounts.owner.to_account_info();
+        let mut remaining = ctx.remaining_accounts;
+        if !ctx.accounts.sell_route.data_is_empty() {
+            let sell_route: Account<SellRoute> = Account::try_from(&ctx.accounts.sell_route)?;
+            let (route, rest) = split_accounts(remaining, 4, PoleError::InvalidSellRoute)?;
+            if route[0].key() != sell_route.open_orders
+                || route[1].key() != sell_route.market
+                || route[2].key() != sell_route.wallet
+            {
+                return Err(PoleError::InvalidSellRoute.into());
+            }
+            dex::close_open_orders(ctx.accounts.create_close_open_orders_cpi(
+                route[0].clone(),
+                route[1].clone(),
+                &[&seeds[..]],
+            ))?;
+            ctx.accounts
+                .empty_and_close(&route[2], Some(&route[3]), &[&seeds[..]])?;
+            close_program_account(&ctx.accounts.sell_route, &owner)?;
+            remaining = rest;
+        }
+        if !ctx.accounts.sub_reward.data_is_empty() {
+            let sub_reward: Account<SubReward> = Account::try_from(&ctx.accounts.sub_reward)?;
+            let (accounts, rest) = split_accounts(remaining, 4, PoleError::InvalidSubReward)?;
+            if accounts[0].key() != sub_reward.open_orders || accounts[2].key() != sub_reward.supply
+            {
+                return Err(PoleError::InvalidSubReward.into());
+            }
+            dex::close_open_orders(ctx.accounts.create_close_open_orders_cpi(
+                accounts[0].clone(),
+                accounts[1].clone(),
+                &[&seeds[..]],
+            ))?;
+            ctx.accounts
+                .empty_and_close(&accounts[2], Some(&accounts[3]), &[&seeds[..]])?;
+            close_program_account(&ctx.accounts.sub_reward, &owner)?;
+            remaining = rest;
+        }
+        if !ctx.accounts.fee_split.data_is_empty() {
+            //the vault is the fee_receiver that took the liquidity dust above
+            let fee_split: Account<FeeSplit> = Account::try_from(&ctx.accounts.fee_split)?;
+            if fee_split.fee_vault != ctx.accounts.fee_receiver.key() {
+                return Err(PoleError::InvalidFeeSplit.into());
+            }
+            let (recipients, rest) = split_accounts(
+                remaining,
+                fee_split.recipient_count as usize,
+                PoleError::InvalidFeeSplit,
+            )?;
+            distribute_fee_vault(
+                &fee_split,
+                &ctx.accounts.fee_receiver,
+                recipients,
+                &ctx.accounts.pole_authority,
+                &ctx.accounts.token_program,
+                &seeds,
+            )?;
+            ctx.accounts
+                .empty_and_close(&ctx.accounts.fee_receiver, None, &[&seeds[..]])?;
+            close_program_account(&ctx.accounts.fee_split, &owner)?;
+            remaining = rest;
+        }
+        if pole_pool.withdraw_queue.is_initialized() {
+            let (queue, _) = split_accounts(remaining, 2, PoleError::WithdrawQueueNotInitialized)?;
+            if queue[0].key() != pole_pool.withdraw_queue.lp_escrow
+                || queue[1].key() != pole_pool.withdraw_queue.settled_liquidity
+            {
+                return Err(PoleError::WithdrawQueueNotInitialized.into());
+            }
+            for account in queue {
+                ctx.accounts.empty_and_close(account, None, &[&seeds[..]])?;
+            }
+        }
+
+        let registry_page = &mut ctx.accounts.registry_page.load_mut()?;
+        if registry_page.position(&pole_pool_key).is_some() {
+            registry_page.set_status(&pole_pool_key, POOL_DEPRECATED)?;
+        }
+        Ok(())
+    }
+
+    //moves tokens sent to the pool authority by mistake, never the pool's own accounts
+    #[access_control(valid_pole_pool(&ctx))]
+    pub fn sweep(ctx: Context<Sweep>) -> ProgramResult {
+        let pole_pool = &ctx.accounts.pole_pool.load()?;
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [pole_pool.generic_config.bump as u8];
+        let seeds = pole_pool.authority_seeds(&pole_pool_key, &bump);
+        let source = ctx.accounts.source.key();
+        if !ctx.accounts.sell_route.data_is_empty() {
+            let sell_route: Account<SellRoute> = Account::try_from(&ctx.accounts.sell_route)?;
+            if source == sell_route.wallet {
+                return Err(PoleError::InvalidSweepAccount.into());
+            }
+        }
+        if !ctx.accounts.sub_reward.data_is_empty() {
+            let sub_reward: Account<SubReward> = Account::try_from(&ctx.accounts.sub_reward)?;
+            if source == sub_reward.supply {
+                return Err(PoleError::InvalidSweepAccount.into());
+            }
+        }
+        let sweep_amount = amount(&ctx.accounts.source)?;
+        transfer(
+            ctx.accounts.create_transfer_cpi(&[&seeds[..]]),
+            sweep_amount,
+        )
+    }
+
+    //one leverage leg per instruction like deposit_liquidity, the first leg checks the rest of the
+    //transaction carries the other legs in order
+    #[access_control(valid_pole_pool(&ctx))]
+    pub fn auto_compound(ctx: Context<AutoCompound>, leverage: u8) -> ProgramResult {
+        let lending_leveraging_params = PortLendingLeveragingParams {
+            user_liquidity: &ctx.accounts.liquidity_supply,
+            obligation_owner: &ctx.accounts.pole_authority,
+            transfer_authority: &ctx.accounts.pole_authority,
+            clock: &ctx.accounts.clock.to_account_info(),
+            token_program: &ctx.accounts.token_program,
+        };
+        //only the first leg reads the obligation, later legs refresh it before borrowing
+        refresh_port_if_stale(
+            &ctx.accounts.port_accounts,
+            &lending_leveraging_params,
+            ctx.remaining_accounts,
+            leverage == 0,
+        )?;
+        let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [pole_pool.generic_config.bump as u8];
+        let seeds = pole_pool.authority_seeds(&pole_pool_key, &bump);
+        if leverage == 0 {
+            let compound_amount = amount(&ctx.accounts.liquidity_supply)?;
+            if compound_amount < pole_pool.port_config.min_deposit {
+                return Err(PoleError::PortDepositAmountTooSmall.into());
+            }
+            verify_compound_legs(
+                &ctx.accounts.transaction_info,
+                pole_pool.port_config.port_iterate,
+            )?;
+            //valid_pole_pool expects it while the legs are in flight, as for a deposit
+            let port_exchange_rate = exchange_rate(&ctx.accounts.port_accounts.reserve)?;
+            let port_liquidity =
+                get_port_liquidity(&port_exchange_rate, &ctx.accounts.port_accounts.obligation)?;
+            pole_pool.port_state.init_port_liquidity = port_liquidity.0 .0;
+            emit!(DidCompound {
+                liquidity_amount_compounded: compound_amount,
+                slot: ctx.accounts.clock.slot
+            });
+        }
+
+        deposit_leverage_step(
+            pole_pool,
+            &ctx.accounts.port_accounts,
+            &ctx.accounts.liquidity_supply,
+            &ctx.accounts.pole_authority,
+            &ctx.accounts.token_program,
+            &ctx.accounts.clock,
+            &seeds,
+        )?;
+        if pole_pool.port_state.leverage == pole_pool.port_config.port_iterate {
+            pole_pool.port_state.reset();
+        }
+        Ok(())
+    }
+
+    //assume reserve and obligation are refreshed
+    #[access_control(valid_pole_pool(&ctx))]
+    pub fn unwind_obligation(
+        ctx: Context<UnwindObligation>,
+        repay_amount: u64,
+        withdraw_amount: u64,
+    ) -> ProgramResult {
+        let pole_pool = ctx.accounts.pole_pool.load()?;
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [pole_pool.generic_config.bump as u8];
+        let seeds = pole_pool.authority_seeds(&pole_pool_key, &bump);
+
+        port_lending_unwind(
+            &ctx.accounts.port_accounts,
+            &PortLendingLeveragingParams {
+                user_liquidity: &ctx.accounts.liquidity_supply,
+                obligation_owner: &ctx.accounts.pole_authority,
+                transfer_authority: &ctx.accounts.pole_authority,
+                clock: &ctx.accounts.clock.to_account_info(),
+                token_program: &ctx.accounts.token_program,
+            },
+            repay_amount,
+            withdraw_amount,
+            &seeds,
+        )
+    }
+
+    pub fn init_withdraw_queue(ctx: Context<InitWithdrawQueue>) -> ProgramResult {
+        let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        pole_pool.withdraw_queue = WithdrawQueue {
+            lp_escrow: ctx.accounts.lp_escrow.key(),
+            settled_liquidity: ctx.accounts.settled_liquidity.key(),
+            pending_lp: 0,
+            settled_liquidity_amount: 0,
+        };
+        Ok(())
+    }
+
+    #[access_control(valid_outdated_pole_pool(&ctx))]
+    pub fn request_withdraw(
+        ctx: Context<RequestWithdraw>,
+        _bump: u8,
+        amount: u64,
+    ) -> ProgramResult {
+        let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        let lp_balance = token::accessor::amount(&ctx.accounts.user_lp_wallet)?;
+        transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.clone(),
+                token::Transfer {
+                    from: ctx.accounts.user_lp_wallet.clone(),
+                    to: ctx.accounts.lp_escrow.clone(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+        pole_pool.withdraw_queue.pending_lp = pole_pool
+            .withdraw_queue
+            .pending_lp
+            .checked_add(amount)
+            .ok_or(PoleError::MathOverflow)?;
+
+        let withdraw_request = &mut ctx.accounts.withdraw_request;
+        withdraw_request.pool = ctx.accounts.pole_pool.key();
+        withdraw_request.user = ctx.accounts.user.key();
+        withdraw_request.lp_amount = amount;
+        withdraw_request.status = WITHDRAW_PENDING;
+        withdraw_request.requested_slot = ctx.accounts.clock.slot;
+
+        match split_user_position(
+            &ctx.accounts.pole_pool.key(),
+            ctx.accounts.user.key,
+            ctx.remaining_accounts,
+        )? {
+            (Some(mut user_position), _) => {
+                user_position.cap_lp(lp_balance);
+                withdraw_request.fee_bips = pole_pool.scheduled_fee_bips(
+                    amount,
+                    user_position.lp_amount,
+                    user_position.held_slots(ctx.accounts.clock.slot),
+                )?;
+                withdraw_request.principal = user_position.redeem(amount);
+                withdraw_request.referrer = user_position.referrer;
+                user_position.exit(ctx.program_id)
+            }
+            (None, _) => {
+                withdraw_request.fee_bips = pole_pool.scheduled_fee_bips(amount, 0, 0)?;
+                Ok(())
+            }
+        }
+    }
+
+    //assume reserve and obligation are refreshed
+    #[access_control(valid_outdated_pole_pool(&ctx))]
+    pub fn unwind_for_withdrawals(
+        ctx: Context<UnwindForWithdrawals>,
+        repay_amount: u64,
+        withdraw_amount: u64,
+    ) -> ProgramResult {
+        let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        let port_exchange_rate = exchange_rate(&ctx.accounts.port_accounts.reserve)?;
+        let port_liquidity =
+            get_port_liquidity(&port_exchange_rate, &ctx.accounts.port_accounts.obligation)?;
+        let available_liquidity = amount(&ctx.accounts.liquidity_supply)?;
+        let owed = pole_pool
+            .basic_state
+            .exchange_rate(port_liquidity.try_add(available_liquidity.into())?)?
+            .lp_to_liquidity(pole_pool.withdraw_queue.pending_lp)?;
+        if available_liquidity >= owed {
+            return Err(PoleError::WithdrawalsAlreadyFunded.into());
+        }
+        //never frees more than the pending requests are short of, what is repaid has to be
+        //withdrawn back on top; one more collateral covers the rounding down
+        let shortfall = owed - available_liquidity;
+        let repay_amount = repay_amount.min(shortfall);
+        let max_withdraw_amount = port_exchange_rate
+            .liquidity_to_collateral(
+                shortfall
+                    .checked_add(repay_amount)
+                    .ok_or(PoleError::MathOverflow)?,
+            )?
+            .checked_add(1)
+            .ok_or(PoleError::MathOverflow)?;
+        let withdraw_amount = withdraw_amount.min(max_withdraw_amount);
+
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [pole_pool.generic_config.bump as u8];
+        let seeds = pole_pool.authority_seeds(&pole_pool_key, &bump);
+        port_lending_unwind(
+            &ctx.accounts.port_accounts,
+            &PortLendingLeveragingParams {
+                user_liquidity: &ctx.accounts.liquidity_supply,
+                obligation_owner: &ctx.accounts.pole_authority,
+                transfer_authority: &ctx.accounts.pole_authority,
+                clock: &ctx.accounts.clock.to_account_info(),
+                token_program: &ctx.accounts.token_program,
+            },
+            repay_amount,
+            withdraw_amount,
+            &seeds,
+        )?;
+        pole_pool.reserved_liquidity = amount(&ctx.accounts.liquidity_supply)?.min(owed);
+        Ok(())
+    }
+
+    //assume reserve and obligation are refreshed
+    #[access_control(valid_outdated_pole_pool(&ctx))]
+    pub fn process_withdraw_request(ctx: Context<ProcessWithdrawRequest>) -> ProgramResult {
+        let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [pole_pool.generic_config.bump as u8];
+        let seeds = pole_pool.authority_seeds(&pole_pool_key, &bump);
+        let lp_amount = ctx.accounts.withdraw_request.lp_amount;
+
+        let port_exchange_rate = exchange_rate(&ctx.accounts.reserve)?;
+        let port_liquidity = get_port_liquidity(&port_exchange_rate, &ctx.accounts.obligation)?;
+        let available_liquidity = amount(&ctx.accounts.liquidity_supply)?;
+        let liquidity_amount = pole_pool
+            .basic_state
+            .exchange_rate(port_liquidity.try_add(available_liquidity.into())?)?
+            .lp_to_liquidity(lp_amount)?;
+        if available_liquidity < liquidity_amount {
+            return Err(PoleError::WithdrawalNotFunded.into());
+        }
+        let fee = Decimal::from_bips(ctx.accounts.withdraw_request.fee_bips)
+            .try_mul(liquidity_amount)?
+            .try_ceil_u64()?;
+        let liquidity_exclude_fee = liquidity_amount
+            .checked_sub(fee)
+            .ok_or(PoleError::MathOverflow)?;
+
+        burn(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.clone(),
+                token::Burn {
+                    mint: ctx.accounts.lp_mint.clone(),
+                    to: ctx.accounts.lp_escrow.clone(),
+                    authority: ctx.accounts.pole_authority.clone(),
+                },
+                &[&seeds[..]],
+            ),
+            lp_amount,
+        )?;
+        pole_pool.basic_state.lp_amount = pole_pool
+            .basic_state
+            .lp_amount
+            .checked_sub(lp_amount)
+            .ok_or(PoleError::MathOverflow)?;
+        pay_withdraw_fee(
+            pole_pool,
+            &ctx.accounts.liquidity_supply,
+            &ctx.accounts.pole_fee_account,
+            active_referrer(
+                &ctx.accounts.withdraw_request.referrer,
+                ctx.remaining_accounts,
+                &ctx.accounts.token_program,
+            )?,
+            &ctx.accounts.pole_authority,
+            &ctx.accounts.token_program,
+            fee,
+            &seeds,
+        )?;
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.clone(),
+                token::Transfer {
+                    from: ctx.accounts.liquidity_supply.clone(),
+                    to: ctx.accounts.settled_liquidity.clone(),
+                    authority: ctx.accounts.pole_authority.clone(),
+                },
+                &[&seeds[..]],
+            ),
+            liquidity_exclude_fee,
+        )?;
+        pole_pool.withdraw_queue.pending_lp = pole_pool
+            .withdraw_queue
+            .pending_lp
+            .checked_sub(lp_amount)
+            .ok_or(PoleError::MathOverflow)?;
+        pole_pool.withdraw_queue.settled_liquidity_amount = pole_pool
+            .withdraw_queue
+            .settled_liquidity_amount
+            .checked_add(liquidity_exclude_fee)
+            .ok_or(PoleError::MathOverflow)?;
+        pole_pool.reserved_liquidity = if pole_pool.withdraw_queue.pending_lp == 0 {
+            0
+        } else {
+            pole_pool
+                .reserved_liquidity
+                .saturating_sub(liquidity_amount)
+        };
+
+        let withdraw_request = &mut ctx.accounts.withdraw_request;
+        withdraw_request.liquidity_amount = liquidity_exclude_fee;
+        withdraw_request.status = WITHDRAW_SETTLED;
+        withdraw_request.settled_slot = ctx.accounts.clock.slot;
+        emit!(DidRedeem {
+            liquidity_amount_redeemed: liquidity_amount,
+            lp_amount_burned: lp_amount,
+        });
+        Ok(())
+    }
+
+    pub fn claim_withdraw(ctx: Context<ClaimWithdraw>) -> ProgramResult {
+        let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [pole_pool.generic_config.bump as u8];
+        let seeds = pole_pool.authority_seeds(&pole_pool_key, &bump);
+        let liquidity_amount = ctx.accounts.withdraw_request.liquidity_amount;
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.clone(),
+                token::Transfer {
+                    from: ctx.accounts.settled_liquidity.clone(),
+                    to: ctx.accounts.user_liquidity_wallet.clone(),
+                    authority: ctx.accounts.pole_authority.clone(),
+                },
+                &[&seeds[..]],
+            ),
+            liquidity_amount,
+        )?;
+        pole_pool.withdraw_queue.settled_liquidity_amount = pole_pool
+            .withdraw_queue
+            .settled_liquidity_amount
+            .checked_sub(liquidity_amount)
+            .ok_or(PoleError::MathOverflow)?;
+        Ok(())
+    }
+
+    pub fn cancel_withdraw_request(ctx: Context<CancelWithdrawRequest>) -> ProgramResult {
+        let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [pole_pool.generic_config.bump as u8];
+        let seeds = pole_pool.authority_seeds(&pole_pool_key, &bump);
+        let lp_amount = ctx.accounts.withdraw_request.lp_amount;
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.clone(),
+                token::Transfer {
+                    from: ctx.accounts.lp_escrow.clone(),
+                    to: ctx.accounts.user_lp_wallet.clone(),
+                    authority: ctx.accounts.pole_authority.clone(),
+                },
+                &[&seeds[..]],
+            ),
+            lp_amount,
+        )?;
+        pole_pool.withdraw_queue.pending_lp = pole_pool
+            .withdraw_queue
+            .pending_lp
+            .checked_sub(lp_amount)
+            .ok_or(PoleError::MathOverflow)?;
+        //what was freed for the requests left waits for them, the rest is deposited again
+        if pole_pool.withdraw_queue.pending_lp == 0 {
+            pole_pool.reserved_liquidity = 0;
+        }
+        //the holding period of the returned LP restarts at the request
+        let requested_slot = ctx.accounts.withdraw_request.requested_slot;
+        let principal = ctx.accounts.withdraw_request.principal;
+        match split_user_position(
+            &ctx.accounts.pole_pool.key(),
+            ctx.accounts.user.key,
+            ctx.remaining_accounts,
+        )? {
+            (Some(mut user_position), _) => {
+                user_position.principal = user_position
+                    .principal
+                    .checked_add(principal)
+                    .ok_or(PoleError::MathOverflow)?;
+                user_position.deposit(lp_amount, requested_slot)?;
+                user_position.exit(ctx.program_id)
+            }
+            //the principal released by the request goes back to the position it came from
+            (None, _) if principal != 0 => Err(PoleError::InvalidUserPosition.into()),
+            (None, _) => Ok(()),
+        }
+    }
+
+    pub fn migrate_authority(ctx: Context<MigrateAuthority>, authority_bump: u8) -> ProgramResult {
+        let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        let legacy_bump = [pole_pool.generic_config.bump as u8];
+        let legacy_seeds: &[&[u8]] = &[&legacy_bump];
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [authority_bump];
+        let seeds: &[&[u8]] = &[pole_pool_key.as_ref(), &bump];
+
+        if pole_pool.port_config.stake_account != Pubkey::default() {
+            let signer = [legacy_seeds];
+            let mut claim = ctx.accounts.port_accounts.create_claim_reward_context(
+                ctx.accounts.legacy_authority.clone(),
+                ctx.accounts.port_supply.clone(),
+                ctx.accounts.clock.to_account_info(),
+                ctx.accounts.token_program.clone(),
+                &signer,
+            );
+            if !ctx.remaining_accounts.is_empty() {
+                check_sub_reward(&pole_pool_key, ctx.remaining_accounts)?;
+                claim
+                    .remaining_accounts
+                    .extend(ctx.remaining_accounts[1..].iter().cloned());
+            }
+            //unlike claim_and_sell a failed claim aborts, the stake account is dropped below
+            claim_reward(claim)?;
+        }
+
+        for account in [
+            &ctx.accounts.liquidity_supply,
+            &ctx.accounts.port_lp_supply,
+            &ctx.accounts.port_supply,
+        ] {
+            token::set_authority(
+                ctx.accounts
+                    .create_set_authority_cpi(account.clone(), &[legacy_seeds]),
+                AuthorityType::AccountOwner,
+                Some(ctx.accounts.pole_authority.key()),
+            )?;
+        }
+        token::set_authority(
+            ctx.accounts
+                .create_set_authority_cpi(ctx.accounts.lp_mint.clone(), &[legacy_seeds]),
+            AuthorityType::MintTokens,
+            Some(ctx.accounts.pole_authority.key()),
+        )?;
+
+        dex::close_open_orders(ctx.accounts.create_close_open_orders_cpi(&[legacy_seeds]))?;
+        dex::init_open_orders(ctx.accounts.create_init_open_orders_cpi(&[seeds]))?;
+
+        pole_pool.generic_config.bump = authority_bump as u64;
+        pole_pool.authority_version = POOL_AUTHORITY;
+        pole_pool.serum_config.port_open_orders = ctx.accounts.port_open_orders.key();
+        //the legacy obligation and stake account can't change owner, the stake account's
+        //rewards were claimed above
+        pole_pool.port_config.obligation = Pubkey::default();
+        pole_pool.port_config.stake_account = Pubkey::default();
+        Ok(())
+    }
+
+    /// Upgrades a pool written by an older program to CURRENT_LAYOUT_VERSION in place.
+    /// A pool smaller than the current layout goes through begin_pool_realloc and
+    /// finish_pool_realloc first.
+    pub fn migrate_pool(ctx: Context<MigratePool>) -> ProgramResult {
+        let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        if pole_pool.layout_version == 0 {
+            //harvest_config, authority_version and min_amount_out were carved out of
+            //zeroed padding, zero already reads as their legacy behaviour
+            pole_pool.layout_version = 1;
+        }
+        if pole_pool.layout_version == 1 {
+            //withdraw_queue was carved out of zeroed padding, the queue stays disabled
+            //until init_withdraw_queue
+            pole_pool.layout_version = 2;
+        }
+        if pole_pool.layout_version == 2 {
+            //fee_schedule and redeem_fee_bips were carved out of zeroed padding,
+            //an unset schedule keeps the flat withdraw_fee_bips
+            pole_pool.layout_version = 3;
+        }
+        if pole_pool.layout_version == 3 {
+            //allowlist_enabled was carved out of zeroed padding, pools stay open
+            pole_pool.layout_version = 4;
+        }
+        if pole_pool.layout_version == 4 {
+            //wallet_cap was carved out of zeroed padding, wallets stay uncapped
+            pole_pool.layout_version = 5;
+        }
+        if pole_pool.layout_version == 5 {
+            //referral_fee_bips was carved out of zeroed padding, referrers get nothing until set
+            pole_pool.layout_version = 6;
+        }
+        if pole_pool.layout_version == 6 {
+            //redeem_lp_amount was carved out of zeroed padding, a redeem in flight across the
+            //upgrade reports no lp burned as it did before
+            pole_pool.layout_version = 7;
+        }
+        if pole_pool.layout_version == 7 {
+            //reserved_liquidity was carved out of zeroed padding, liquidity an older program
+            //freed for the queue goes back to Port with the next deposit, as it did before
+            pole_pool.layout_version = 8;
+        }
+        Ok(())
+    }
+
+    /// solana-program 1.8 has no AccountInfo::realloc: moves the data of a pool smaller than
+    /// the current layout into a PoolRealloc PDA and closes the pool, the runtime deletes it
+    /// at the end of the transaction. The pool can't be used until finish_pool_realloc.
+    pub fn begin_pool_realloc(ctx: Context<BeginPoolRealloc>, _bump: u8) -> ProgramResult {
+        let pole_pool = &ctx.accounts.pole_pool;
+        let pool_realloc = &mut ctx.accounts.pool_realloc;
+        pool_realloc.pool = pole_pool.key();
+        pool_realloc.owner = ctx.accounts.owner.key();
+        pool_realloc.data = pole_pool.try_borrow_data()?[DISCRIMINATOR_SIZE..].to_vec();
+
+        let owner = ctx.accounts.owner.to_account_info();
+        **owner.lamports.borrow_mut() = owner
+            .lamports()
+            .checked_add(pole_pool.lamports())
+            .ok_or(PoleError::MathOverflow)?;
+        **pole_pool.lamports.borrow_mut() = 0;
+        //nothing later in the transaction may load the closed pool
+        pole_pool.try_borrow_mut_data()?.fill(0);
+        Ok(())
+    }
+
+    /// Recreates the pool closed by begin_pool_realloc at the same address with the current
+    /// size, in a later transaction. The pool keeps its layout_version, run migrate_pool next.
+    pub fn finish_pool_realloc(
+        ctx: Context<FinishPoolRealloc>,
+        _name: String,
+        _pda_bump: u8,
+    ) -> ProgramResult {
+        let pole_pool = &mut ctx.accounts.pole_pool.load_init()?;
+        let data = &ctx.accounts.pool_realloc.data;
+        //the layout only ever grew at the end, the new tail stays zeroed
+        anchor_lang::__private::bytemuck::bytes_of_mut(&mut **pole_pool)[..data.len()]
+            .copy_from_slice(data);
+        Ok(())
+    }
+
     #[access_control(valid_pole_pool(&ctx))]
     pub fn change_owner(ctx: Context<ChangeOwner>, owner: Pubkey) -> ProgramResult {
         let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
@@ -540,6 +1670,69 @@ pub mod pole {
         Ok(())
     }
 
+    pub fn init_fee_split(ctx: Context<InitFeeSplit>, _bump: u8) -> ProgramResult {
+        let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        let fee_split = &mut ctx.accounts.fee_split;
+        fee_split.pool = ctx.accounts.pole_pool.key();
+        fee_split.fee_vault = ctx.accounts.fee_vault.key();
+        fee_split.recipient_count = 1;
+        fee_split.recipients[0] = pole_pool.generic_config.fee_receiver;
+        fee_split.bips[0] = 10000;
+        pole_pool.generic_config.fee_receiver = ctx.accounts.fee_vault.key();
+        Ok(())
+    }
+
+    pub fn change_fee_split(
+        ctx: Context<ChangeFeeSplit>,
+        recipients: Vec<FeeRecipient>,
+    ) -> ProgramResult {
+        let fee_split = &mut ctx.accounts.fee_split;
+        fee_split.recipient_count = recipients.len() as u64;
+        fee_split.recipients = [Pubkey::default(); MAX_FEE_RECIPIENTS];
+        fee_split.bips = [0; MAX_FEE_RECIPIENTS];
+        for (i, recipient) in recipients.iter().enumerate() {
+            fee_split.recipients[i] = recipient.recipient;
+            fee_split.bips[i] = recipient.bips;
+        }
+        if !fee_split.validate() {
+            return Err(PoleError::InvalidFeeSplit.into());
+        }
+        Ok(())
+    }
+
+    /// Splits everything in the fee vault by the fee_split bips, the last recipient takes the rounding.
+    pub fn distribute_fees(ctx: Context<DistributeFees>) -> ProgramResult {
+        let pole_pool = ctx.accounts.pole_pool.load()?;
+        let pole_pool_key = ctx.accounts.pole_pool.key();
+        let bump = [pole_pool.generic_config.bump as u8];
+        let seeds = pole_pool.authority_seeds(&pole_pool_key, &bump);
+        distribute_fee_vault(
+            &ctx.accounts.fee_split,
+            &ctx.accounts.fee_vault,
+            ctx.remaining_accounts,
+            &ctx.accounts.pole_authority,
+            &ctx.accounts.token_program,
+            &seeds,
+        )
+    }
+
+    /// Share of the withdraw fee, in bips of the fee, paid to the referrer of the redeeming position.
+    #[access_control(valid_pole_pool(&ctx))]
+    pub fn change_referral_fee(ctx: Context<ChangeReferralFee>, bips: u64) -> ProgramResult {
+        let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        pole_pool.referral_fee_bips = bips;
+        Ok(())
+    }
+
+    /// Caps the principal each user position may deposit, 0 removes the cap.
+    /// Positions already above it can only redeem until they are back under it.
+    #[access_control(valid_pole_pool(&ctx))]
+    pub fn change_wallet_cap(ctx: Context<ChangeWalletCap>, cap: u64) -> ProgramResult {
+        let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        pole_pool.wallet_cap = cap;
+        Ok(())
+    }
+
     #[access_control(valid_pole_pool(&ctx))]
     pub fn change_withdraw_fee(ctx: Context<ChangeWithdrawFee>, bips: u8) -> ProgramResult {
         let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
@@ -547,6 +1740,73 @@ pub mod pole {
         Ok(())
     }
 
+    #[access_control(valid_pole_pool(&ctx))]
+    pub fn change_harvest_config(
+        ctx: Context<ChangeHarvestConfig>,
+        stale_slot: u64,
+        min_harvest_interval: u64,
+        harvest_bounty_bips: u64,
+    ) -> ProgramResult {
+        let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        pole_pool.harvest_config = HarvestConfig {
+            stale_slot,
+            min_harvest_interval,
+            harvest_bounty_bips,
+        };
+        Ok(())
+    }
+
+    #[access_control(valid_pole_pool(&ctx))]
+    pub fn change_allowlist_mode(
+        ctx: Context<ChangeAllowlistMode>,
+        enabled: bool,
+    ) -> ProgramResult {
+        let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        pole_pool.allowlist_enabled = enabled as u64;
+        Ok(())
+    }
+
+    pub fn add_allowlist_entry(
+        ctx: Context<AddAllowlistEntry>,
+        _bump: u8,
+        wallet: Pubkey,
+    ) -> ProgramResult {
+        let allowlist_entry = &mut ctx.accounts.allowlist_entry;
+        allowlist_entry.pool = ctx.accounts.pole_pool.key();
+        allowlist_entry.wallet = wallet;
+        Ok(())
+    }
+
+    //closing the entry is all there is to it, existing holders keep redeeming
+    pub fn remove_allowlist_entry(_ctx: Context<RemoveAllowlistEntry>) -> ProgramResult {
+        Ok(())
+    }
+
+    /// Replaces the holding period fee schedule, an empty `tiers` restores the flat withdraw fee.
+    #[access_control(valid_pole_pool(&ctx))]
+    pub fn change_fee_schedule(
+        ctx: Context<ChangeFeeSchedule>,
+        tiers: Vec<FeeTier>,
+    ) -> ProgramResult {
+        let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
+        let mut held_slots = [0; MAX_FEE_TIERS];
+        let mut bips = [0; MAX_FEE_TIERS];
+        for (i, tier) in tiers.iter().enumerate() {
+            held_slots[i] = tier.held_slots;
+            bips[i] = tier.bips;
+        }
+        let fee_schedule = FeeSchedule {
+            held_slots,
+            bips,
+            tier_count: tiers.len() as u64,
+        };
+        if !fee_schedule.validate() {
+            return Err(PoleError::InvalidPoolConfig.into());
+        }
+        pole_pool.fee_schedule = fee_schedule;
+        Ok(())
+    }
+
     #[access_control(valid_pole_pool(&ctx))]
     pub fn change_min_deposit(ctx: Context<ChangeMinDeposit>, min_deposit: u64) -> ProgramResult {
         let pole_pool = &mut ctx.accounts.pole_pool.load_mut()?;
@@ -560,6 +1820,28 @@ pub mod pole {
         Ok(())
     }
 
+    pub fn create_user_position(ctx: Context<CreateUserPosition>, _bump: u8) -> ProgramResult {
+        let user_position = &mut ctx.accounts.user_position;
+        user_position.pool = ctx.accounts.pole_pool.key();
+        user_position.user = token::accessor::authority(&ctx.accounts.user_liquidity_wallet)?;
+        Ok(())
+    }
+
+    /// Attributes the position to a referrer, prepended to the deposit that brings the user in.
+    /// The first referrer sticks, passing it again is a no-op.
+    pub fn set_referrer(ctx: Context<SetReferrer>) -> ProgramResult {
+        let user_position = &mut ctx.accounts.user_position;
+        if user_position.referrer == Pubkey::default() {
+            user_position.referrer = ctx.accounts.referrer_fee_account.key();
+            emit!(DidRefer {
+                pool: user_position.pool,
+                user: user_position.user,
+                referrer: user_position.referrer,
+            });
+        }
+        Ok(())
+    }
+
     pub fn add_balance(ctx: Context<AddBalance>, amount: u64) -> ProgramResult {
         let user_balance = &mut ctx.accounts.user_balance;
         user_balance.balance = user_balance
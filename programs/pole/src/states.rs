@@ -1,9 +1,50 @@
 use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
 
+use crate::error::PoleError;
 use crate::StakingBumps;
 use solana_maths::{Decimal, Rate, TryDiv, TryMul};
 
 pub(crate) const DISCRIMINATOR_SIZE: usize = 8;
+pub(crate) const DEFAULT_STALE_SLOT: u64 = 50;
+pub(crate) const MAX_HARVEST_BOUNTY_BIPS: u64 = 1000;
+pub(crate) const MAX_SCHEDULE_FEE_BIPS: u64 = 1000;
+pub(crate) const MAX_REFERRAL_FEE_BIPS: u64 = 10000;
+// pole_authority derived with seeds = [], shared by every pool created before per-pool authorities
+pub const LEGACY_AUTHORITY: u64 = 0;
+// pole_authority derived with seeds = [pole_pool]
+pub const POOL_AUTHORITY: u64 = 1;
+// 0 is every pool written before layout_version existed, bump on any layout change
+pub const CURRENT_LAYOUT_VERSION: u64 = 8;
+// size of the zero-copy pool data, without the discriminator
+pub const POLE_PORT_POOL_SIZE: usize = 936;
+
+// the zero-copy layout is what is on chain, new fields have to come out of _padding
+const _: [(); 184] = [(); std::mem::size_of::<GenericPoolConfig>()];
+const _: [(); 280] = [(); std::mem::size_of::<PortConfig>()];
+const _: [(); 96] = [(); std::mem::size_of::<SerumConfig>()];
+const _: [(); 8] = [(); std::mem::size_of::<BasicState>()];
+const _: [(); 136] = [(); std::mem::size_of::<PortState>()];
+const _: [(); 24] = [(); std::mem::size_of::<HarvestConfig>()];
+const _: [(); 80] = [(); std::mem::size_of::<WithdrawQueue>()];
+const _: [(); 48] = [(); std::mem::size_of::<FeeSchedule>()];
+const _: [(); POLE_PORT_POOL_SIZE] = [(); std::mem::size_of::<PolePortPool>()];
+const _: [(); 112] = [(); std::mem::size_of::<RegistryEntry>()];
+pub const REGISTRY_PAGE_SIZE: usize = 64;
+pub const REGISTRY_SEED: &[u8] = b"registry";
+pub const POOL_ACTIVE: u64 = 1;
+pub const POOL_DEPRECATED: u64 = 2;
+pub const WITHDRAW_REQUEST_SEED: &[u8] = b"withdraw";
+pub const WITHDRAW_PENDING: u64 = 1;
+pub const WITHDRAW_SETTLED: u64 = 2;
+pub const POSITION_SEED: &[u8] = b"position";
+pub const MAX_FEE_TIERS: usize = 4;
+pub const ALLOWLIST_SEED: &[u8] = b"allowlist";
+pub const FEE_SPLIT_SEED: &[u8] = b"fee_split";
+pub const SUB_REWARD_SEED: &[u8] = b"sub_reward";
+pub const SELL_ROUTE_SEED: &[u8] = b"sell_route";
+pub const MAX_FEE_RECIPIENTS: usize = 4;
+pub const REALLOC_SEED: &[u8] = b"realloc";
 // 64*4;
 #[account(zero_copy)]
 #[derive(Debug, PartialEq)]
@@ -13,7 +54,174 @@ pub struct PolePortPool {
     pub serum_config: SerumConfig,
     pub basic_state: BasicState,
     pub port_state: PortState,
-    pub _padding: [u64; 30],
+    pub harvest_config: HarvestConfig,
+    pub authority_version: u64,
+    pub layout_version: u64,
+    pub withdraw_queue: WithdrawQueue,
+    pub fee_schedule: FeeSchedule,
+    pub redeem_fee_bips: u64, // fee of the redeem in flight, set by its first leg
+    pub allowlist_enabled: u64, // boolean, deposits need an AllowlistEntry of the wallet owner
+    pub wallet_cap: u64,      // principal a single UserPosition may deposit, 0 means uncapped
+    pub referral_fee_bips: u64, // share of the withdraw fee paid to the position's referrer
+    pub redeem_lp_amount: u64, // lp burned by the redeem in flight, set by its first leg
+    pub reserved_liquidity: u64, // of liquidity_supply, freed by unwind_for_withdrawals for the queue
+    pub _padding: [u64; 2],
+}
+
+impl PolePortPool {
+    pub fn authority_seeds<'a>(&self, pole_pool: &'a Pubkey, bump: &'a [u8]) -> Vec<&'a [u8]> {
+        if self.authority_version == POOL_AUTHORITY {
+            vec![pole_pool.as_ref(), bump]
+        } else {
+            vec![bump]
+        }
+    }
+
+    /// Withdraw fee of redeeming `lp_amount`: the part tracked by the position pays the tier
+    /// reached after `held_slots`, LP it does not track (received by transfer) pays the first tier.
+    pub fn scheduled_fee_bips(
+        &self,
+        lp_amount: u64,
+        position_lp_amount: u64,
+        held_slots: u64,
+    ) -> Result<u64, ProgramError> {
+        if !self.fee_schedule.is_set() || lp_amount == 0 {
+            return Ok(self.generic_config.withdraw_fee_bips);
+        }
+        let tracked = lp_amount.min(position_lp_amount) as u128;
+        let untracked = lp_amount as u128 - tracked;
+        let weighted = tracked
+            .checked_mul(self.fee_schedule.bips(held_slots) as u128)
+            .and_then(|fee| fee.checked_add(untracked * self.fee_schedule.bips(0) as u128))
+            .ok_or(PoleError::MathOverflow)?;
+        let lp_amount = lp_amount as u128;
+        Ok(((weighted + lp_amount - 1) / lp_amount) as u64)
+    }
+
+    pub fn authority(&self, pole_pool: &Pubkey) -> Result<Pubkey, ProgramError> {
+        let bump = [self.generic_config.bump as u8];
+        Pubkey::create_program_address(&self.authority_seeds(pole_pool, &bump), &crate::ID)
+            .map_err(|_| PoleError::InvalidAuthority.into())
+    }
+
+    /// Whether `account` is referenced by the generic or Port config, which `sweep` must not touch.
+    pub fn is_config_account(&self, account: &Pubkey) -> bool {
+        let generic = &self.generic_config;
+        let port = &self.port_config;
+        [
+            generic.fee_receiver,
+            generic.owner,
+            generic.lp_mint,
+            generic.liquidity_supply,
+            generic.token_program,
+            port.obligation,
+            port.reserve,
+            port.stake_account,
+            port.port_lending_program,
+            port.port_staking_program,
+            port.port_token_mint,
+            port.port_lp_supply,
+            port.port_supply,
+            self.withdraw_queue.lp_escrow,
+            self.withdraw_queue.settled_liquidity,
+        ]
+        .contains(account)
+    }
+
+    /// Whether the redeem in progress has freed enough liquidity to pay the user out.
+    pub fn is_redeem_unrolled(&self) -> Result<bool, ProgramError> {
+        Ok(self.port_state.leverage
+            == self
+                .port_config
+                .port_iterate
+                .checked_sub(1)
+                .ok_or(PoleError::MathOverflow)?
+            || (self.port_state.leverage == 0 && self.port_state.amount_to_unroll == 0))
+    }
+}
+
+// lists every pool so nothing has to scan program accounts, the pools are in its pages,
+// seeds = [REGISTRY_SEED, program id] are longer than any single pool name seed
+#[account(zero_copy)]
+#[derive(Debug, PartialEq)]
+pub struct PoolRegistry {
+    pub owner: Pubkey,
+    pub bump: u64,
+    pub page_count: u64,
+}
+
+// seeds = [REGISTRY_SEED, program id, index as little endian u64]
+#[account(zero_copy)]
+#[derive(Debug, PartialEq)]
+pub struct RegistryPage {
+    pub index: u64,
+    pub bump: u64,
+    pub pool_count: u64,
+    pub pools: [RegistryEntry; REGISTRY_PAGE_SIZE],
+}
+
+#[zero_copy]
+#[derive(Debug, PartialEq, Default)]
+pub struct RegistryEntry {
+    pub pool: Pubkey,
+    pub liquidity_mint: Pubkey,
+    pub reserve: Pubkey,
+    pub status: u64, // POOL_ACTIVE or POOL_DEPRECATED
+    pub created_slot: u64,
+}
+
+impl RegistryPage {
+    pub fn address(index: u64, bump: u8) -> Result<Pubkey, ProgramError> {
+        Pubkey::create_program_address(
+            &[
+                REGISTRY_SEED,
+                crate::ID.as_ref(),
+                &index.to_le_bytes(),
+                &[bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| PoleError::InvalidRegistryPage.into())
+    }
+
+    pub fn position(&self, pool: &Pubkey) -> Option<usize> {
+        let pool_count = self.pool_count as usize;
+        self.pools[..pool_count.min(REGISTRY_PAGE_SIZE)]
+            .iter()
+            .position(|entry| {
+                let entry_pool = entry.pool;
+                entry_pool == *pool
+            })
+    }
+
+    pub fn register(&mut self, entry: RegistryEntry) -> ProgramResult {
+        let pool_count = self.pool_count as usize;
+        if pool_count >= REGISTRY_PAGE_SIZE {
+            return Err(PoleError::RegistryFull.into());
+        }
+        if self.position(&entry.pool).is_some() {
+            return Err(PoleError::PoolAlreadyRegistered.into());
+        }
+        self.pools[pool_count] = entry;
+        self.pool_count += 1;
+        Ok(())
+    }
+
+    pub fn set_status(&mut self, pool: &Pubkey, status: u64) -> ProgramResult {
+        let index = self.position(pool).ok_or(PoleError::PoolNotRegistered)?;
+        self.pools[index].status = status;
+        Ok(())
+    }
+
+    //swaps the last entry into the freed slot, entries are unordered
+    pub fn unregister(&mut self, pool: &Pubkey) -> ProgramResult {
+        let index = self.position(pool).ok_or(PoleError::PoolNotRegistered)?;
+        let last = (self.pool_count - 1) as usize;
+        self.pools[index] = self.pools[last];
+        self.pools[last] = RegistryEntry::default();
+        self.pool_count -= 1;
+        Ok(())
+    }
 }
 
 #[account]
@@ -44,6 +252,18 @@ impl GenericPoolConfig {
     pub fn validate(&self) -> bool {
         self.bump <= 255 && self.withdraw_fee_bips <= 255
     }
+
+    /// Reads the generic config of pool data of any layout version, it has led the pool
+    /// since the first one. Loading a pool smaller than PolePortPool would panic instead.
+    pub fn of_pool_data(data: &[u8]) -> Result<Self, ProgramError> {
+        let end = DISCRIMINATOR_SIZE + std::mem::size_of::<GenericPoolConfig>();
+        if data.len() < end || data[..DISCRIMINATOR_SIZE] != PolePortPool::discriminator() {
+            return Err(PoleError::InvalidPoolState.into());
+        }
+        anchor_lang::__private::bytemuck::try_from_bytes(&data[DISCRIMINATOR_SIZE..end])
+            .map(|config| *config)
+            .map_err(|_| ProgramError::InvalidAccountData)
+    }
 }
 #[zero_copy]
 #[derive(Debug, PartialEq)]
@@ -65,6 +285,8 @@ impl PortConfig {
         self.min_deposit > 10 && self.port_iterate < 10 && self.port_reserve_percentage < 10
     }
 }
+// the harvest route's second hop is serum config too, it lives in the SellRoute PDA
+// since the pool has no padding to spare for it
 #[zero_copy]
 #[derive(Debug, PartialEq)]
 pub struct SerumConfig {
@@ -73,6 +295,78 @@ pub struct SerumConfig {
     pub port_open_orders: Pubkey,
 }
 
+#[zero_copy]
+#[derive(Debug, PartialEq, Default)]
+pub struct HarvestConfig {
+    pub stale_slot: u64,           // 0 means DEFAULT_STALE_SLOT
+    pub min_harvest_interval: u64, // < stale_slot
+    pub harvest_bounty_bips: u64,  // <= MAX_HARVEST_BOUNTY_BIPS
+}
+impl HarvestConfig {
+    pub fn stale_slot(&self) -> u64 {
+        if self.stale_slot == 0 {
+            DEFAULT_STALE_SLOT
+        } else {
+            self.stale_slot
+        }
+    }
+    pub fn validate(&self) -> bool {
+        self.min_harvest_interval < self.stale_slot()
+            && self.harvest_bounty_bips <= MAX_HARVEST_BOUNTY_BIPS
+    }
+}
+
+// default keys mean the queue was never set up with init_withdraw_queue
+#[zero_copy]
+#[derive(Debug, PartialEq, Default)]
+pub struct WithdrawQueue {
+    pub lp_escrow: Pubkey,             // LP locked by pending requests
+    pub settled_liquidity: Pubkey,     // liquidity of settled, unclaimed requests
+    pub pending_lp: u64,               // == lp_escrow amount
+    pub settled_liquidity_amount: u64, // == settled_liquidity amount
+}
+impl WithdrawQueue {
+    pub fn is_initialized(&self) -> bool {
+        let lp_escrow = self.lp_escrow;
+        lp_escrow != Pubkey::default()
+    }
+}
+
+// tier_count == 0 means the flat generic_config.withdraw_fee_bips
+#[zero_copy]
+#[derive(Debug, PartialEq, Default)]
+pub struct FeeSchedule {
+    pub held_slots: [u64; MAX_FEE_TIERS], // ascending, the first tier starts at 0
+    pub bips: [u16; MAX_FEE_TIERS],       // non-increasing, <= MAX_SCHEDULE_FEE_BIPS
+    pub tier_count: u64,
+}
+impl FeeSchedule {
+    pub fn is_set(&self) -> bool {
+        self.tier_count != 0
+    }
+
+    pub fn validate(&self) -> bool {
+        let tier_count = self.tier_count as usize;
+        let (held_slots, bips) = (self.held_slots, self.bips);
+        tier_count <= MAX_FEE_TIERS
+            && (tier_count == 0 || held_slots[0] == 0)
+            && (1..tier_count).all(|i| held_slots[i] > held_slots[i - 1] && bips[i] <= bips[i - 1])
+            && bips[..tier_count]
+                .iter()
+                .all(|bips| *bips as u64 <= MAX_SCHEDULE_FEE_BIPS)
+    }
+
+    // bips of the last tier reached after holding for `held_slots`
+    pub fn bips(&self, held_slots: u64) -> u64 {
+        let tier_count = (self.tier_count as usize).min(MAX_FEE_TIERS);
+        let (tier_held_slots, bips) = (self.held_slots, self.bips);
+        (0..tier_count)
+            .take_while(|i| tier_held_slots[*i] <= held_slots)
+            .last()
+            .map_or(0, |i| bips[i] as u64)
+    }
+}
+
 #[zero_copy]
 #[derive(Debug, PartialEq, Default)]
 pub struct BasicState {
@@ -92,6 +386,7 @@ pub struct PortState {
     pub amount_to_unroll: u64,
     pub last_sold_slot: u64,
     pub user_liquidity_percentage: [u64; 2], // Rate
+    pub min_amount_out: u64,
 }
 
 impl PortState {
@@ -105,9 +400,40 @@ impl PortState {
         self.redeem_amount = 0;
         self.amount_to_unroll = 0;
         self.user_liquidity_percentage = Rate::zero().0 .0;
+        self.min_amount_out = 0;
+    }
+
+    /// What `recover_port_state` does next with the deposit or redeem left half-way.
+    pub fn recover_leg(&self, available_liquidity: u64) -> Result<RecoverLeg, ProgramError> {
+        if self.leverage == 0 {
+            Ok(RecoverLeg::Reset)
+        } else if self.deposit_verified == 1 {
+            if available_liquidity != 0 {
+                Ok(RecoverLeg::DepositStep)
+            } else {
+                Ok(RecoverLeg::FinishDeposit)
+            }
+        } else if self.redeem_verified == 1 {
+            if self.is_redeemed != 1 {
+                Ok(RecoverLeg::RedeemStep)
+            } else {
+                Ok(RecoverLeg::Advance)
+            }
+        } else {
+            Err(PoleError::InvalidPoolState.into())
+        }
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub enum RecoverLeg {
+    Reset,         // nothing has moved yet, only the verify flags are stale
+    DepositStep,   // liquidity still waits in the pole wallet
+    FinishDeposit, // the borrowed liquidity is already in the obligation
+    RedeemStep,    // the redeem is still unrolling
+    Advance,       // the user is paid, the remaining legs only count up
+}
+
 impl BasicState {
     pub fn exchange_rate(&self, liquidity_amount: Decimal) -> Result<ExchangeRate, ProgramError> {
         if liquidity_amount == Decimal::zero() || self.lp_amount == 0 {
@@ -131,6 +457,181 @@ impl ExchangeRate {
         Decimal::from(lp_amount).try_div(self.0)?.try_floor_u64()
     }
 }
+// fees collect in fee_vault, the pool's fee_receiver, until distribute_fees splits them
+#[account]
+#[derive(Debug, PartialEq)]
+pub struct FeeSplit {
+    pub pool: Pubkey,
+    pub fee_vault: Pubkey,
+    pub recipient_count: u64,
+    pub recipients: [Pubkey; MAX_FEE_RECIPIENTS], // liquidity token accounts
+    pub bips: [u64; MAX_FEE_RECIPIENTS],          // sum to 10000
+    pub _padding: [u64; 4],
+}
+
+impl FeeSplit {
+    pub fn address(pole_pool: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[FEE_SPLIT_SEED, pole_pool.as_ref()], &crate::ID).0
+    }
+}
+
+// the secondary Port staking reward, claimed into supply and sold on its own market by sell_sub_reward
+#[account]
+#[derive(Debug, PartialEq)]
+pub struct SubReward {
+    pub pool: Pubkey,
+    pub mint: Pubkey,
+    pub supply: Pubkey,      // pool authority token account of mint
+    pub open_orders: Pubkey, // on the mint (coin) / liquidity mint (pc) market
+    pub last_sold_slot: u64, // sell_sub_reward waits min_harvest_interval from it, like claim_and_sell
+    pub _padding: [u64; 3],
+}
+
+impl SubReward {
+    pub fn address(pole_pool: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[SUB_REWARD_SEED, pole_pool.as_ref()], &crate::ID).0
+    }
+}
+
+// the second hop of the harvest route, claim_and_sell sells PORT for the harvest market's pc,
+// then buys the liquidity mint with it on market
+#[account]
+#[derive(Debug, PartialEq)]
+pub struct SellRoute {
+    pub pool: Pubkey,
+    pub market: Pubkey,      // liquidity mint (coin) / harvest market pc (pc)
+    pub open_orders: Pubkey, // on market
+    pub wallet: Pubkey,      // pool authority token account of the pc, holds it between the hops
+    pub _padding: [u64; 4],
+}
+
+impl SellRoute {
+    pub fn address(pole_pool: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[SELL_ROUTE_SEED, pole_pool.as_ref()], &crate::ID).0
+    }
+}
+
+impl FeeSplit {
+    pub fn validate(&self) -> bool {
+        let recipient_count = self.recipient_count as usize;
+        recipient_count > 0
+            && recipient_count <= MAX_FEE_RECIPIENTS
+            && self.bips[..recipient_count].iter().sum::<u64>() == 10000
+    }
+}
+
+// the data of a pool between begin_pool_realloc and finish_pool_realloc,
+// the pool address has no account in between
+#[account]
+#[derive(Debug, PartialEq)]
+pub struct PoolRealloc {
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    pub data: Vec<u8>, // pool data without the discriminator
+}
+
+impl PoolRealloc {
+    pub fn space(pool_data_len: usize) -> usize {
+        DISCRIMINATOR_SIZE + 32 + 32 + 4 + pool_data_len.saturating_sub(DISCRIMINATOR_SIZE)
+    }
+
+    pub fn address(pole_pool: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[REALLOC_SEED, pole_pool.as_ref()], &crate::ID).0
+    }
+}
+
+#[account]
+#[derive(Debug, PartialEq)]
+pub struct AllowlistEntry {
+    pub pool: Pubkey,
+    pub wallet: Pubkey, // owner of the liquidity wallet allowed to deposit
+    pub _padding: [u64; 4],
+}
+
+// written only by the deposit, redeem and withdraw queue instructions it is passed to,
+// unlike UserBalance
+#[account]
+#[derive(Debug, PartialEq)]
+pub struct UserPosition {
+    pub pool: Pubkey,
+    pub user: Pubkey, // owner of the liquidity wallet deposits and redeems go through
+    pub lp_amount: u64, // LP minted by the user's deposits, less what it redeemed
+    pub deposit_slot: u64, // average slot of those deposits, weighted by LP
+    pub principal: u64, // liquidity deposited, less the share of it redeemed
+    pub referrer: Pubkey, // liquidity token account sharing the withdraw fee, set once
+    pub _padding: [u64; 3],
+}
+
+impl UserPosition {
+    pub fn deposit(&mut self, lp_amount: u64, slot: u64) -> ProgramResult {
+        let total = self
+            .lp_amount
+            .checked_add(lp_amount)
+            .ok_or(PoleError::MathOverflow)?;
+        if total == 0 {
+            return Ok(());
+        }
+        let weighted = (self.lp_amount as u128)
+            .checked_mul(self.deposit_slot as u128)
+            .and_then(|weighted| weighted.checked_add(lp_amount as u128 * slot as u128))
+            .ok_or(PoleError::MathOverflow)?;
+        self.deposit_slot = (weighted / total as u128) as u64;
+        self.lp_amount = total;
+        Ok(())
+    }
+
+    pub fn add_principal(&mut self, amount: u64, wallet_cap: u64) -> ProgramResult {
+        let principal = self
+            .principal
+            .checked_add(amount)
+            .ok_or(PoleError::MathOverflow)?;
+        if wallet_cap != 0 && principal > wallet_cap {
+            return Err(PoleError::MeetWalletDepositLimit.into());
+        }
+        self.principal = principal;
+        Ok(())
+    }
+
+    // returns the principal released by redeeming `lp_amount`
+    pub fn redeem(&mut self, lp_amount: u64) -> u64 {
+        let principal_redeemed = if lp_amount >= self.lp_amount {
+            self.principal
+        } else {
+            (self.principal as u128 * lp_amount as u128 / self.lp_amount as u128) as u64
+        };
+        self.principal -= principal_redeemed;
+        self.lp_amount -= lp_amount.min(self.lp_amount);
+        principal_redeemed
+    }
+
+    // LP transferred out of the user's wallet is no longer tracked, nor is its principal
+    pub fn cap_lp(&mut self, lp_balance: u64) {
+        if self.lp_amount > lp_balance {
+            self.redeem(self.lp_amount - lp_balance);
+        }
+    }
+
+    pub fn held_slots(&self, slot: u64) -> u64 {
+        slot.saturating_sub(self.deposit_slot)
+    }
+}
+
+#[account]
+#[derive(Debug, PartialEq)]
+pub struct WithdrawRequest {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub lp_amount: u64,
+    pub liquidity_amount: u64, // net of the withdraw fee, set when settled
+    pub status: u64,           // WITHDRAW_PENDING or WITHDRAW_SETTLED
+    pub requested_slot: u64,
+    pub settled_slot: u64,
+    pub fee_bips: u64,    // withdraw fee of the requester's position when requested
+    pub principal: u64,   // released from the position, given back on cancel
+    pub referrer: Pubkey, // of the requester's position when requested
+    pub _padding: [u64; 2],
+}
+
 #[account]
 #[derive(Debug, PartialEq)]
 pub struct UserBalance {
@@ -139,3 +640,123 @@ pub struct UserBalance {
     pub user: Pubkey,
     pub _padding: [u64; 16],
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn port_state(deposit_verified: u64, redeem_verified: u64, leverage: u64) -> PortState {
+        PortState {
+            deposit_verified,
+            redeem_verified,
+            leverage,
+            init_port_liquidity: Decimal::zero().0 .0,
+            approved_wallet: Pubkey::new_unique(),
+            is_redeemed: 0,
+            redeem_amount: 0,
+            amount_to_unroll: 0,
+            last_sold_slot: 0,
+            user_liquidity_percentage: Rate::zero().0 .0,
+            min_amount_out: 0,
+        }
+    }
+
+    #[test]
+    fn recover_resets_when_no_leg_ran() {
+        assert_eq!(port_state(1, 0, 0).recover_leg(100), Ok(RecoverLeg::Reset));
+        assert_eq!(port_state(0, 1, 0).recover_leg(0), Ok(RecoverLeg::Reset));
+    }
+
+    #[test]
+    fn recover_deposit() {
+        let state = port_state(1, 0, 2);
+        assert_eq!(state.recover_leg(100), Ok(RecoverLeg::DepositStep));
+        assert_eq!(state.recover_leg(0), Ok(RecoverLeg::FinishDeposit));
+    }
+
+    #[test]
+    fn recover_redeem() {
+        let mut state = port_state(0, 1, 2);
+        assert_eq!(state.recover_leg(100), Ok(RecoverLeg::RedeemStep));
+        state.is_redeemed = 1;
+        assert_eq!(state.recover_leg(100), Ok(RecoverLeg::Advance));
+    }
+
+    #[test]
+    fn recover_rejects_unverified_legs() {
+        assert!(port_state(0, 0, 2).recover_leg(0).is_err());
+    }
+
+    #[test]
+    fn reset_keeps_last_sold_slot() {
+        let mut state = port_state(1, 0, 2);
+        state.last_sold_slot = 42;
+        state.reset();
+        assert_eq!(state.last_sold_slot, 42);
+        assert_eq!(state.recover_leg(0), Ok(RecoverLeg::Reset));
+    }
+
+    #[test]
+    fn generic_config_of_a_smaller_pool() {
+        //u64 words keep the data aligned like account data
+        let mut words = vec![0u64; (DISCRIMINATOR_SIZE + 200) / 8];
+        let data: &mut [u8] = anchor_lang::__private::bytemuck::cast_slice_mut(&mut words);
+        data[..DISCRIMINATOR_SIZE].copy_from_slice(&PolePortPool::discriminator());
+        let owner = Pubkey::new_unique();
+        //after bump, liquidity_cap, withdraw_fee_bips and fee_receiver
+        data[DISCRIMINATOR_SIZE + 56..DISCRIMINATOR_SIZE + 88].copy_from_slice(owner.as_ref());
+        assert_eq!(GenericPoolConfig::of_pool_data(data).unwrap().owner, owner);
+
+        assert!(GenericPoolConfig::of_pool_data(&data[..DISCRIMINATOR_SIZE + 100]).is_err());
+        data[0] ^= 1;
+        assert!(GenericPoolConfig::of_pool_data(data).is_err());
+    }
+
+    fn registry_entry() -> RegistryEntry {
+        RegistryEntry {
+            pool: Pubkey::new_unique(),
+            status: POOL_ACTIVE,
+            ..RegistryEntry::default()
+        }
+    }
+
+    #[test]
+    fn registry_page_fills_up_without_duplicates() {
+        let mut page: RegistryPage = anchor_lang::__private::bytemuck::Zeroable::zeroed();
+        let entry = registry_entry();
+        page.register(entry).unwrap();
+        assert_eq!(
+            page.register(entry),
+            Err(PoleError::PoolAlreadyRegistered.into())
+        );
+        for _ in 1..REGISTRY_PAGE_SIZE {
+            page.register(registry_entry()).unwrap();
+        }
+        assert_eq!(
+            page.register(registry_entry()),
+            Err(PoleError::RegistryFull.into())
+        );
+
+        page.unregister(&entry.pool).unwrap();
+        assert_eq!(page.position(&entry.pool), None);
+        page.register(entry).unwrap();
+    }
+
+    #[test]
+    fn position_stops_tracking_lp_moved_out() {
+        let mut position = UserPosition {
+            pool: Pubkey::new_unique(),
+            user: Pubkey::new_unique(),
+            lp_amount: 100,
+            deposit_slot: 10,
+            principal: 200,
+            referrer: Pubkey::default(),
+            _padding: [0; 3],
+        };
+        position.cap_lp(150);
+        assert_eq!((position.lp_amount, position.principal), (100, 200));
+        position.cap_lp(40);
+        assert_eq!((position.lp_amount, position.principal), (40, 80));
+        assert_eq!(position.deposit_slot, 10);
+    }
+}
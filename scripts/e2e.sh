@@ -0,0 +1,21 @@
+#!/usr/bin/env bash
+# Runs the e2e tests against pole deployed with the upgradeable loader, as on mainnet, so
+# init_registry checks a real upgrade authority (the wallet below). The dependencies stay at genesis.
+set -euo pipefail
+
+export ANCHOR_WALLET=${ANCHOR_WALLET:-~/.config/solana/id.json}
+
+mkdir -p target/deploy
+cp PoLEr5uRhLSpEZgmBaSmzTUVbEANuFp4vBARZbKsqnu.json target/deploy/pole-keypair.json
+
+solana-test-validator --reset --quiet --ledger target/test-ledger \
+  --bpf-program 9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin deps/serum_dex.so \
+  --bpf-program 22Y43yTVxuUkoRKdm9thyRhQ3SdgQS7c7kB6UNCiaczD deps/serum_swap.so \
+  --bpf-program Port7uDYB3wk6GJAw4KT1WpTeMtSu9bTcChBHkX2LfR deps/port_finance_variable_rate_lending.so \
+  --bpf-program stkarvwmSzv2BygN5e2LeTwimTczLWHCKPKGC2zVLiq deps/port_finance_staking.so &
+validator=$!
+trap 'kill $validator' EXIT
+until solana cluster-version --url localhost > /dev/null 2>&1; do sleep 1; done
+
+# anchor deploys the workspace with `solana program deploy`, the wallet becomes the upgrade authority
+anchor test --skip-build --skip-local-validator "$@"
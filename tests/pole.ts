@@ -1,8 +1,9 @@
 import * as anchor from '@project-serum/anchor'
-import {ASSOCIATED_TOKEN_PROGRAM_ID, Token, TOKEN_PROGRAM_ID} from '@solana/spl-token';
+import {ASSOCIATED_TOKEN_PROGRAM_ID, Token, TOKEN_PROGRAM_ID, u64} from '@solana/spl-token';
 import {
   Keypair,
   PublicKey,
+  SystemProgram,
   SYSVAR_CLOCK_PUBKEY,
   SYSVAR_INSTRUCTIONS_PUBKEY,
   SYSVAR_RENT_PUBKEY,
@@ -11,13 +12,16 @@ import {
 import {
   PORT_LENDING,
   PORT_STAKING,
+  SERUM_DEX_PROGRAM_ID,
+  SWAP_PROGRAM_ID,
 } from './constants';
-import {setupAMarket} from './serum'
+import {fundAccount, setupAMarket, setupMarket} from './serum'
+import {OpenOrders} from '@project-serum/serum';
 import {Program} from '@project-serum/anchor';
 import {Pole, IDL} from '../target/types/pole';
-import {createPolePool, fetchPolePool, fetchReserve, PoleState, SerumState, StakingPoolState} from './utils';
+import {assertPoleError, createPolePool, createRegistry, createUserPosition, fetchPolePool, fetchReserve, PoleState, poolReallocAddress, registryAddress, registryPageAddress, SerumState, StakingPoolState, userPositionAddress} from './utils';
 import {refreshReserveInstruction, refreshObligationInstruction} from '@port.finance/port-sdk';
-import {getTokenAccount, sleep} from '@project-serum/common';
+import {createMint, createTokenAccount, getMintInfo, getTokenAccount, sleep} from '@project-serum/common';
 import {assert} from 'chai';
 import {createDefaultReserve, createLendingMarket, createStakingPool, generatePortAccounts, generateSerumAccounts, ReserveState} from './port';
 import Big from "big.js";
@@ -69,7 +73,11 @@ describe('pole', () => {
   const poolName = "USDC";
   let poleState: PoleState;
 
-  const deposit = (amount: number|string, isInit: boolean) =>
+  const U64_MAX = "18446744073709551615";
+
+  //a delegate of the user's liquidity wallet signs the transfer in place of its owner,
+  //a zap in swaps amount of PORT into the wallet on the first leg and deposits what it returned
+  const deposit = (amount: number|string, isInit: boolean, minLpOut: number|string = 0, expectedError?: string, delegate?: Keypair, zapInMinLiquidityOut?: number|string) =>
     async () => {
       const [polePool] = await PublicKey.findProgramAddress(
         [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
@@ -77,7 +85,7 @@ describe('pole', () => {
       );
       let freshPolePool = await fetchPolePool(pole, polePool);
       const [poleAuthority] = await PublicKey.findProgramAddress(
-        [],
+        [polePool.toBuffer()],
         pole.programId
       );
       const poleLpTokenAccount = await Token.getAssociatedTokenAddress(
@@ -105,6 +113,7 @@ describe('pole', () => {
           })(),
           []
         );
+        await createUserPosition(pole, polePool, serumOrderBook.usdcTokenAccount);
         beforeUserLpAmount = new anchor.BN(0);
       } else {
         beforeUserLpAmount = (await getTokenAccount(provider, poleLpTokenAccount)).amount;
@@ -126,26 +135,65 @@ describe('pole', () => {
           },
           portAccounts: await generatePortAccounts(
             serumOrderBook.usdcMint, reserveState.address, reserve, freshPolePool),
-          userTransferAuthority: provider.wallet.publicKey,
+          userTransferAuthority: (delegate ?? provider.wallet).publicKey,
           tokenProgram: freshPolePool.genericConfig.tokenProgram,
-          clock: SYSVAR_CLOCK_PUBKEY
+          clock: SYSVAR_CLOCK_PUBKEY,
         },
+        remainingAccounts: [
+          {pubkey: (await userPositionAddress(pole, polePool))[0], isWritable: true, isSigner: false}
+        ],
         signers: []
       };
+      //the zap in's accounts go after the oracle, on the first leg only
+      let zapInAccounts: PublicKey[] = [];
+      if (zapInMinLiquidityOut !== undefined) {
+        const marketAccounts = await userMarketAccounts(freshPolePool);
+        zapInAccounts = [
+          marketAccounts.market,
+          marketAccounts.openOrders,
+          marketAccounts.requestQueue,
+          marketAccounts.eventQueue,
+          marketAccounts.bids,
+          marketAccounts.asks,
+          marketAccounts.coinVault,
+          marketAccounts.pcVault,
+          marketAccounts.vaultSigner,
+          serumOrderBook.portTokenAccount,
+          serumOrderBook.portMint,
+          SERUM_DEX_PROGRAM_ID,
+          SWAP_PROGRAM_ID,
+          SYSVAR_RENT_PUBKEY,
+        ];
+      }
+      const zapInMin = zapInMinLiquidityOut === undefined ? null : new anchor.BN(zapInMinLiquidityOut);
       const depositIxAmount = pole.instruction.depositLiquidity(
         new anchor.BN(amount),
-        depositAccounts
+        new anchor.BN(minLpOut),
+        zapInMin,
+        {
+          ...depositAccounts,
+          remainingAccounts: depositAccounts.remainingAccounts
+            .concat(zapInAccounts.map((pubkey, i) => ({pubkey, isWritable: i < 8 || i == 9, isSigner: false}))),
+        }
       );
       const depositIxEmpty = pole.instruction.depositLiquidity(
         new anchor.BN(0),
+        new anchor.BN(0),
+        null,
         depositAccounts
       );
+      //allowlisted pools check the entry of the wallet owner
+      const allowlistAccounts = freshPolePool.allowlistEnabled.eqn(1)
+        ? [serumOrderBook.usdcTokenAccount, (await allowlistEntryAddress(polePool, provider.wallet.publicKey))[0]]
+          .map(pubkey => ({pubkey, isWritable: false, isSigner: false}))
+        : [];
       const verifyIx = pole.instruction.verifyDeposit(
         {
           accounts: {
             polePool: polePool,
             transactionInfo: SYSVAR_INSTRUCTIONS_PUBKEY
-          }
+          },
+          remainingAccounts: allowlistAccounts
         }
       );
       const refreshReserveIx = refreshReserveInstruction(
@@ -175,7 +223,30 @@ describe('pole', () => {
         )
       }
 
-      await pole.provider.send(tx);
+      if (delegate) {
+        await pole.provider.send(new Transaction().add(Token.createApproveInstruction(
+          TOKEN_PROGRAM_ID,
+          serumOrderBook.usdcTokenAccount,
+          delegate.publicKey,
+          provider.wallet.publicKey,
+          [],
+          new u64(amount.toString())
+        )));
+      }
+      const signers = delegate ? [delegate] : [];
+      if (expectedError) {
+        await assertPoleError(pole.provider.send(tx, signers), expectedError);
+        return;
+      }
+      const beforePort = await tokenBalance(serumOrderBook.portTokenAccount);
+      const beforeUserLiquidity = await tokenBalance(serumOrderBook.usdcTokenAccount);
+      await pole.provider.send(tx, signers);
+      if (zapInMin) {
+        assert((await tokenBalance(serumOrderBook.portTokenAccount)).eq(beforePort.sub(new anchor.BN(amount))), "The zap should sell the whole source amount");
+        assert((await tokenBalance(serumOrderBook.usdcTokenAccount)).eq(beforeUserLiquidity), "The leg should deposit exactly what the swap returned");
+        assert((await getTokenAccount(provider, poleLpTokenAccount)).amount.gt(beforeUserLpAmount), "The zap should mint LP");
+        return;
+      }
 
       freshPolePool = await fetchPolePool(pole, polePool);
       const userLpWallet = await getTokenAccount(provider, poleLpTokenAccount);
@@ -196,6 +267,19 @@ describe('pole', () => {
 
     };
 
+  const delegated_deposit = async () => {
+    const [polePool] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    const [userPosition] = await userPositionAddress(pole, polePool);
+    const positionLpBefore = (await pole.account.userPosition.fetch(userPosition)).lpAmount;
+    await deposit(1_000_000, true, 0, undefined, Keypair.generate())();
+    const position = await pole.account.userPosition.fetch(userPosition);
+    assert(position.user.equals(provider.wallet.publicKey));
+    assert(position.lpAmount.gt(positionLpBefore), "The wallet owner's position should track a deposit signed by a delegate");
+  };
+
   const change_liquidity_cap =  (cap: number) => async () => {
     const [polePoolAddr] = await PublicKey.findProgramAddress(
       [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
@@ -219,6 +303,294 @@ describe('pole', () => {
     const freshPolePool = await fetchPolePool(pole, polePoolAddr);
     assert(freshPolePool.genericConfig.liquidityCap.eq(new anchor.BN(cap)))
   };
+  const set_referrer = async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    const freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    const [userPosition] = await userPositionAddress(pole, polePoolAddr);
+    const referrer = await createTokenAccount(provider, serumOrderBook.usdcMint, Keypair.generate().publicKey);
+    await pole.rpc.changeReferralFee(new anchor.BN(2000), {
+      accounts: {
+        polePool: polePoolAddr,
+        owner: pole.provider.wallet.publicKey
+      }
+    });
+    await pole.rpc.setReferrer({
+      accounts: {
+        polePool: polePoolAddr,
+        userPosition: userPosition,
+        referrerFeeAccount: referrer,
+        liquiditySupply: freshPolePool.genericConfig.liquiditySupply,
+        user: pole.provider.wallet.publicKey,
+        tokenProgram: freshPolePool.genericConfig.tokenProgram,
+      }
+    });
+    const position = await pole.account.userPosition.fetch(userPosition);
+    assert(position.referrer.equals(referrer), "The position should be attributed to the referrer");
+    assert((await fetchPolePool(pole, polePoolAddr)).referralFeeBips.eq(new anchor.BN(2000)));
+  };
+  const split_fees = async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    const freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    const [polePoolAuthority] = await PublicKey.findProgramAddress(
+      [polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    const [feeSplit, bump] = await PublicKey.findProgramAddress(
+      [Buffer.from("fee_split"), polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    const feeVault = Keypair.generate();
+    await pole.rpc.initFeeSplit(bump, {
+      accounts: {
+        polePool: polePoolAddr,
+        poleAuthority: polePoolAuthority,
+        feeSplit: feeSplit,
+        feeVault: feeVault.publicKey,
+        liquidityMint: serumOrderBook.usdcMint,
+        liquiditySupply: freshPolePool.genericConfig.liquiditySupply,
+        owner: pole.provider.wallet.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: SYSVAR_RENT_PUBKEY,
+      },
+      signers: [feeVault]
+    });
+    assert((await fetchPolePool(pole, polePoolAddr)).genericConfig.feeReceiver.equals(feeVault.publicKey));
+    //the vault would stop receiving fees the split has to pay out
+    await assertPoleError(pole.rpc.changeFeeReceiver(Keypair.generate().publicKey, {
+      accounts: {
+        polePool: polePoolAddr,
+        feeSplit: feeSplit,
+        owner: pole.provider.wallet.publicKey
+      }
+    }), "FeeSplitActive");
+    const partner = await createTokenAccount(provider, serumOrderBook.usdcMint, Keypair.generate().publicKey);
+    await pole.rpc.changeFeeSplit(
+      [
+        {recipient: freshPolePool.genericConfig.feeReceiver, bips: new anchor.BN(7000)},
+        {recipient: partner, bips: new anchor.BN(3000)},
+      ],
+      {
+        accounts: {
+          polePool: polePoolAddr,
+          feeSplit: feeSplit,
+          owner: pole.provider.wallet.publicKey
+        }
+      }
+    );
+    await pole.provider.send(new Transaction().add(Token.createTransferInstruction(
+      TOKEN_PROGRAM_ID,
+      serumOrderBook.usdcTokenAccount,
+      feeVault.publicKey,
+      provider.wallet.publicKey,
+      [],
+      10_001
+    )));
+    const balance = async (account: PublicKey) => new anchor.BN((await provider.connection.getTokenAccountBalance(account)).value.amount);
+    const receiverBefore = await balance(freshPolePool.genericConfig.feeReceiver);
+    const partnerBefore = await balance(partner);
+    await pole.rpc.distributeFees({
+      accounts: {
+        polePool: polePoolAddr,
+        poleAuthority: polePoolAuthority,
+        feeSplit: feeSplit,
+        feeVault: feeVault.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      },
+      remainingAccounts: [freshPolePool.genericConfig.feeReceiver, partner].map(pubkey => ({pubkey, isWritable: true, isSigner: false}))
+    });
+    const split = await pole.account.feeSplit.fetch(feeSplit);
+    assert(split.recipientCount == 2, "The fee split should have two recipients");
+    assert((await balance(freshPolePool.genericConfig.feeReceiver)).sub(receiverBefore).eqn(7000), "The first recipient should get its 70%, rounded down");
+    assert((await balance(partner)).sub(partnerBefore).eqn(3001), "The last recipient should get the rest");
+    assert((await balance(feeVault.publicKey)).eqn(0), "The vault should be paid out");
+  };
+  //the pool's own accounts are refused, a stray one is swept
+  const sweep = async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    const freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    const [polePoolAuthority] = await PublicKey.findProgramAddress(
+      [polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    const [sellRoute] = await PublicKey.findProgramAddress(
+      [Buffer.from("sell_route"), polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    const [subReward] = await PublicKey.findProgramAddress(
+      [Buffer.from("sub_reward"), polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    const target = await createTokenAccount(provider, serumOrderBook.usdcMint, provider.wallet.publicKey);
+    const sweepFrom = (source: PublicKey) => pole.rpc.sweep({
+      accounts: {
+        polePool: polePoolAddr,
+        poleAuthority: polePoolAuthority,
+        source: source,
+        target: target,
+        sellRoute: sellRoute,
+        subReward: subReward,
+        owner: provider.wallet.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      },
+    });
+    const poolAccounts = [
+      freshPolePool.genericConfig.liquiditySupply,
+      freshPolePool.withdrawQueue.settledLiquidity,
+      (await pole.account.subReward.fetch(subReward)).supply,
+    ];
+    for (const account of poolAccounts) {
+      await assertPoleError(sweepFrom(account), "InvalidSweepAccount");
+    }
+
+    const stray = await createTokenAccount(provider, serumOrderBook.usdcMint, polePoolAuthority);
+    await provider.send(new Transaction().add(Token.createTransferInstruction(
+      TOKEN_PROGRAM_ID, serumOrderBook.usdcTokenAccount, stray, provider.wallet.publicKey, [], 1_000
+    )));
+    await sweepFrom(stray);
+    assert((await tokenBalance(target)).eqn(1_000), "The stray balance should be swept");
+  };
+  const change_wallet_cap = (cap: number) => async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    await pole.rpc.changeWalletCap(
+      new anchor.BN(cap),
+      {
+        accounts: {
+          polePool: polePoolAddr,
+          owner: pole.provider.wallet.publicKey
+        }
+      }
+    );
+    const freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    assert(freshPolePool.walletCap.eq(new anchor.BN(cap)));
+  };
+  const change_harvest_config = (staleSlot: number, minHarvestInterval: number, harvestBountyBips: number) => async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    await pole.rpc.changeHarvestConfig(
+      new anchor.BN(staleSlot),
+      new anchor.BN(minHarvestInterval),
+      new anchor.BN(harvestBountyBips),
+      {
+        accounts: {
+          polePool: polePoolAddr,
+          owner: pole.provider.wallet.publicKey
+        }
+      }
+    );
+    const freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    assert(freshPolePool.harvestConfig.staleSlot.eq(new anchor.BN(staleSlot)));
+    assert(freshPolePool.harvestConfig.minHarvestInterval.eq(new anchor.BN(minHarvestInterval)));
+    assert(freshPolePool.harvestConfig.harvestBountyBips.eq(new anchor.BN(harvestBountyBips)));
+  };
+  const change_fee_schedule = (tiers: [number, number][]) => async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    await pole.rpc.changeFeeSchedule(
+      tiers.map(([heldSlots, bips]) => ({heldSlots: new anchor.BN(heldSlots), bips})),
+      {
+        accounts: {
+          polePool: polePoolAddr,
+          owner: pole.provider.wallet.publicKey
+        }
+      }
+    );
+    const freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    assert(freshPolePool.feeSchedule.tierCount.eq(new anchor.BN(tiers.length)));
+    tiers.forEach(([heldSlots, bips], i) => {
+      assert(freshPolePool.feeSchedule.heldSlots[i].eq(new anchor.BN(heldSlots)));
+      assert.equal(freshPolePool.feeSchedule.bips[i], bips);
+    });
+  };
+  const allowlistEntryAddress = (polePool: PublicKey, wallet: PublicKey) => PublicKey.findProgramAddress(
+    [Buffer.from("allowlist"), polePool.toBuffer(), wallet.toBuffer()],
+    pole.programId
+  );
+  const change_allowlist_mode = (enabled: boolean) => async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    await pole.rpc.changeAllowlistMode(enabled, {
+      accounts: {
+        polePool: polePoolAddr,
+        owner: pole.provider.wallet.publicKey,
+      }
+    });
+    const freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    assert(freshPolePool.allowlistEnabled.eq(new anchor.BN(enabled ? 1 : 0)));
+  };
+  const allowlist_user = (allowed: boolean) => async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    const [allowlistEntry, bump] = await allowlistEntryAddress(polePoolAddr, pole.provider.wallet.publicKey);
+    if (allowed) {
+      await pole.rpc.addAllowlistEntry(bump, pole.provider.wallet.publicKey, {
+        accounts: {
+          polePool: polePoolAddr,
+          allowlistEntry: allowlistEntry,
+          owner: pole.provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        }
+      });
+    } else {
+      await pole.rpc.removeAllowlistEntry({
+        accounts: {
+          polePool: polePoolAddr,
+          allowlistEntry: allowlistEntry,
+          owner: pole.provider.wallet.publicKey,
+        }
+      });
+    }
+  };
+  const manage_allowlist = async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    const wallet = Keypair.generate().publicKey;
+    const [allowlistEntry, bump] = await allowlistEntryAddress(polePoolAddr, wallet);
+    await pole.rpc.addAllowlistEntry(bump, wallet, {
+      accounts: {
+        polePool: polePoolAddr,
+        allowlistEntry: allowlistEntry,
+        owner: pole.provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      }
+    });
+    const entry = await pole.account.allowlistEntry.fetch(allowlistEntry);
+    assert(entry.pool.equals(polePoolAddr));
+    assert(entry.wallet.equals(wallet));
+
+    await pole.rpc.removeAllowlistEntry({
+      accounts: {
+        polePool: polePoolAddr,
+        allowlistEntry: allowlistEntry,
+        owner: pole.provider.wallet.publicKey,
+      }
+    });
+    assert.isNull(await provider.connection.getAccountInfo(allowlistEntry), "Removed entries should be closed");
+    const freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    assert(freshPolePool.allowlistEnabled.eq(new anchor.BN(0)), "Pools are open unless allowlist mode is on");
+  };
   const claim_and_sell =
     async () => {
       const [polePoolAddr] = await PublicKey.findProgramAddress(
@@ -227,7 +599,7 @@ describe('pole', () => {
       );
       const freshPolePool = await fetchPolePool(pole, polePoolAddr);
       const [poleAuthority] = await PublicKey.findProgramAddress(
-        [],
+        [polePoolAddr.toBuffer()],
         pole.programId
       );
       const [stakingProgramAuthority] = await anchor.web3.PublicKey.findProgramAddress(
@@ -239,6 +611,7 @@ describe('pole', () => {
           polePool: polePoolAddr,
           poleAuthority: poleAuthority,
           liquiditySupply: freshPolePool.genericConfig.liquiditySupply,
+          harvesterLiquidityWallet: serumOrderBook.usdcTokenAccount,
           portSupply: freshPolePool.portConfig.portSupply,
           dexProgram: freshPolePool.serumConfig.dexProgram,
           swapProgram: freshPolePool.serumConfig.swapProgram,
@@ -274,14 +647,137 @@ describe('pole', () => {
   };
 
 
-  const withdraw = (amount: number | string | anchor.BN) => async () => {
+  //PORT stands in for the staking pool's second reward, sold on the PORT/USDC market
+  const sell_sub_reward = async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    const [poleAuthority] = await PublicKey.findProgramAddress(
+      [polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    const [subReward, subRewardBump] = await PublicKey.findProgramAddress(
+      [Buffer.from("sub_reward"), polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    const freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    const subRewardSupply = Keypair.generate();
+    const subRewardOpenOrders = Keypair.generate();
+    await pole.rpc.initSubReward(subRewardBump, {
+      accounts: {
+        polePool: polePoolAddr,
+        poleAuthority: poleAuthority,
+        subReward: subReward,
+        subRewardSupply: subRewardSupply.publicKey,
+        subRewardMint: serumOrderBook.portMint,
+        subRewardOpenOrders: subRewardOpenOrders.publicKey,
+        dexMarket: serumOrderBook.marketPortUSDC.address,
+        owner: provider.wallet.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        dexProgram: SERUM_DEX_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: SYSVAR_RENT_PUBKEY,
+      },
+      signers: [subRewardSupply, subRewardOpenOrders]
+    });
+    const fund = () => provider.send(new Transaction().add(Token.createTransferInstruction(
+      TOKEN_PROGRAM_ID, serumOrderBook.portTokenAccount, subRewardSupply.publicKey, provider.wallet.publicKey, [], 10_000_000
+    )));
+    const sell = async () => pole.rpc.sellSubReward({
+      accounts: {
+        polePool: polePoolAddr,
+        poleAuthority: poleAuthority,
+        subReward: subReward,
+        liquiditySupply: freshPolePool.genericConfig.liquiditySupply,
+        harvesterLiquidityWallet: serumOrderBook.usdcTokenAccount,
+        subRewardSupply: subRewardSupply.publicKey,
+        subRewardMint: serumOrderBook.portMint,
+        marketAccounts: {
+          ...(await generateSerumAccounts(provider, serumOrderBook.marketPortUSDC.address, freshPolePool)),
+          openOrders: subRewardOpenOrders.publicKey,
+        },
+        tokenProgram: TOKEN_PROGRAM_ID,
+        dexProgram: SERUM_DEX_PROGRAM_ID,
+        swapProgram: SWAP_PROGRAM_ID,
+        clock: SYSVAR_CLOCK_PUBKEY,
+        rent: SYSVAR_RENT_PUBKEY,
+      }
+    });
+
+    await fund();
+    const before = await tokenBalance(freshPolePool.genericConfig.liquiditySupply);
+    await sell();
+    assert((await tokenBalance(freshPolePool.genericConfig.liquiditySupply)).gt(before), "The sub reward should be sold for liquidity");
+    assert((await tokenBalance(subRewardSupply.publicKey)).lt(new anchor.BN(10_000_000)));
+    const sold = await pole.account.subReward.fetch(subReward);
+    assert(sold.lastSoldSlot.gtn(0), "The sale should be recorded");
+
+    //a second sale within min_harvest_interval is refused
+    await change_harvest_config(100, 50, 10)();
+    await fund();
+    await assertPoleError(sell(), "HarvestTooSoon");
+    await change_harvest_config(100, 0, 10)();
+  };
+
+  //one leg per port_iterate in one transaction, refreshing the reserve and obligation in-handler
+  const auto_compound = async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    const freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    const [poleAuthority] = await PublicKey.findProgramAddress(
+      [polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    const portIterate = freshPolePool.portConfig.portIterate.toNumber();
+    assert(portIterate > 1, "The pool should leverage over several legs");
+    const reserve = await fetchReserve(provider, reserveState.address);
+    const autoCompoundAccs = {
+      accounts: {
+        polePool: polePoolAddr,
+        poleAuthority: poleAuthority,
+        liquiditySupply: freshPolePool.genericConfig.liquiditySupply,
+        portAccounts: await generatePortAccounts(
+          serumOrderBook.usdcMint, reserveState.address, reserve, freshPolePool),
+        transactionInfo: SYSVAR_INSTRUCTIONS_PUBKEY,
+        tokenProgram: freshPolePool.genericConfig.tokenProgram,
+        clock: SYSVAR_CLOCK_PUBKEY,
+      }
+    };
+    const legs = (count: number) => {
+      const tx = new Transaction();
+      for (let leverage = 0; leverage < count; leverage += 1) {
+        tx.add(pole.instruction.autoCompound(leverage, autoCompoundAccs));
+      }
+      return tx;
+    };
+
+    //the first leg refuses a transaction without every other leg
+    await assertPoleError(pole.provider.send(legs(portIterate - 1)), "InvalidTransaction");
+
+    const borrowedBefore = reserve.liquidity.borrowedAmountWads;
+    await pole.provider.send(legs(portIterate));
+
+    const afterLiquidityAmount = await tokenBalance(freshPolePool.genericConfig.liquiditySupply);
+    assert(afterLiquidityAmount.lt(freshPolePool.portConfig.minDeposit), "Harvested liquidity should be compounded");
+    const borrowedAfter = (await fetchReserve(provider, reserveState.address)).liquidity.borrowedAmountWads;
+    assert(borrowedAfter.gt(borrowedBefore), "The compounded liquidity should be leveraged");
+    assert((await fetchPolePool(pole, polePoolAddr)).portState.leverage.eqn(0), "The legs should leave the pool idle");
+  };
+
+
+  //with staleReserve the legs get no refresh instructions, only the reserve oracle (none here) in
+  //remaining accounts, and have to refresh the reserve and obligation themselves
+  const withdraw = (amount: number | string | anchor.BN, minLiquidityOut: number | string = 0, expectedError?: string, tracked = true, zapOutMinOutput?: number | string, staleReserve = false) => async () => {
     const [polePoolAddr] = await PublicKey.findProgramAddress(
       [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
       pole.programId
     );
     const freshPolePool = await fetchPolePool(pole, polePoolAddr);
     const [poleAuthority] = await PublicKey.findProgramAddress(
-      [],
+      [polePoolAddr.toBuffer()],
       pole.programId
     );
 
@@ -299,6 +795,31 @@ describe('pole', () => {
     }
     // console.log("withdraw amount", amount.toString())
     const reserve = await fetchReserve(provider, reserveState.address);
+    const [userPosition] = await userPositionAddress(pole, polePoolAddr);
+    //the position's referrer goes last, after the position
+    const referrer = tracked ? (await pole.account.userPosition.fetch(userPosition)).referrer : PublicKey.default;
+    const hasReferrer = !referrer.equals(PublicKey.default);
+    //a zap out swaps the payout into PORT, its accounts go after the referrer
+    let zapOutAccounts: PublicKey[] = [];
+    if (zapOutMinOutput !== undefined) {
+      const marketAccounts = await userMarketAccounts(freshPolePool);
+      zapOutAccounts = [
+        marketAccounts.market,
+        marketAccounts.openOrders,
+        marketAccounts.requestQueue,
+        marketAccounts.eventQueue,
+        marketAccounts.bids,
+        marketAccounts.asks,
+        marketAccounts.coinVault,
+        marketAccounts.pcVault,
+        marketAccounts.vaultSigner,
+        serumOrderBook.portTokenAccount,
+        SERUM_DEX_PROGRAM_ID,
+        SWAP_PROGRAM_ID,
+        SYSVAR_RENT_PUBKEY,
+      ];
+    }
+    const zapOutMin = zapOutMinOutput === undefined ? null : new anchor.BN(zapOutMinOutput);
 
     const withdrawAccs = {
       accounts: {
@@ -317,8 +838,11 @@ describe('pole', () => {
         userTransferAuthority: provider.wallet.publicKey,
         tokenProgram: freshPolePool.genericConfig.tokenProgram,
         clock: SYSVAR_CLOCK_PUBKEY,
-        poleFeeAccount: freshPolePool.genericConfig.feeReceiver
+        poleFeeAccount: freshPolePool.genericConfig.feeReceiver,
       },
+      remainingAccounts: (tracked ? [userPosition] : []).concat(hasReferrer ? [referrer] : [])
+        .map(pubkey => ({pubkey, isWritable: true, isSigner: false}))
+        .concat(zapOutAccounts.map((pubkey, i) => ({pubkey, isWritable: i < 8 || i == 9, isSigner: false}))),
       signers: []
     };
 
@@ -332,11 +856,15 @@ describe('pole', () => {
     );
     const withdrawIx = pole.instruction.redeemLiquidity(
       new anchor.BN(amount),
+      new anchor.BN(minLiquidityOut),
+      zapOutMin,
       withdrawAccs
     );
 
     const withdrawIx2 = pole.instruction.redeemLiquidity(
       new anchor.BN(0),
+      new anchor.BN(0),
+      zapOutMin,
       withdrawAccs
     );
 
@@ -351,47 +879,826 @@ describe('pole', () => {
     );
 
     const withdrawTx = new Transaction();
-    withdrawTx.add(
-      verifyIx,
-      refreshReserveIx,
-      refreshObligationIx,
-      withdrawIx,
-    );
+    withdrawTx.add(verifyIx);
+    if (!staleReserve) {
+      withdrawTx.add(refreshReserveIx, refreshObligationIx);
+    }
+    withdrawTx.add(withdrawIx);
 
     for (let i = 1; i < freshPolePool.portConfig.portIterate; i += 1) {
-      withdrawTx.add(
-        refreshReserveIx,
-        refreshObligationIx,
-        withdrawIx2,
-      )
+      if (!staleReserve) {
+        withdrawTx.add(refreshReserveIx, refreshObligationIx);
+      }
+      withdrawTx.add(withdrawIx2);
     }
     const lp_amount = freshPolePool.basicState.lpAmount;
     const lp_percentage = new Big(amount.toString()).div(new Big(lp_amount.toString()));
 
+    if (staleReserve) {
+      //let interest accrue over a few slots
+      await sleep(2000);
+    }
     const beforePoleLiquidity = new Big((await provider.connection.getTokenAccountBalance(freshPolePool.genericConfig.liquiditySupply)).value.amount);
     const parsedReserve = await fetchReserve(provider, reserveState.address);
-    const beforeTotalLiquidity = new Big(parsedReserve.liquidity.availableAmount.toString()).add(beforePoleLiquidity);
-    const liquidityShouldGet = beforeTotalLiquidity.mul(lp_percentage).mul(new Big("10000").sub(new Big(freshPolePool.genericConfig.withdrawFeeBips.toString()))).div(new Big("10000"));
+    let beforeTotalLiquidity = new Big(parsedReserve.liquidity.availableAmount.toString()).add(beforePoleLiquidity);
     const beforeUserLiquidity = new Big( (await provider.connection.getTokenAccountBalance(serumOrderBook.usdcTokenAccount)).value.amount);
+    const beforeFee = new Big((await provider.connection.getTokenAccountBalance(freshPolePool.genericConfig.feeReceiver)).value.amount);
+    const referrerBalance = async () => hasReferrer
+      ? new Big((await provider.connection.getTokenAccountBalance(referrer)).value.amount)
+      : new Big(0);
+    const beforeReferrer = await referrerBalance();
+    if (expectedError) {
+      await assertPoleError(provider.send(withdrawTx), expectedError);
+      return;
+    }
+    const beforePort = await tokenBalance(serumOrderBook.portTokenAccount);
     await provider.send(withdrawTx);
-    const fee_received =  new anchor.BN((await provider.connection.getTokenAccountBalance(freshPolePool.genericConfig.feeReceiver)).value.amount);
-    assert(fee_received > new anchor.BN(0));
+    if (staleReserve) {
+      const refreshedReserve = await fetchReserve(provider, reserveState.address);
+      const before = parsedReserve.liquidity.cumulativeBorrowRateWads;
+      const after = refreshedReserve.liquidity.cumulativeBorrowRateWads;
+      assert(refreshedReserve.lastUpdate.slot > parsedReserve.lastUpdate.slot, "The legs should refresh the stale reserve");
+      assert(after.gt(before), "The refresh should accrue interest");
+      //the pool holds all the collateral but the reserve's initial liquidity and is the only
+      //borrower, priced at the refreshed exchange rate
+      const growth = new Big(after.div(before).toString());
+      const borrowed = new Big(parsedReserve.liquidity.borrowedAmountWads.toString()).mul(growth);
+      const totalLiquidity = new Big(parsedReserve.liquidity.availableAmount.toString()).add(borrowed);
+      const collateralSupply = new Big(parsedReserve.collateral.mintTotalSupply.toString());
+      const poolCollateral = collateralSupply.sub(initialLiquidity);
+      beforeTotalLiquidity = poolCollateral.mul(totalLiquidity).div(collateralSupply).sub(borrowed).add(beforePoleLiquidity);
+    }
+    if (zapOutMin) {
+      assert((await tokenBalance(serumOrderBook.portTokenAccount)).gte(beforePort.add(zapOutMin)), "The payout should be swapped into PORT");
+      return;
+    }
+    //the fee schedule tier the redeem paid, recorded by its first leg
+    const feeBips = new Big((await fetchPolePool(pole, polePoolAddr)).redeemFeeBips.toString());
+    const liquidityShouldGet = beforeTotalLiquidity.mul(lp_percentage).mul(new Big("10000").sub(feeBips)).div(new Big("10000"));
+    const referralGet = (await referrerBalance()).sub(beforeReferrer);
+    const feeGet = new Big((await provider.connection.getTokenAccountBalance(freshPolePool.genericConfig.feeReceiver)).value.amount).sub(beforeFee).add(referralGet);
+    //the referrer takes referral_fee_bips of the fee, rounded down
+    const referralShouldGet = hasReferrer
+      ? feeGet.mul(freshPolePool.referralFeeBips.toString()).div(10000).round(0, 0)
+      : new Big(0);
+    assert(referralGet.eq(referralShouldGet), "Referrer got " + referralGet.toString() + ", should get " + referralShouldGet.toString());
+    assert(!hasReferrer || referralGet.gt(0), "The referrer should be paid");
 
     const afterUserLiquidity = new Big((await provider.connection.getTokenAccountBalance(serumOrderBook.usdcTokenAccount)).value.amount);
     const liquidityGet = afterUserLiquidity.sub(beforeUserLiquidity);
+    //the fee is the redeemed liquidity times the tier's bips, rounded up
+    const feeRounding = feeGet.mul(10000).sub(feeGet.add(liquidityGet).mul(feeBips));
+    assert(feeRounding.gte(0) && feeRounding.lt(10000), "Fee " + feeGet.toString() + " should be " + feeBips.toString() + " bips of the redeem");
 
     assert(liquidityGet.lte(liquidityShouldGet))
     assert(liquidityGet.gte(liquidityShouldGet.mul(0.999)) || liquidityGet.eq(liquidityShouldGet.toFixed(0, 0)), liquidityGet.toString() + ", " + liquidityShouldGet.toString())
   };
 
+  const redeem_fee_tier = (tracked: boolean, feeBips: number) => async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    await withdraw(10_000, 0, undefined, tracked)();
+    const freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    assert(freshPolePool.redeemFeeBips.eq(new anchor.BN(feeBips)), "The redeem should pay " + feeBips + " bips");
+  };
+
+  //the user's own open orders on the PORT/USDC market, zaps trade through them
+  let userOpenOrders: PublicKey;
+  const userMarketAccounts = async (freshPolePool: any) => {
+    if (!userOpenOrders) {
+      const openOrders = Keypair.generate();
+      await provider.send(new Transaction().add(await OpenOrders.makeCreateAccountTransaction(
+        provider.connection,
+        serumOrderBook.marketPortUSDC.address,
+        provider.wallet.publicKey,
+        openOrders.publicKey,
+        SERUM_DEX_PROGRAM_ID
+      )), [openOrders]);
+      userOpenOrders = openOrders.publicKey;
+    }
+    return {
+      ...(await generateSerumAccounts(provider, serumOrderBook.marketPortUSDC.address, freshPolePool)),
+      openOrders: userOpenOrders
+    };
+  };
+  const tokenBalance = async (account: PublicKey) => new anchor.BN((await provider.connection.getTokenAccountBalance(account)).value.amount);
+  const zap_in = (amount: number, minLiquidityOut: number | string, expectedError?: string) =>
+    deposit(amount, true, 0, expectedError, undefined, minLiquidityOut);
+
+  const withdraw_queue = async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    const [poleAuthority] = await PublicKey.findProgramAddress(
+      [polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    let freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    const lpEscrow = Keypair.generate();
+    const settledLiquidity = Keypair.generate();
+    await pole.rpc.initWithdrawQueue({
+      accounts: {
+        polePool: polePoolAddr,
+        poleAuthority: poleAuthority,
+        lpMint: freshPolePool.genericConfig.lpMint,
+        liquidityMint: serumOrderBook.usdcMint,
+        liquiditySupply: freshPolePool.genericConfig.liquiditySupply,
+        lpEscrow: lpEscrow.publicKey,
+        settledLiquidity: settledLiquidity.publicKey,
+        owner: provider.wallet.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: SYSVAR_RENT_PUBKEY,
+      },
+      signers: [lpEscrow, settledLiquidity],
+    });
+
+    const userLpWallet = await Token.getAssociatedTokenAddress(
+      ASSOCIATED_TOKEN_PROGRAM_ID,
+      TOKEN_PROGRAM_ID,
+      freshPolePool.genericConfig.lpMint,
+      provider.wallet.publicKey,
+    );
+    const [withdrawRequest, requestBump] = await PublicKey.findProgramAddress(
+      [Buffer.from("withdraw"), polePoolAddr.toBuffer(), provider.wallet.publicKey.toBuffer()],
+      pole.programId
+    );
+    const [userPosition] = await userPositionAddress(pole, polePoolAddr);
+    const positionAccounts = [{pubkey: userPosition, isWritable: true, isSigner: false}];
+    const lpBalance = async () => new anchor.BN((await provider.connection.getTokenAccountBalance(userLpWallet)).value.amount);
+    const lpAmount = new anchor.BN(1_000_000);
+    const request = () => pole.rpc.requestWithdraw(requestBump, lpAmount, {
+      accounts: {
+        polePool: polePoolAddr,
+        withdrawRequest: withdrawRequest,
+        userLpWallet: userLpWallet,
+        lpEscrow: lpEscrow.publicKey,
+        user: provider.wallet.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        clock: SYSVAR_CLOCK_PUBKEY,
+      },
+      remainingAccounts: positionAccounts,
+    });
+
+    const lpBefore = await lpBalance();
+    const positionLpBefore = (await pole.account.userPosition.fetch(userPosition)).lpAmount;
+    await request();
+    assert((await lpBalance()).eq(lpBefore.sub(lpAmount)), "LP should be escrowed");
+    freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    assert(freshPolePool.withdrawQueue.pendingLp.eq(lpAmount));
+
+    await pole.rpc.cancelWithdrawRequest({
+      accounts: {
+        polePool: polePoolAddr,
+        poleAuthority: poleAuthority,
+        withdrawRequest: withdrawRequest,
+        lpEscrow: lpEscrow.publicKey,
+        userLpWallet: userLpWallet,
+        user: provider.wallet.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      },
+      remainingAccounts: positionAccounts,
+    });
+    assert((await lpBalance()).eq(lpBefore), "Cancelling should return the LP");
+    assert((await pole.account.userPosition.fetch(userPosition)).lpAmount.eq(positionLpBefore), "Cancelling should track the LP again");
+    assert((await fetchPolePool(pole, polePoolAddr)).withdrawQueue.pendingLp.eq(new anchor.BN(0)));
+
+    await request();
+    freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    const reserve = await fetchReserve(provider, reserveState.address);
+    const supplyBalance = async () => new anchor.BN((await provider.connection.getTokenAccountBalance(freshPolePool.genericConfig.liquiditySupply)).value.amount);
+    const supplyBefore = await supplyBalance();
+    const refreshIxs = () => [
+      refreshReserveInstruction(reserveState.address, null),
+      refreshObligationInstruction(poleState.obligation, [reserveState.address], [reserveState.address]),
+    ];
+    //asks for far more than the request is short of, the program frees only the shortfall
+    await pole.provider.send(new Transaction().add(
+      ...refreshIxs(),
+      pole.instruction.unwindForWithdrawals(new anchor.BN(0), new anchor.BN(U64_MAX), {
+        accounts: {
+          polePool: polePoolAddr,
+          poleAuthority: poleAuthority,
+          liquiditySupply: freshPolePool.genericConfig.liquiditySupply,
+          portAccounts: await generatePortAccounts(
+            serumOrderBook.usdcMint, reserveState.address, reserve, freshPolePool),
+          keeper: provider.wallet.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          clock: SYSVAR_CLOCK_PUBKEY,
+        },
+      }),
+    ));
+    const reserved = (await fetchPolePool(pole, polePoolAddr)).reservedLiquidity;
+    //the collateral rounding may free a little more than the request is owed, only that is reserved
+    const freed = await supplyBalance();
+    assert(reserved.gtn(0) && reserved.lte(freed) && freed.sub(reserved).lten(10), "The freed liquidity should be reserved, got " + reserved.toString());
+
+    //a deposit goes on, leaving the reserved liquidity in the pole wallet for the request
+    await deposit(1_000_000, true)();
+    assert((await supplyBalance()).eq(reserved), "The deposit should not lend the reserved liquidity");
+    assert((await fetchPolePool(pole, polePoolAddr)).reservedLiquidity.eq(reserved));
+
+    await pole.provider.send(new Transaction().add(
+      ...refreshIxs(),
+      pole.instruction.processWithdrawRequest({
+        accounts: {
+          polePool: polePoolAddr,
+          poleAuthority: poleAuthority,
+          withdrawRequest: withdrawRequest,
+          lpMint: freshPolePool.genericConfig.lpMint,
+          lpEscrow: lpEscrow.publicKey,
+          liquiditySupply: freshPolePool.genericConfig.liquiditySupply,
+          settledLiquidity: settledLiquidity.publicKey,
+          poleFeeAccount: freshPolePool.genericConfig.feeReceiver,
+          reserve: reserveState.address,
+          obligation: poleState.obligation,
+          keeper: provider.wallet.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          clock: SYSVAR_CLOCK_PUBKEY,
+        },
+      }),
+    ));
+    //what was freed went to the request, give or take the collateral rounding
+    const supplyAfter = await supplyBalance();
+    assert(supplyAfter.lte(supplyBefore.addn(10)), "Only the shortfall should be withdrawn, kept " + supplyAfter.toString());
+    const settled = await pole.account.withdrawRequest.fetch(withdrawRequest);
+    assert(settled.liquidityAmount.gtn(0), "The request should be settled");
+    freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    assert(freshPolePool.withdrawQueue.pendingLp.eq(new anchor.BN(0)));
+    assert(freshPolePool.reservedLiquidity.eqn(0), "Nothing should stay reserved once the queue is empty");
+
+    const liquidityBefore = new anchor.BN((await provider.connection.getTokenAccountBalance(serumOrderBook.usdcTokenAccount)).value.amount);
+    await pole.rpc.claimWithdraw({
+      accounts: {
+        polePool: polePoolAddr,
+        poleAuthority: poleAuthority,
+        withdrawRequest: withdrawRequest,
+        settledLiquidity: settledLiquidity.publicKey,
+        userLiquidityWallet: serumOrderBook.usdcTokenAccount,
+        user: provider.wallet.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      },
+    });
+    const liquidityAfter = new anchor.BN((await provider.connection.getTokenAccountBalance(serumOrderBook.usdcTokenAccount)).value.amount);
+    assert(liquidityAfter.sub(liquidityBefore).eq(settled.liquidityAmount), "The settled liquidity should be claimed");
+    assert((await provider.connection.getAccountInfo(withdrawRequest)) === null, "The claimed request should be closed");
+  };
+
+  const realloc_current_pool = async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    const [poolRealloc, bump] = await poolReallocAddress(pole, polePoolAddr);
+    await assertPoleError(
+      pole.rpc.beginPoolRealloc(bump, {
+        accounts: {
+          polePool: polePoolAddr,
+          poolRealloc: poolRealloc,
+          owner: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        },
+      }),
+      "InvalidPoolState"
+    );
+    //the pool is still there
+    await fetchPolePool(pole, polePoolAddr);
+  };
+
+  const recover_port_state = (signer?: Keypair) => async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    const freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    const [poleAuthority] = await PublicKey.findProgramAddress(
+      [polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    const reserve = await fetchReserve(provider, reserveState.address);
+    const recover = pole.rpc.recoverPortState({
+      accounts: {
+        polePool: polePoolAddr,
+        poleAuthority: poleAuthority,
+        poleFeeAccount: freshPolePool.genericConfig.feeReceiver,
+        poleLiquidityAccounts: {
+          userLiquidityWallet: serumOrderBook.usdcTokenAccount,
+          poleLiquidityWallet: freshPolePool.genericConfig.liquiditySupply
+        },
+        poleLpAccounts: {
+          lpMint: freshPolePool.genericConfig.lpMint,
+          userLpWallet: await Token.getAssociatedTokenAddress(
+            ASSOCIATED_TOKEN_PROGRAM_ID,
+            TOKEN_PROGRAM_ID,
+            freshPolePool.genericConfig.lpMint,
+            provider.wallet.publicKey,
+          )
+        },
+        portAccounts: await generatePortAccounts(
+          serumOrderBook.usdcMint, reserveState.address, reserve, freshPolePool),
+        owner: signer ? signer.publicKey : provider.wallet.publicKey,
+        tokenProgram: freshPolePool.genericConfig.tokenProgram,
+        clock: SYSVAR_CLOCK_PUBKEY,
+      },
+      instructions: [refreshReserveInstruction(reserveState.address, null)],
+      signers: signer ? [signer] : []
+    });
+    if (signer) {
+      await assertPoleError(recover, "InvalidOwner");
+      return;
+    }
+    await recover;
+    const recovered = await fetchPolePool(pole, polePoolAddr);
+    assert(recovered.portState.leverage.eq(new anchor.BN(0)), "Recovery should leave the port state idle");
+    assert(recovered.portState.depositVerified.eq(new anchor.BN(0)));
+    assert(recovered.portState.redeemVerified.eq(new anchor.BN(0)));
+    assert(recovered.basicState.lpAmount.eq(freshPolePool.basicState.lpAmount), "Recovering an idle pool should not mint or burn LP");
+  };
+
+  const create_pool_with_decimals = (name: string, decimals: number, registered = true) => async () => {
+    const liquidityMint = await createMint(provider, provider.wallet.publicKey, decimals);
+    const [polePoolAddr, addressBump] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(name.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    const [poleAuthority, authorityBump] = await PublicKey.findProgramAddress(
+      [polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    const lpMint = Keypair.generate();
+    const liquiditySupply = Keypair.generate();
+    const portLpSupply = Keypair.generate();
+    const portSupply = Keypair.generate();
+    const feeReceiver = Keypair.generate();
+    const openOrders = Keypair.generate();
+    await pole.rpc.createPool(
+      authorityBump,
+      name,
+      addressBump,
+      {
+        liquidityCap: new anchor.BN(1000000000000000),
+        withdrawFeeBips: 10,
+        portIterate: 5,
+        portReservePercentage: 5,
+        portMinDeposit: new anchor.BN(100),
+        swapProgram: SWAP_PROGRAM_ID,
+        reserve: reserveState.address
+      },
+      {
+        accounts: {
+          polePool: polePoolAddr,
+          lpMint: lpMint.publicKey,
+          liquiditySupply: liquiditySupply.publicKey,
+          portLpSupply: portLpSupply.publicKey,
+          portSupply: portSupply.publicKey,
+          feeReceiver: feeReceiver.publicKey,
+          portOpenOrders: openOrders.publicKey,
+          liquidityMint: liquidityMint,
+          portLpMint: reserveState.collateralMintAccount,
+          portMint: serumOrderBook.portMint,
+          poleAuthority: poleAuthority,
+          user: provider.wallet.publicKey,
+          dexMarket: serumOrderBook.marketPortUSDC.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          dexProgram: SERUM_DEX_PROGRAM_ID,
+          poolRealloc: (await poolReallocAddress(pole, polePoolAddr))[0],
+          systemProgram: SystemProgram.programId,
+          rent: SYSVAR_RENT_PUBKEY,
+          clock: SYSVAR_CLOCK_PUBKEY,
+        },
+        remainingAccounts: registered
+          ? [{pubkey: (await registryPageAddress(pole, 0))[0], isSigner: false, isWritable: true}]
+          : [],
+        signers: [lpMint, feeReceiver, openOrders, liquiditySupply, portLpSupply, portSupply],
+      }
+    );
+    const lpMintInfo = await getMintInfo(provider, lpMint.publicKey);
+    assert(lpMintInfo.decimals === decimals, "LP decimals should match the liquidity mint " + lpMintInfo.decimals.toString());
+    const page = await pole.account.registryPage.fetch((await registryPageAddress(pole, 0))[0]);
+    const listed = page.pools.slice(0, page.poolCount.toNumber()).some(p => p.pool.equals(polePoolAddr));
+    assert(listed === registered, registered ? "Pool should be registered" : "A pool created without a registry page should not be listed");
+  };
+
+  //a pool whose liquidity mint is not the harvest market's pc sells PORT→USDC→liquidity
+  const sell_through_route = (name: string) => async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(name.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    const [poleAuthority] = await PublicKey.findProgramAddress(
+      [polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    const [stakingProgramAuthority] = await PublicKey.findProgramAddress(
+      [stakingPoolState.stakingPool.publicKey.toBuffer()], PORT_STAKING
+    );
+    const obligation = Keypair.generate();
+    const stakeAccount = Keypair.generate();
+    await pole.rpc.initPortAccounts({
+      accounts: {
+        polePool: polePoolAddr,
+        obligation: obligation.publicKey,
+        stakeAccount: stakeAccount.publicKey,
+        poleAuthority: poleAuthority,
+        user: provider.wallet.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        portLendingProgram: PORT_LENDING,
+        portStakingProgram: PORT_STAKING,
+        portLendingMarket: lendingMarket.publicKey,
+        stakingPool: stakingPoolState.stakingPool.publicKey,
+        rent: SYSVAR_RENT_PUBKEY,
+        clock: SYSVAR_CLOCK_PUBKEY,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [obligation, stakeAccount]
+    });
+    const freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    const liquidityMint = (await getTokenAccount(provider, freshPolePool.genericConfig.liquiditySupply)).mint;
+
+    //a liquidity/USDC market with asks to buy the liquidity from
+    const liquidityAccount = await createTokenAccount(provider, liquidityMint, provider.wallet.publicKey);
+    await provider.send(new Transaction().add(Token.createMintToInstruction(
+      TOKEN_PROGRAM_ID, liquidityMint, liquidityAccount, provider.wallet.publicKey, [], 100_000_000_000
+    )));
+    const maker = await fundAccount({
+      provider,
+      mints: [
+        {tokenAccount: liquidityAccount, mint: liquidityMint, amount: 100_000_000_000, decimals: 6},
+        {tokenAccount: serumOrderBook.usdcTokenAccount, mint: serumOrderBook.usdcMint, amount: 100_000_000_000, decimals: 6},
+      ],
+    });
+    const routeMarket = await setupMarket({
+      provider,
+      marketMaker: {
+        account: maker.account,
+        baseTokenAccount: maker.tokens.get(liquidityMint.toString()),
+        quoteTokenAccount: maker.tokens.get(serumOrderBook.usdcMint.toString()),
+      },
+      baseMint: liquidityMint,
+      quoteMint: serumOrderBook.usdcMint,
+      bids: [[0.99, 1000]],
+      asks: [[1.01, 1000]],
+    });
+
+    const [sellRoute, sellRouteBump] = await PublicKey.findProgramAddress(
+      [Buffer.from("sell_route"), polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    const routeOpenOrders = Keypair.generate();
+    const routeWallet = Keypair.generate();
+    await pole.rpc.initSellRoute(sellRouteBump, {
+      accounts: {
+        polePool: polePoolAddr,
+        poleAuthority: poleAuthority,
+        sellRoute: sellRoute,
+        routeOpenOrders: routeOpenOrders.publicKey,
+        routeMarket: routeMarket.address,
+        routeWallet: routeWallet.publicKey,
+        routeMint: serumOrderBook.usdcMint,
+        owner: provider.wallet.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        dexProgram: SERUM_DEX_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: SYSVAR_RENT_PUBKEY,
+      },
+      signers: [routeOpenOrders, routeWallet]
+    });
+    const route = await pole.account.sellRoute.fetch(sellRoute);
+    assert(route.pool.equals(polePoolAddr));
+    assert(route.openOrders.equals(routeOpenOrders.publicKey));
+    assert(route.wallet.equals(routeWallet.publicKey), "The route should keep the wallet it created");
+
+    //the pool has nothing staked, hand it PORT to sell
+    await provider.send(new Transaction().add(Token.createTransferInstruction(
+      TOKEN_PROGRAM_ID, serumOrderBook.portTokenAccount, freshPolePool.portConfig.portSupply, provider.wallet.publicKey, [], 10_000_000
+    )));
+    const routeAccounts = {
+      ...(await generateSerumAccounts(provider, routeMarket.address, freshPolePool)),
+      openOrders: routeOpenOrders.publicKey,
+    };
+    const claimAndSellAccs = {
+      accounts: {
+        polePool: polePoolAddr,
+        poleAuthority: poleAuthority,
+        liquiditySupply: freshPolePool.genericConfig.liquiditySupply,
+        harvesterLiquidityWallet: liquidityAccount,
+        portSupply: freshPolePool.portConfig.portSupply,
+        marketAccounts: await generateSerumAccounts(
+          provider, serumOrderBook.marketPortUSDC.address, freshPolePool
+        ),
+        portAccounts: {
+          portStakingProgram: PORT_STAKING,
+          stakeAccount: stakeAccount.publicKey,
+          stakingPool: stakingPoolState.stakingPool.publicKey,
+          rewardSupply: stakingPoolState.rewardTokenAccount.publicKey,
+          stakingProgramAuthority: stakingProgramAuthority,
+        },
+        portMint: serumOrderBook.portMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        dexProgram: SERUM_DEX_PROGRAM_ID,
+        swapProgram: SWAP_PROGRAM_ID,
+        clock: SYSVAR_CLOCK_PUBKEY,
+        rent: SYSVAR_RENT_PUBKEY,
+      },
+    };
+    const routeRemaining = [
+      {pubkey: sellRoute, isWritable: false, isSigner: false},
+      ...[
+        routeAccounts.market,
+        routeAccounts.openOrders,
+        routeAccounts.requestQueue,
+        routeAccounts.eventQueue,
+        routeAccounts.bids,
+        routeAccounts.asks,
+        routeAccounts.coinVault,
+        routeAccounts.pcVault,
+      ].map(pubkey => ({pubkey, isWritable: true, isSigner: false})),
+      {pubkey: routeAccounts.vaultSigner, isWritable: false, isSigner: false},
+    ];
+
+    //any other wallet of the pool authority is not the route's
+    const otherWallet = await createTokenAccount(provider, serumOrderBook.usdcMint, poleAuthority);
+    await assertPoleError(pole.rpc.claimAndSell({
+      ...claimAndSellAccs,
+      remainingAccounts: [...routeRemaining, {pubkey: otherWallet, isWritable: true, isSigner: false}],
+    }), "InvalidSellRoute");
+    await assertPoleError(pole.rpc.claimAndSell(claimAndSellAccs), "InvalidSellRoute");
+
+    const before = await tokenBalance(freshPolePool.genericConfig.liquiditySupply);
+    await pole.rpc.claimAndSell({
+      ...claimAndSellAccs,
+      remainingAccounts: [...routeRemaining, {pubkey: routeWallet.publicKey, isWritable: true, isSigner: false}],
+    });
+    const after = await tokenBalance(freshPolePool.genericConfig.liquiditySupply);
+    assert(after.gt(before), "Both hops should land liquidity in liquidity_supply");
+    assert((await tokenBalance(freshPolePool.portConfig.portSupply)).lt(new anchor.BN(10_000_000)), "The PORT should be sold");
+  };
+
+  //settles both open orders of a routed pool, then moves it to a new harvest market and retires that
+  const manage_harvest_markets = (name: string) => async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(name.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    const [poleAuthority] = await PublicKey.findProgramAddress(
+      [polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    const [sellRoute] = await PublicKey.findProgramAddress(
+      [Buffer.from("sell_route"), polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    const freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    const route = await pole.account.sellRoute.fetch(sellRoute);
+    const settle = (coinWallet: PublicKey, pcWallet: PublicKey, marketAccounts: any) => pole.rpc.settleOpenOrders({
+      accounts: {
+        polePool: polePoolAddr,
+        poleAuthority: poleAuthority,
+        coinWallet: coinWallet,
+        pcWallet: pcWallet,
+        marketAccounts: marketAccounts,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        dexProgram: SERUM_DEX_PROGRAM_ID,
+      },
+      remainingAccounts: [{pubkey: sellRoute, isWritable: false, isSigner: false}],
+    });
+    const harvestMarket = await generateSerumAccounts(provider, serumOrderBook.marketPortUSDC.address, freshPolePool);
+    const routeMarket = {
+      ...(await generateSerumAccounts(provider, route.market, freshPolePool)),
+      openOrders: route.openOrders,
+    };
+
+    await assertPoleError(
+      settle(freshPolePool.genericConfig.liquiditySupply, route.wallet, harvestMarket), "InvalidSettleAccounts");
+    await assertPoleError(
+      settle(freshPolePool.portConfig.portSupply, freshPolePool.genericConfig.liquiditySupply, harvestMarket),
+      "InvalidSettleAccounts");
+    await settle(freshPolePool.portConfig.portSupply, route.wallet, harvestMarket);
+    await assertPoleError(
+      settle(freshPolePool.portConfig.portSupply, route.wallet, routeMarket), "InvalidSettleAccounts");
+    await settle(freshPolePool.genericConfig.liquiditySupply, route.wallet, routeMarket);
+
+    const legacyOpenOrders = freshPolePool.serumConfig.portOpenOrders;
+    const portOpenOrders = Keypair.generate();
+    await pole.rpc.migrateMarket({
+      accounts: {
+        polePool: polePoolAddr,
+        poleAuthority: poleAuthority,
+        legacyOpenOrders: legacyOpenOrders,
+        legacyMarket: serumOrderBook.marketPortUSDC.address,
+        portOpenOrders: portOpenOrders.publicKey,
+        dexMarket: serumOrderBook.marketPortUSDC.address,
+        owner: provider.wallet.publicKey,
+        dexProgram: SERUM_DEX_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: SYSVAR_RENT_PUBKEY,
+      },
+      signers: [portOpenOrders],
+    });
+    assert((await fetchPolePool(pole, polePoolAddr)).serumConfig.portOpenOrders.equals(portOpenOrders.publicKey));
+    assert.isNull(await provider.connection.getAccountInfo(legacyOpenOrders), "The legacy open orders should be closed");
+
+    await pole.rpc.closeOpenOrders({
+      accounts: {
+        polePool: polePoolAddr,
+        poleAuthority: poleAuthority,
+        portOpenOrders: portOpenOrders.publicKey,
+        dexMarket: serumOrderBook.marketPortUSDC.address,
+        owner: provider.wallet.publicKey,
+        dexProgram: SERUM_DEX_PROGRAM_ID,
+      },
+    });
+    assert((await fetchPolePool(pole, polePoolAddr)).serumConfig.portOpenOrders.equals(PublicKey.default));
+    assert.isNull(await provider.connection.getAccountInfo(portOpenOrders.publicKey), "Retired open orders should be closed");
+  };
+
+  //closes an empty routed pool, the sell route accounts go with it
+  const close_pool = (name: string) => async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(name.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    const [poleAuthority] = await PublicKey.findProgramAddress(
+      [polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    const [sellRoute] = await PublicKey.findProgramAddress(
+      [Buffer.from("sell_route"), polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    const [subReward] = await PublicKey.findProgramAddress(
+      [Buffer.from("sub_reward"), polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    const [feeSplit] = await PublicKey.findProgramAddress(
+      [Buffer.from("fee_split"), polePoolAddr.toBuffer()],
+      pole.programId
+    );
+    const [registryPage] = await registryPageAddress(pole, 0);
+    const freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    const route = await pole.account.sellRoute.fetch(sellRoute);
+    const closePoolAccs = {
+      accounts: {
+        polePool: polePoolAddr,
+        poleAuthority: poleAuthority,
+        lpMint: freshPolePool.genericConfig.lpMint,
+        liquiditySupply: freshPolePool.genericConfig.liquiditySupply,
+        feeReceiver: freshPolePool.genericConfig.feeReceiver,
+        portLpSupply: freshPolePool.portConfig.portLpSupply,
+        portSupply: freshPolePool.portConfig.portSupply,
+        ownerPortWallet: serumOrderBook.portTokenAccount,
+        portOpenOrders: freshPolePool.serumConfig.portOpenOrders,
+        dexMarket: serumOrderBook.marketPortUSDC.address,
+        obligation: freshPolePool.portConfig.obligation,
+        sellRoute: sellRoute,
+        subReward: subReward,
+        feeSplit: feeSplit,
+        registryPage: registryPage,
+        owner: provider.wallet.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        dexProgram: SERUM_DEX_PROGRAM_ID,
+      },
+    };
+
+    //the route accounts can not be left behind
+    await assertPoleError(pole.rpc.closePool(closePoolAccs), "InvalidSellRoute");
+    await pole.rpc.closePool({
+      ...closePoolAccs,
+      remainingAccounts: [route.openOrders, route.market, route.wallet, serumOrderBook.usdcTokenAccount]
+        .map(pubkey => ({pubkey, isWritable: true, isSigner: false})),
+    });
+    for (const closed of [polePoolAddr, sellRoute, route.openOrders, route.wallet, freshPolePool.genericConfig.liquiditySupply]) {
+      assert.isNull(await provider.connection.getAccountInfo(closed), closed.toString() + " should be closed");
+    }
+    const page = await pole.account.registryPage.fetch(registryPage);
+    const entry = page.pools.slice(0, page.poolCount.toNumber()).find(p => p.pool.equals(polePoolAddr));
+    assert(entry === undefined || entry.status.eq(new anchor.BN(2)), "A listed pool should be deprecated");
+  };
+
+  const add_registry_page = (signer?: Keypair) => async () => {
+    const [registry] = await registryAddress(pole);
+    const pageCount = (await pole.account.poolRegistry.fetch(registry)).pageCount.toNumber();
+    const [registryPage, bump] = await registryPageAddress(pole, pageCount);
+    const add = pole.rpc.addRegistryPage(bump, {
+      accounts: {
+        registry: registry,
+        registryPage: registryPage,
+        owner: (signer ?? provider.wallet).publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: signer ? [signer] : [],
+    });
+    if (signer) {
+      await assertPoleError(add, "InvalidOwner");
+      return;
+    }
+    await add;
+    const page = await pole.account.registryPage.fetch(registryPage);
+    assert(page.index.eq(new anchor.BN(pageCount)));
+    assert(page.poolCount.eq(new anchor.BN(0)));
+    assert((await pole.account.poolRegistry.fetch(registry)).pageCount.eq(new anchor.BN(pageCount + 1)));
+  };
+
+  const register_pool = (pageIndex: number, expectedError?: string) => async () => {
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    const freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    const [registryPage] = await registryPageAddress(pole, pageIndex);
+    const register = pole.rpc.registerPool({
+      accounts: {
+        polePool: polePoolAddr,
+        liquiditySupply: freshPolePool.genericConfig.liquiditySupply,
+        registry: (await registryAddress(pole))[0],
+        registryPage: registryPage,
+        owner: provider.wallet.publicKey,
+        clock: SYSVAR_CLOCK_PUBKEY,
+      },
+    });
+    if (expectedError) {
+      await assertPoleError(register, expectedError);
+      return;
+    }
+    await register;
+    const page = await pole.account.registryPage.fetch(registryPage);
+    const entry = page.pools.slice(0, page.poolCount.toNumber()).find(p => p.pool.equals(polePoolAddr));
+    assert(entry !== undefined, "Pool should be registered");
+    assert(entry.liquidityMint.equals(serumOrderBook.usdcMint));
+    assert(entry.reserve.equals(reserveState.address));
+
+    //backfilled twice here, drop the copy again
+    await pole.rpc.unregisterPool(polePoolAddr, {
+      accounts: {
+        registry: (await registryAddress(pole))[0],
+        registryPage: registryPage,
+        owner: provider.wallet.publicKey,
+      },
+    });
+    const unregistered = await pole.account.registryPage.fetch(registryPage);
+    assert(unregistered.pools.slice(0, unregistered.poolCount.toNumber()).every(p => !p.pool.equals(polePoolAddr)));
+  };
+
   const sleepTest = (ms: number) => it('Sleep', async () => {
     await sleep(ms)
   });
+  it('Only the upgrade authority can set up the registry', async () => {
+    const signer = Keypair.generate();
+    await provider.send(new Transaction().add(SystemProgram.transfer({
+      fromPubkey: provider.wallet.publicKey,
+      toPubkey: signer.publicKey,
+      lamports: 100_000_000,
+    })));
+    await assertPoleError(createRegistry(pole, signer), "InvalidUpgradeAuthority");
+  });
+  it('Set up registry', async () => {
+    await createRegistry(pole);
+  });
   it('Set up pole', async () => {
     poleState = await createPolePool(pole, lendingMarket.publicKey, serumOrderBook, stakingPoolState, reserveState);
+    const [polePoolAddr] = await PublicKey.findProgramAddress(
+      [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
+      pole.programId
+    );
+    const freshPolePool = await fetchPolePool(pole, polePoolAddr);
+    assert(freshPolePool.layoutVersion.eq(new anchor.BN(8)), "New pools should use the current layout");
+    const registryPage = await pole.account.registryPage.fetch((await registryPageAddress(pole, 0))[0]);
+    const entry = registryPage.pools.slice(0, registryPage.poolCount.toNumber()).find(p => p.pool.equals(polePoolAddr));
+    assert(entry !== undefined, "Pool should be registered");
+    assert(entry.liquidityMint.equals(serumOrderBook.usdcMint));
+    assert(entry.reserve.equals(reserveState.address));
+    assert(entry.status.eq(new anchor.BN(1)));
   });
+  it('Only the registry owner can add a page', add_registry_page(Keypair.generate()));
+  it('Be able to add a registry page', add_registry_page());
+  it('A registered pool is not registered twice in a page', register_pool(0, "PoolAlreadyRegistered"));
+  it('Be able to register and unregister a pool', register_pool(1));
+  it('LP mint has the decimals of a 6-decimal asset', create_pool_with_decimals("DEC6", 6));
+  it('LP mint has the decimals of an 8-decimal asset', create_pool_with_decimals("DEC8", 8));
+  it('LP mint has the decimals of a 9-decimal asset, created without a registry page', create_pool_with_decimals("DEC9", 9, false));
+  it('Be able to claim and sell through a sell route', sell_through_route("DEC6"));
+  it('Be able to settle, migrate and close the harvest open orders', manage_harvest_markets("DEC6"));
+  it('Be able to close an empty pool with its sell route', close_pool("DEC6"));
   it('Be able to deposit into Pole successfully',deposit(5_000_000_000_000_00, false));
+  it('Be able to change harvest config', change_harvest_config(100, 0, 10));
+  it('Be able to manage the allowlist', manage_allowlist);
+  it('Be able to change fee schedule', change_fee_schedule([[0, 50], [1_000_000, 0]]));
   it('Be able to claim and sell',claim_and_sell);
+  it('Be able to sell the sub reward once per harvest interval', sell_sub_reward);
+  it('Be able to turn allowlist mode on', change_allowlist_mode(true));
+  it('A wallet off the allowlist can not deposit', deposit(1_000_000, true, 0, "NotAllowlisted"));
+  it('Be able to allowlist the user', allowlist_user(true));
+  it('An allowlisted wallet can deposit', deposit(1_000_000, true));
+  it('Be able to take the user off the allowlist', allowlist_user(false));
+  it('Redeems do not check the allowlist', withdraw(10_000));
+  it('A removed wallet can not deposit again', deposit(1_000_000, true, 0, "NotAllowlisted"));
+  it('Be able to turn allowlist mode off', change_allowlist_mode(false));
+  it('Deposit fails below min_lp_out', deposit(100_000, true, U64_MAX, "LPOutTooSmall"));
+  it('Withdraw fails below min_liquidity_out', withdraw(10_000, U64_MAX, "LiquidityOutTooSmall"));
   it('Be able to withdraw', withdraw(19_000_000_000_000));
   it('Be able to claim and sell',claim_and_sell);
   it('Be able to deposit into Pole successfully',deposit(100_000, true));
@@ -402,6 +1709,7 @@ describe('pole', () => {
   sleepTest(10_000);
   it('Be able to claim and sell',claim_and_sell);
   it('Claim and withdraw (no unrolling)', withdraw(10_000));
+  it('A redeem refreshes a stale reserve and prices on the refreshed rate', withdraw(10_000, 0, undefined, true, undefined, true));
   it('Be able to claim and sell',claim_and_sell);
   it('Be able to deposit into Pole successfully', deposit(100, true));
   it('Be able to claim and sell',claim_and_sell);
@@ -409,7 +1717,28 @@ describe('pole', () => {
   it('Be able to claim and sell',claim_and_sell);
   it('Be able to deposit into Pole successfully', deposit(100_000_000, true));
   it('Be able to claim and sell',claim_and_sell);
+  it('Be able to auto compound', auto_compound);
+  it('Zap in fails below min_liquidity_out', zap_in(10_000_000, U64_MAX, "SwapOutTooSmall"));
+  it('Be able to zap PORT into a deposit', zap_in(10_000_000, 50_000_000));
+  it('Zap out fails below its min output', withdraw(100_000_000, 0, "SwapOutTooSmall", true, U64_MAX));
+  it('Be able to zap a redeem out into PORT', withdraw(100_000_000, 0, undefined, true, 1_000_000));
+  it('Only the owner can recover the port state', recover_port_state(Keypair.generate()));
+  it('Recovering an idle port state is a no-op', recover_port_state());
+  it('A pool of the current size is not reallocated', realloc_current_pool);
+  it('Be able to request, cancel, process and claim a queued withdrawal', withdraw_queue);
+  it('Be able to change fee schedule', change_fee_schedule([[0, 100], [1, 10]]));
+  it('A tracked redeem pays the fee tier of its holding period', redeem_fee_tier(true, 10));
+  it('An untracked redeem pays the first fee tier', redeem_fee_tier(false, 100));
+  it('Be able to restore the flat withdraw fee', change_fee_schedule([]));
   it('Be able to deposit into Pole successfully', deposit(1000_000_000, true));
+  it('Be able to change the per-wallet cap', change_wallet_cap(1_000_000));
+  it('Deposit fails over the per-wallet cap', deposit(1_000_000, true, 0, "MeetWalletDepositLimit"));
+  it('Be able to lift the per-wallet cap', change_wallet_cap(0));
+  it("A delegate's deposit is tracked on the wallet owner's position", delegated_deposit);
   it('Be able to Change liquidity cap', change_liquidity_cap(1_000_000));
+  it('Be able to set a referrer', set_referrer);
+  it('A redeem pays the referrer its share of the fee', withdraw(1_000_000));
+  it('Be able to split fees', split_fees);
+  it('Be able to sweep a stray account but not the pool accounts', sweep);
   //would fail it('Be able to deposit into Pole successfully', deposit(1_500_000, true));
 });
@@ -2,8 +2,9 @@ import { BN, Program, Provider } from "@project-serum/anchor";
 import { Market } from "@project-serum/serum";
 import { TOKEN_PROGRAM_ID } from "@solana/spl-token";
 import { Keypair, PublicKey, SystemProgram, SYSVAR_CLOCK_PUBKEY, SYSVAR_RENT_PUBKEY, Transaction } from "@solana/web3.js"
-import { Pole } from "../../target/types/pole";
-import { PORT_LENDING, PORT_STAKING, SERUM_DEX_PROGRAM_ID, SWAP_PROGRAM_ID } from "../constants";
+import { assert } from "chai";
+import { IDL, Pole } from "../../target/types/pole";
+import { BPF_LOADER_UPGRADEABLE_PROGRAM_ID, PORT_LENDING, PORT_STAKING, SERUM_DEX_PROGRAM_ID, SWAP_PROGRAM_ID } from "../constants";
 import { ReserveState } from "../port";
 import { parseReserve, Reserve } from "../state/reserve";
 
@@ -37,6 +38,25 @@ export const fetchPolePool = async (program: Program<Pole>, address: PublicKey):
   return polePoolData;
 }
 
+// Resolves a PoleError name to its code through the IDL and expects `promise` to fail with it.
+// Transactions sent through provider.send only surface the code as hex in the logs.
+export const assertPoleError = async (promise: Promise<unknown>, name: string): Promise<void> => {
+  const code = IDL.errors.find(e => e.name === name)?.code;
+  assert(code !== undefined, "Unknown pole error " + name);
+  try {
+    await promise;
+  } catch (err) {
+    const hex = "0x" + code.toString(16);
+    const logs: string[] = err.logs ?? [];
+    assert(
+      err.code === code || err.toString().includes(hex) || logs.some(log => log.includes(hex)),
+      "Expected " + name + " but got " + err.toString()
+    );
+    return;
+  }
+  assert.fail("Expected " + name + " but the transaction succeeded");
+}
+
 export const fetchReserve = async (provider: Provider, address: PublicKey): Promise<Reserve> => {
   const reserveRaw = await provider.connection.getAccountInfo(address);
   if (!reserveRaw || !reserveRaw.data) {
@@ -51,6 +71,74 @@ export const fetchReserve = async (provider: Provider, address: PublicKey): Prom
   return parsedReserve.data
 }
 
+export const registryAddress = async (pole: Program<Pole>): Promise<[PublicKey, number]> =>
+  PublicKey.findProgramAddress(
+    [Buffer.from("registry"), pole.programId.toBuffer()],
+    pole.programId
+  );
+
+export const registryPageAddress = async (pole: Program<Pole>, index: number): Promise<[PublicKey, number]> =>
+  PublicKey.findProgramAddress(
+    [Buffer.from("registry"), pole.programId.toBuffer(), new BN(index).toArrayLike(Buffer, "le", 8)],
+    pole.programId
+  );
+
+export const poolReallocAddress = async (pole: Program<Pole>, polePool: PublicKey): Promise<[PublicKey, number]> =>
+  PublicKey.findProgramAddress(
+    [Buffer.from("realloc"), polePool.toBuffer()],
+    pole.programId
+  );
+
+// pole is deployed with the upgradeable loader (see scripts/e2e.sh), the provider wallet is its upgrade authority
+export const createRegistry = async (pole: Program<Pole>, owner?: Keypair): Promise<PublicKey> => {
+  const [registry, bump] = await registryAddress(pole);
+  const [registryPage, pageBump] = await registryPageAddress(pole, 0);
+  const [programData] = await PublicKey.findProgramAddress(
+    [pole.programId.toBuffer()],
+    BPF_LOADER_UPGRADEABLE_PROGRAM_ID
+  );
+  await pole.rpc.initRegistry(
+    bump,
+    pageBump,
+    {
+      accounts: {
+        registry: registry,
+        registryPage: registryPage,
+        program: pole.programId,
+        programData: programData,
+        owner: (owner ?? pole.provider.wallet).publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: owner ? [owner] : [],
+    }
+  );
+  return registry;
+}
+
+export const userPositionAddress = async (pole: Program<Pole>, polePool: PublicKey): Promise<[PublicKey, number]> =>
+  PublicKey.findProgramAddress(
+    [Buffer.from("position"), polePool.toBuffer(), pole.provider.wallet.publicKey.toBuffer()],
+    pole.programId
+  );
+
+// keyed by the owner of userLiquidityWallet, the provider wallet here
+export const createUserPosition = async (pole: Program<Pole>, polePool: PublicKey, userLiquidityWallet: PublicKey): Promise<PublicKey> => {
+  const [userPosition, bump] = await userPositionAddress(pole, polePool);
+  await pole.rpc.createUserPosition(
+    bump,
+    {
+      accounts: {
+        polePool: polePool,
+        userPosition: userPosition,
+        userLiquidityWallet: userLiquidityWallet,
+        user: pole.provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      }
+    }
+  );
+  return userPosition;
+}
+
 export interface SerumState {
   marketPortUSDC: Market;
   marketMaker: { tokens: Map<string, PublicKey>; account: Keypair; };
@@ -81,14 +169,14 @@ export const createPolePool = async (
   const openOrders = Keypair.generate();
   const obligation = Keypair.generate();
   const stakeAccount = Keypair.generate();
-  const [poleAuthority, authority_bump] = await PublicKey.findProgramAddress(
-    [],
-    pole.programId
-  );
   const [polePool, address_bump] = await PublicKey.findProgramAddress(
     [Uint8Array.from(poolName.split("").map(c => c.charCodeAt(0)))],
     pole.programId
   );
+  const [poleAuthority, authority_bump] = await PublicKey.findProgramAddress(
+    [polePool.toBuffer()],
+    pole.programId
+  );
   
   const liquiditySupply = await Keypair.generate();
   const portLpSupply = await Keypair.generate();
@@ -123,9 +211,12 @@ export const createPolePool = async (
         dexMarket: serumOrderBook.marketPortUSDC.publicKey,
         tokenProgram: TOKEN_PROGRAM_ID,
         dexProgram: SERUM_DEX_PROGRAM_ID,
+        poolRealloc: (await poolReallocAddress(pole, polePool))[0],
         systemProgram: SystemProgram.programId,
         rent: SYSVAR_RENT_PUBKEY,
+        clock: SYSVAR_CLOCK_PUBKEY,
       },
+      remainingAccounts: [{pubkey: (await registryPageAddress(pole, 0))[0], isSigner: false, isWritable: true}],
       signers: [lpMint,
         feeReceiver, openOrders,liquiditySupply,portLpSupply,portSupply
       ],